@@ -1,4 +1,4 @@
-use crate::config::schema::SpiderConfig;
+use crate::config::schema::{SpiderConfig, VisitedStrategy};
 use anyhow::{Context, Result};
 use std::collections::HashSet;
 use std::fs;
@@ -95,6 +95,27 @@ impl ConfigLoader {
         if child.max_depth.is_some() {
             parent.max_depth = child.max_depth;
         }
+        if child.search.enabled {
+            parent.search = child.search;
+        }
+        if child.store_raw.enabled {
+            parent.store_raw = child.store_raw;
+        }
+        if child.output.warc.enabled {
+            parent.output.warc = child.output.warc;
+        }
+        if child.dedupe_by_canonical {
+            parent.dedupe_by_canonical = true;
+        }
+        if child.dedupe.skip_duplicates {
+            parent.dedupe = child.dedupe;
+        }
+        if child.visited.strategy != VisitedStrategy::Memory {
+            parent.visited = child.visited;
+        }
+        if child.schedule.is_some() {
+            parent.schedule = child.schedule;
+        }
 
         for (key, val) in child.selectors {
             parent.selectors.insert(key, val);
@@ -62,6 +62,246 @@ pub struct SpiderConfig {
     /// Optional path to a parent configuration file to inherit from
     #[serde(default)]
     pub extends: Option<String>,
+
+    /// Full-text search indexing of results
+    #[serde(default)]
+    pub search: SearchConfig,
+
+    /// Raw response archiving, so results can be re-extracted offline
+    #[serde(default)]
+    pub store_raw: RawStoreConfig,
+
+    /// Where crawl output is written besides the state database
+    #[serde(default)]
+    pub output: OutputConfig,
+
+    /// Store pages that declare a different `<link rel="canonical">` under
+    /// that URL, so duplicates yield a single result
+    #[serde(default)]
+    pub dedupe_by_canonical: bool,
+
+    /// Exact and near-duplicate content detection
+    #[serde(default)]
+    pub dedupe: DedupeConfig,
+
+    /// How visited URLs are remembered during a crawl
+    #[serde(default)]
+    pub visited: VisitedConfig,
+
+    /// When `spider-cli daemon` runs this config: a cron expression in UTC
+    /// (`0 3 * * *`) or an interval (`every 6h`)
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+impl SpiderConfig {
+    /// Selector queries keyed by field name; crawls without selectors
+    /// extract the page title.
+    pub fn selector_queries(&self) -> HashMap<String, String> {
+        if self.selectors.is_empty() {
+            let mut s = HashMap::new();
+            s.insert("title".to_string(), "title".to_string());
+            return s;
+        }
+        self.selectors
+            .iter()
+            .map(|(k, v)| (k.clone(), v.to_query_string()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SearchConfig {
+    /// Index extracted values so `spider-cli search` can find them
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Also index the visible text of every page (grows the database)
+    #[serde(default)]
+    pub page_text: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeConfig {
+    /// Skip extraction for pages whose content matches an earlier page;
+    /// they are still recorded with the page they duplicate
+    #[serde(default)]
+    pub skip_duplicates: bool,
+
+    /// Pages whose text SimHashes differ in at most this many bits count as
+    /// near duplicates; 0 only matches identical text
+    #[serde(default = "default_max_distance")]
+    pub max_distance: u32,
+}
+
+impl Default for DedupeConfig {
+    fn default() -> Self {
+        Self {
+            skip_duplicates: false,
+            max_distance: default_max_distance(),
+        }
+    }
+}
+
+fn default_max_distance() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisitedConfig {
+    #[serde(default)]
+    pub strategy: VisitedStrategy,
+
+    /// Directory for the persisted filters of the `bloom` strategy
+    #[serde(default = "default_visited_path")]
+    pub path: String,
+
+    /// URLs the first `bloom` filter is sized for; later ones double
+    #[serde(default = "default_expected_urls")]
+    pub expected_urls: u64,
+
+    /// Share of unvisited URLs the `bloom` strategy may wrongly skip
+    #[serde(default = "default_false_positive_rate")]
+    pub false_positive_rate: f64,
+
+    /// Recently visited URLs the `sqlite` strategy keeps in memory
+    #[serde(default = "default_lru_capacity")]
+    pub lru_capacity: usize,
+}
+
+impl Default for VisitedConfig {
+    fn default() -> Self {
+        Self {
+            strategy: VisitedStrategy::default(),
+            path: default_visited_path(),
+            expected_urls: default_expected_urls(),
+            false_positive_rate: default_false_positive_rate(),
+            lru_capacity: default_lru_capacity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisitedStrategy {
+    /// Every URL in a hash set; exact, but memory grows with the crawl
+    #[default]
+    Memory,
+    /// A scalable Bloom filter saved between runs; small, rarely skips an
+    /// unvisited URL
+    Bloom,
+    /// Look URLs up in the state database behind an LRU cache
+    Sqlite,
+}
+
+fn default_visited_path() -> String {
+    "visited".to_string()
+}
+
+fn default_expected_urls() -> u64 {
+    1_000_000
+}
+
+fn default_false_positive_rate() -> f64 {
+    0.001
+}
+
+fn default_lru_capacity() -> usize {
+    100_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawStoreConfig {
+    /// Keep every response body, status and headers
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory holding the content-addressed bodies and per-crawl manifests
+    #[serde(default = "default_raw_store_path")]
+    pub path: String,
+
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+impl Default for RawStoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_raw_store_path(),
+            compression: Compression::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    Zstd,
+    Gzip,
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OutputConfig {
+    /// WARC 1.1 archive of every fetched page
+    #[serde(default)]
+    pub warc: WarcConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarcConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory the `.warc.gz` files are written to
+    #[serde(default = "default_warc_path")]
+    pub path: String,
+
+    /// File name prefix, followed by crawl ID, timestamp and serial number
+    #[serde(default = "default_warc_prefix")]
+    pub prefix: String,
+
+    /// Start a new file once the current one reaches this size
+    #[serde(default = "default_warc_max_size")]
+    pub max_file_size_mb: u64,
+
+    /// Compress every record as its own gzip member
+    #[serde(default = "default_true")]
+    pub gzip: bool,
+}
+
+impl Default for WarcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_warc_path(),
+            prefix: default_warc_prefix(),
+            max_file_size_mb: default_warc_max_size(),
+            gzip: true,
+        }
+    }
+}
+
+fn default_warc_path() -> String {
+    "warc".to_string()
+}
+
+fn default_warc_prefix() -> String {
+    "spider".to_string()
+}
+
+fn default_warc_max_size() -> u64 {
+    1000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_raw_store_path() -> String {
+    "raw_store".to_string()
 }
 
 fn default_concurrency() -> usize {
@@ -0,0 +1,126 @@
+//! Progress a crawl reports while it runs, for whoever is watching: the CLI's
+//! summary, the dashboard's log and its live feeds.
+//!
+//! The state store is written by the crawl itself, not from these events:
+//! they are only sent when something watches (`Crawler::with_events`), and a
+//! page's result must be saved before the page counts as visited, which a
+//! consumer at the other end of a channel couldn't promise.
+
+use crate::state::CrawlStatus;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc;
+
+/// Events buffered before a slow consumer starts holding the crawl up.
+pub const BUFFER: usize = 256;
+
+/// A channel for `Crawler::with_events`.
+pub fn channel() -> (mpsc::Sender<CrawlEvent>, mpsc::Receiver<CrawlEvent>) {
+    mpsc::channel(BUFFER)
+}
+
+/// Something that happened in a crawl, tagged by `type` when serialized.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CrawlEvent {
+    /// A page the crawl means to visit: a seed, or a link to an unvisited
+    /// page on the crawled site
+    Queued {
+        url: String,
+    },
+    /// A response arrived
+    Fetched {
+        url: String,
+        status: u16,
+        bytes: u64,
+        /// Until the response headers arrived, when it was measured
+        latency_ms: Option<u64>,
+    },
+    /// A page yielded data for the crawl's selectors
+    Extracted {
+        url: String,
+        fields: Map<String, Value>,
+    },
+    Skipped {
+        url: String,
+        reason: SkipReason,
+    },
+    /// A page got no response, or the crawl itself failed when `url` is
+    /// `None`
+    Failed {
+        url: Option<String>,
+        error: String,
+    },
+    /// Always the last event of a run
+    Finished {
+        summary: Summary,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Visited by an earlier run of the crawl
+    Visited,
+    /// A conditional request found the page unchanged
+    NotModified,
+    /// Same or nearly the same content as a page crawled before
+    Duplicate,
+}
+
+/// How a run ended and what it got through.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub status: CrawlStatus,
+    pub fetched: u64,
+    pub extracted: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} fetched, {} extracted, {} skipped, {} failed",
+            self.status.as_str(),
+            self.fetched,
+            self.extracted,
+            self.skipped,
+            self.failed
+        )
+    }
+}
+
+/// Running counts of the events a crawl has emitted.
+#[derive(Default)]
+pub(super) struct Tally {
+    fetched: AtomicU64,
+    extracted: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Tally {
+    pub fn count(&self, event: &CrawlEvent) {
+        let counter = match event {
+            CrawlEvent::Fetched { .. } => &self.fetched,
+            CrawlEvent::Extracted { .. } => &self.extracted,
+            CrawlEvent::Skipped { .. } => &self.skipped,
+            CrawlEvent::Failed { url: Some(_), .. } => &self.failed,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self, status: CrawlStatus) -> Summary {
+        Summary {
+            status,
+            fetched: self.fetched.load(Ordering::Relaxed),
+            extracted: self.extracted.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::config::SearchConfig;
+use crate::state::{Link, SearchIndex};
+use crate::url_parser::normalize_url;
+use chadselect::ChadSelect;
+use std::collections::HashMap;
+
+/// Fields pulled out of a single page, plus its visible text when search
+/// indexing of page text is on.
+#[derive(Default)]
+pub struct Extraction {
+    pub data: serde_json::Map<String, serde_json::Value>,
+    pub page_text: String,
+}
+
+/// Runs the configured selectors against a page. Shared by live crawls and
+/// offline re-extraction so both produce identical results.
+pub fn extract(
+    html: String,
+    selectors: &HashMap<String, String>,
+    search: &SearchConfig,
+) -> Extraction {
+    let mut cs = ChadSelect::new();
+    cs.add_html(html);
+
+    let mut data = serde_json::Map::new();
+    for (name, selector) in selectors {
+        let final_selector = if selector.starts_with("css:")
+            || selector.starts_with("xpath:")
+            || selector.starts_with("regex:")
+        {
+            selector.clone()
+        } else {
+            format!("css:{}", selector)
+        };
+
+        let val = cs.select(0, &final_selector);
+        // Note: chadselect warns if select(0, ...) is called on 0 results.
+        // In a production environment, we should use a safer API if available.
+        if !val.is_empty() {
+            data.insert(name.clone(), serde_json::json!(val));
+        }
+    }
+
+    let page_text = if search.enabled && search.page_text {
+        cs.select(0, "css:body")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        String::new()
+    };
+
+    Extraction { data, page_text }
+}
+
+impl Extraction {
+    /// How this page should be indexed when its result is saved.
+    pub fn search_index<'a>(&'a self, search: &SearchConfig) -> SearchIndex<'a> {
+        match (search.enabled, search.page_text) {
+            (false, _) => SearchIndex::Off,
+            (true, false) => SearchIndex::Values,
+            (true, true) => SearchIndex::ValuesAndText(&self.page_text),
+        }
+    }
+}
+
+/// The text a reader sees on a page, whitespace-collapsed, without the
+/// contents of scripts, styles and other non-rendered elements.
+pub fn visible_text(html: &str) -> String {
+    let document = scraper::Html::parse_document(html);
+    let mut words = Vec::new();
+    for node in document.root_element().descendants() {
+        let Some(text) = node.value().as_text() else {
+            continue;
+        };
+        let hidden = node.ancestors().any(|a| {
+            a.value()
+                .as_element()
+                .is_some_and(|e| matches!(e.name(), "script" | "style" | "noscript" | "template"))
+        });
+        if !hidden {
+            words.extend(text.split_whitespace());
+        }
+    }
+    words.join(" ")
+}
+
+/// Every `<a href>` on a page, resolved against `base_url` (the URL the page
+/// was actually served from). Targets are normalized and lose their
+/// fragment; links to non-web schemes such as `mailto:` are skipped.
+pub fn extract_links(source: &str, base_url: &str, html: &str) -> Vec<Link> {
+    let Ok(base) = url::Url::parse(base_url) else {
+        return vec![];
+    };
+    let document = scraper::Html::parse_document(html);
+    let anchors = scraper::Selector::parse("a[href]").unwrap();
+
+    document
+        .select(&anchors)
+        .enumerate()
+        .filter_map(|(position, a)| {
+            let mut target = base.join(a.value().attr("href")?.trim()).ok()?;
+            if !matches!(target.scheme(), "http" | "https" | "file") {
+                return None;
+            }
+            target.set_fragment(None);
+            let rel = a.value().attr("rel").unwrap_or_default().to_string();
+            Some(Link {
+                source: source.to_string(),
+                target: normalize_url(target.as_str()),
+                anchor_text: a
+                    .text()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                nofollow: rel
+                    .split_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("nofollow")),
+                rel,
+                position: position as i64,
+            })
+        })
+        .collect()
+}
+
+/// The page's `<link rel="canonical">`, resolved against `base_url` and
+/// normalized.
+pub fn extract_canonical(base_url: &str, html: &str) -> Option<String> {
+    let base = url::Url::parse(base_url).ok()?;
+    let document = scraper::Html::parse_document(html);
+    let links = scraper::Selector::parse("link[rel][href]").unwrap();
+
+    document
+        .select(&links)
+        .find(|l| {
+            l.value()
+                .attr("rel")
+                .unwrap_or_default()
+                .split_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("canonical"))
+        })
+        .and_then(|l| base.join(l.value().attr("href")?.trim()).ok())
+        .map(|u| normalize_url(u.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_selectors_and_page_text() {
+        let html = "<html><head><title>Widget</title></head><body><h1 class=\"price\">12</h1>\n  <p>In   stock</p></body></html>";
+        let mut selectors = HashMap::new();
+        selectors.insert("title".to_string(), "title".to_string());
+        selectors.insert("price".to_string(), "css:.price".to_string());
+        selectors.insert("missing".to_string(), ".nope".to_string());
+        let search = SearchConfig {
+            enabled: true,
+            page_text: true,
+        };
+
+        let extraction = extract(html.to_string(), &selectors, &search);
+        assert_eq!(extraction.data["title"], "Widget");
+        assert_eq!(extraction.data["price"], "12");
+        assert!(!extraction.data.contains_key("missing"));
+        assert!(extraction.page_text.contains("In stock"));
+        assert!(matches!(
+            extraction.search_index(&search),
+            SearchIndex::ValuesAndText(_)
+        ));
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let html = r#"<a href="/docs/#intro">  Read
+            the <b>docs</b> </a>
+            <a href="mailto:hi@example.com">mail</a>
+            <a href="https://other.com/x" rel="nofollow noopener">ext</a>"#;
+
+        let links = extract_links("https://example.com", "https://example.com/a/", html);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "https://example.com/docs");
+        assert_eq!(links[0].anchor_text, "Read the docs");
+        assert_eq!(links[0].position, 0);
+        assert!(!links[0].nofollow);
+        assert_eq!(links[1].target, "https://other.com/x");
+        assert_eq!(links[1].rel, "nofollow noopener");
+        assert!(links[1].nofollow);
+        assert_eq!(links[1].position, 2);
+
+        let head = r#"<head><link rel="stylesheet" href="/a.css">
+            <link rel="Canonical" href="../b/?y=2&x=1"></head>"#;
+        assert_eq!(
+            extract_canonical("https://example.com/a/", head).as_deref(),
+            Some("https://example.com/b?x=1&y=2")
+        );
+        assert_eq!(extract_canonical("https://example.com/", html), None);
+
+        let page = r#"<html><head><title>Hi</title><style>p { color: red }</style></head>
+            <body><p>Hello
+            <b>world</b></p><script>var x = 1;</script></body></html>"#;
+        assert_eq!(visible_text(page), "Hi Hello world");
+    }
+}
@@ -0,0 +1,168 @@
+use super::replay::{ArchivedPage, PageSource};
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use url::Url;
+
+/// Pages read straight from disk for `file://` crawls. Like a live crawl
+/// staying on its domain, only HTML files under the seed's directory are
+/// followed.
+pub struct LocalFiles {
+    root: PathBuf,
+}
+
+impl LocalFiles {
+    pub fn new(start_url: &str) -> Result<Self> {
+        let path = Url::parse(start_url)?
+            .to_file_path()
+            .map_err(|_| anyhow::anyhow!("Not a local file URL: {}", start_url))?;
+        let path = std::fs::canonicalize(&path)
+            .with_context(|| format!("Start path not found: {}", path.display()))?;
+        let root = if path.is_dir() {
+            path
+        } else {
+            path.parent()
+                .context("Start file has no parent")?
+                .to_path_buf()
+        };
+        Ok(Self { root })
+    }
+
+    /// The HTML file a URL refers to; directories serve their `index.html`.
+    fn resolve(&self, url: &str) -> Option<PathBuf> {
+        let mut path = Url::parse(url).ok()?.to_file_path().ok()?;
+        if path.is_dir() {
+            path.push("index.html");
+        }
+        let path = std::fs::canonicalize(path).ok()?;
+        let is_html = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "html" | "htm" | "xhtml"));
+        (is_html && path.is_file() && path.starts_with(&self.root)).then_some(path)
+    }
+}
+
+impl PageSource for LocalFiles {
+    fn fetch(&self, url: &str) -> Option<Cow<'_, ArchivedPage>> {
+        let path = self.resolve(url)?;
+        let body = match std::fs::read(&path) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        Some(Cow::Owned(ArchivedPage {
+            // Relative links resolve against the file itself, not the
+            // normalized URL (which drops a directory's trailing slash)
+            url: Url::from_file_path(&path).ok()?.to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/html".to_string())],
+            body,
+        }))
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.resolve(url).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler::Crawler;
+    use crate::crawler::{CrawlEvent, SkipReason};
+    use crate::state::{CrawlStatus, MemoryStateStore, StateStore};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_local_crawl_resolves_relative_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let site = dir.path().join("site");
+        fs::create_dir_all(site.join("guide")).unwrap();
+        fs::write(
+            site.join("index.html"),
+            "<title>Home</title><a href=\"guide/\">guide</a><a href=\"../outside.html\">out</a><a href=\"missing.html\">x</a>",
+        )
+        .unwrap();
+        fs::write(
+            site.join("guide/index.html"),
+            "<title>Guide</title><a href=\"../about.html#team\">about</a><a href=\"style.css\">css</a><a href=\"print.html\">print</a>",
+        )
+        .unwrap();
+        fs::write(site.join("guide/style.css"), "body {}").unwrap();
+        // A duplicate of about.html, only stored under its canonical URL
+        fs::write(
+            site.join("guide/print.html"),
+            "<title>Print</title><link rel=\"canonical\" href=\"../about.html\">",
+        )
+        .unwrap();
+        fs::write(site.join("about.html"), "<title>About</title>").unwrap();
+        fs::write(dir.path().join("outside.html"), "<title>Outside</title>").unwrap();
+
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::new());
+        let crawl_id = store.create_crawl("Local").await.unwrap();
+        let mut selectors = HashMap::new();
+        selectors.insert("title".to_string(), "title".to_string());
+
+        let start = Url::from_file_path(site.join("index.html")).unwrap();
+        let (events, mut progress) = crate::crawler::channel();
+        Crawler::new(store.clone(), crawl_id, vec![])
+            .with_canonical_dedupe(true)
+            .with_events(Some(events))
+            .run(
+                start.as_str(),
+                selectors,
+                false,
+                false,
+                None,
+                1,
+                vec![],
+                vec![],
+                None,
+                tokio_util::sync::CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let results = store.get_results(crawl_id).await.unwrap();
+        let titles: Vec<_> = results
+            .iter()
+            .map(|(_, data)| data["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles.len(), 3);
+        for title in ["Home", "Guide", "About"] {
+            assert!(titles.contains(&title), "missing {}", title);
+        }
+
+        let pages = store.get_page_info(crawl_id).await.unwrap();
+        assert_eq!(pages.len(), 4);
+        let print = pages
+            .iter()
+            .find(|p| p.url.ends_with("print.html"))
+            .unwrap();
+        assert!(
+            print
+                .canonical
+                .as_ref()
+                .unwrap()
+                .ends_with("/site/about.html")
+        );
+
+        let mut events = Vec::new();
+        while let Some(event) = progress.recv().await {
+            events.push(event);
+        }
+        assert!(events.iter().any(|e| matches!(e,
+            CrawlEvent::Skipped { url, reason: SkipReason::Duplicate } if url.ends_with("print.html"))));
+        let Some(CrawlEvent::Finished { summary }) = events.last() else {
+            panic!("crawl didn't finish with a summary: {:?}", events.last());
+        };
+        assert_eq!(summary.status, CrawlStatus::Completed);
+        assert_eq!((summary.fetched, summary.extracted), (4, 3));
+        assert_eq!(summary.skipped, 1);
+    }
+}
@@ -1,23 +1,69 @@
-use crate::features::cache::CacheManager;
+mod event;
+mod extract;
+mod local;
+mod replay;
+
+pub use event::{CrawlEvent, SkipReason, channel};
+pub use extract::{Extraction, extract, extract_canonical, extract_links, visible_text};
+pub use replay::Archive;
+
+use crate::config::{DedupeConfig, SearchConfig, SpiderConfig};
+use crate::features::cache::{CacheManager, VisitedSet, VisitedStats};
+use crate::features::conditional::{self, ConditionalRequests, Revalidation};
+use crate::features::dedupe::simhash;
+use crate::features::metrics::Metrics;
 use crate::features::proxy::ProxyManager;
-use crate::state::StateManager;
+use crate::features::raw_store::RawStore;
+use crate::features::redirects::{self, RedirectLog};
+use crate::features::timing::{FetchTimes, Outcome, Timing};
+use crate::features::warc::{Capture, WarcWriter};
+use crate::state::{CrawlStatus, Link, PageInfo, RedirectHop, StateStore};
 use anyhow::Result;
-use chadselect::ChadSelect;
+use event::Tally;
+use local::LocalFiles;
+use sha2::{Digest, Sha256};
 use spider::website::Website;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::Sender;
 
 pub struct Crawler {
-    state_manager: Arc<StateManager>,
+    state_manager: Arc<dyn StateStore>,
     proxy_manager: Option<Arc<ProxyManager>>,
-    cache_manager: Arc<CacheManager>,
+    cache_manager: CacheManager,
     crawl_id: i64,
+    search: SearchConfig,
+    recrawl: bool,
+    raw_store: Option<Arc<RawStore>>,
+    warc: Option<Arc<Mutex<WarcWriter>>>,
+    canonical_dedupe: bool,
+    dedupe: DedupeConfig,
+    revalidation: Arc<Revalidation>,
+    pause: Arc<AtomicBool>,
+    events: Option<Sender<CrawlEvent>>,
+    metrics: Option<Arc<Metrics>>,
+    tally: Tally,
+    /// How each request went, until its page comes off the channel
+    fetch_times: Arc<FetchTimes>,
+    /// Redirects each request followed, likewise
+    redirects: Arc<RedirectLog>,
+    /// Queued URLs that haven't been fetched or skipped yet
+    queued: Mutex<HashSet<String>>,
 }
 
-use tokio::sync::mpsc::UnboundedSender;
+/// How a page was fetched, recorded next to its result.
+struct Fetched<'a> {
+    url: &'a str,
+    /// Where the page was served from after redirects; links resolve
+    /// against it
+    final_url: &'a str,
+    status: u16,
+    redirects: Vec<RedirectHop>,
+}
 
 impl Crawler {
-    pub fn new(state_manager: Arc<StateManager>, crawl_id: i64, proxies: Vec<String>) -> Self {
+    pub fn new(state_manager: Arc<dyn StateStore>, crawl_id: i64, proxies: Vec<String>) -> Self {
         let proxy_manager = if proxies.is_empty() {
             None
         } else {
@@ -27,11 +73,124 @@ impl Crawler {
         Self {
             state_manager,
             proxy_manager,
-            cache_manager: Arc::new(CacheManager::new()),
+            cache_manager: CacheManager::new(),
             crawl_id,
+            search: SearchConfig::default(),
+            recrawl: false,
+            raw_store: None,
+            warc: None,
+            canonical_dedupe: false,
+            dedupe: DedupeConfig::default(),
+            revalidation: Arc::new(Revalidation::default()),
+            pause: Arc::default(),
+            events: None,
+            metrics: None,
+            tally: Tally::default(),
+            fetch_times: Arc::default(),
+            redirects: Arc::default(),
+            queued: Mutex::default(),
         }
     }
 
+    /// A crawler with `config`'s search, dedupe, visited set, raw response
+    /// store and WARC options.
+    pub fn from_config(
+        state_manager: Arc<dyn StateStore>,
+        crawl_id: i64,
+        config: &SpiderConfig,
+    ) -> Result<Self> {
+        let visited = VisitedSet::from_config(&config.visited, state_manager.clone(), crawl_id)?;
+        Ok(Self::new(state_manager, crawl_id, vec![])
+            .with_search(config.search.clone())
+            .with_canonical_dedupe(config.dedupe_by_canonical)
+            .with_dedupe(config.dedupe.clone())
+            .with_visited(visited)
+            .with_raw_store(RawStore::from_config(&config.store_raw)?.map(Arc::new))
+            .with_warc(WarcWriter::from_config(config, crawl_id)?))
+    }
+
+    /// Index results for full-text search as they are saved.
+    pub fn with_search(mut self, search: SearchConfig) -> Self {
+        self.search = search;
+        self
+    }
+
+    /// Incremental recrawl: re-fetch every known URL of the crawl, store a
+    /// new version only for pages whose data or content changed, and mark
+    /// pages that are no longer reachable as removed.
+    pub fn with_recrawl(mut self, recrawl: bool) -> Self {
+        self.recrawl = recrawl;
+        self
+    }
+
+    /// Archive every fetched response so it can be re-extracted offline.
+    pub fn with_raw_store(mut self, raw_store: Option<Arc<RawStore>>) -> Self {
+        self.raw_store = raw_store;
+        self
+    }
+
+    /// Write every fetched page to WARC files as it is processed.
+    pub fn with_warc(mut self, warc: Option<WarcWriter>) -> Self {
+        self.warc = warc.map(|w| Arc::new(Mutex::new(w)));
+        self
+    }
+
+    /// Store pages declaring a different canonical URL under that URL, and
+    /// skip them when the canonical page already has a result.
+    pub fn with_canonical_dedupe(mut self, dedupe: bool) -> Self {
+        self.canonical_dedupe = dedupe;
+        self
+    }
+
+    /// Skip extraction for pages with the same or nearly the same content as
+    /// a page crawled before them.
+    pub fn with_dedupe(mut self, dedupe: DedupeConfig) -> Self {
+        self.cache_manager = self.cache_manager.with_near_distance(dedupe.max_distance);
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Remember visited URLs in `visited` instead of an in-memory set.
+    pub fn with_visited(mut self, visited: VisitedSet) -> Self {
+        self.cache_manager = self.cache_manager.with_visited(visited);
+        self
+    }
+
+    /// Set `pause` before cancelling the crawl to pause it instead: the
+    /// links it had yet to visit are saved to the frontier, and a later run
+    /// with `resume` continues from them.
+    pub fn with_pause(mut self, pause: Arc<AtomicBool>) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Report progress on `events` as the crawl goes. The channel is bounded,
+    /// so a consumer that falls behind slows the crawl down.
+    pub fn with_events(mut self, events: Option<Sender<CrawlEvent>>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Report fetches, errors, extraction failures and the frontier size to
+    /// `metrics`.
+    pub fn with_metrics(mut self, metrics: Option<Arc<Metrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Size and memory footprint of the visited set.
+    pub fn visited_stats(&self) -> VisitedStats {
+        self.cache_manager.stats()
+    }
+
+    /// Pages a recrawl found unchanged through conditional requests.
+    pub fn revalidation(&self) -> &Revalidation {
+        &self.revalidation
+    }
+
+    /// Crawls from `start_url` and records how the crawl ended: completed,
+    /// paused, cancelled or failed.
+    #[allow(clippy::too_many_arguments)]
     pub async fn run(
         &self,
         start_url: &str,
@@ -39,13 +198,110 @@ impl Crawler {
         resume: bool,
         respect_robots: bool,
         delay: Option<u64>,
-        _concurrency: usize,
+        concurrency: usize,
         blacklist: Vec<String>,
         whitelist: Vec<String>,
         max_depth: Option<usize>,
-        status_tx: Option<UnboundedSender<String>>,
         cancel_token: tokio_util::sync::CancellationToken,
     ) -> Result<()> {
+        self.state_manager
+            .set_crawl_status(self.crawl_id, CrawlStatus::Active)
+            .await?;
+        let result = self
+            .crawl(
+                start_url,
+                selectors,
+                resume,
+                respect_robots,
+                delay,
+                concurrency,
+                blacklist,
+                whitelist,
+                max_depth,
+                cancel_token,
+            )
+            .await;
+        let status = match &result {
+            Ok(status) => *status,
+            Err(e) => {
+                let _ = self
+                    .state_manager
+                    .set_crawl_status(self.crawl_id, CrawlStatus::Failed)
+                    .await;
+                self.emit(CrawlEvent::Failed {
+                    url: None,
+                    error: e.to_string(),
+                })
+                .await;
+                CrawlStatus::Failed
+            }
+        };
+        self.emit(CrawlEvent::Finished {
+            summary: self.tally.summary(status),
+        })
+        .await;
+        result.map(|_| ())
+    }
+
+    /// Counts `event` and passes it on to the `with_events` channel, waiting
+    /// for room when the consumer is behind.
+    async fn emit(&self, event: CrawlEvent) {
+        let frontier = {
+            let mut queued = self.queued.lock().unwrap();
+            match &event {
+                CrawlEvent::Queued { url } => {
+                    queued.insert(url.clone());
+                }
+                CrawlEvent::Fetched { url, .. }
+                | CrawlEvent::Skipped { url, .. }
+                | CrawlEvent::Failed { url: Some(url), .. } => {
+                    queued.remove(url);
+                }
+                _ => {}
+            }
+            (!matches!(event, CrawlEvent::Finished { .. })).then_some(queued.len())
+        };
+        if let Some(metrics) = &self.metrics {
+            metrics.record(&event);
+            metrics.set_frontier(self.crawl_id, frontier);
+        }
+        self.tally.count(&event);
+        if let Some(events) = &self.events {
+            // Nobody listening any more doesn't stop the crawl
+            let _ = events.send(event).await;
+        }
+    }
+
+    /// Reports the URLs among `urls` that are new to the crawl as queued.
+    async fn queue(&self, urls: impl IntoIterator<Item = String>) {
+        // Most links are queued already, and those need no visited lookup
+        let urls: Vec<String> = {
+            let queued = self.queued.lock().unwrap();
+            urls.into_iter().filter(|u| !queued.contains(u)).collect()
+        };
+        for url in self.cache_manager.uncached(urls).await {
+            // A page may link to the same URL more than once
+            if self.queued.lock().unwrap().contains(&url) {
+                continue;
+            }
+            self.emit(CrawlEvent::Queued { url }).await;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn crawl(
+        &self,
+        start_url: &str,
+        selectors: HashMap<String, String>,
+        resume: bool,
+        respect_robots: bool,
+        delay: Option<u64>,
+        _concurrency: usize,
+        blacklist: Vec<String>,
+        whitelist: Vec<String>,
+        max_depth: Option<usize>,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> Result<CrawlStatus> {
         let mut website: Website = Website::new(start_url);
 
         tracing::info!(
@@ -71,6 +327,7 @@ impl Crawler {
         if let Some(d) = delay {
             website.configuration.delay = d;
         }
+        #[allow(clippy::collapsible_if)]
         if let Some(proxy_manager) = &self.proxy_manager {
             if let Some(_proxy) = proxy_manager.get_next() {
                 // In spider 2.0, proxies might be a Vec or a different field.
@@ -79,27 +336,92 @@ impl Crawler {
             }
         }
 
-        if let Ok(visited) = self.state_manager.get_visited_urls(self.crawl_id).await {
-            self.cache_manager.extend(visited);
+        // Known and pending pages spider wouldn't reach from the start URL
+        let mut extra_links = Vec::new();
+        if self.recrawl {
+            // Known pages are fetched again rather than skipped; seed them
+            // so pages that lost their inbound links are still re-checked.
+            let visited = self.state_manager.get_visited_urls(self.crawl_id).await?;
+            tracing::info!("Recrawling {} known URLs", visited.len());
+            self.state_manager.begin_recrawl(self.crawl_id).await?;
+            self.cache_manager.clear();
+            extra_links = visited;
+        } else {
+            if self.cache_manager.needs_preload()
+                && let Ok(visited) = self.state_manager.get_visited_urls(self.crawl_id).await
+            {
+                self.cache_manager.extend(visited);
+            }
+            if self.dedupe.skip_duplicates {
+                self.cache_manager
+                    .extend_contents(&self.state_manager.get_page_info(self.crawl_id).await?);
+            }
+        }
+
+        // Every request is timed and its redirects recorded, and a recrawl
+        // asks for known pages only if they changed since
+        let mut client = spider::reqwest_middleware::ClientBuilder::new(redirects::client(
+            &website,
+            self.redirects.clone(),
+        )?)
+        .with(Timing::new(self.fetch_times.clone(), self.metrics.clone()));
+        if self.recrawl {
+            let mut validators = self.state_manager.get_validators(self.crawl_id).await?;
+            // spider stops when the start page comes back empty, so it is
+            // always fetched in full to reach the rest of the site
+            validators.remove(&crate::url_parser::normalize_url(start_url));
+            if !validators.is_empty() {
+                tracing::info!(
+                    "Revalidating {} pages with conditional requests",
+                    validators.len()
+                );
+                client = client.with(ConditionalRequests::new(
+                    validators,
+                    self.revalidation.clone(),
+                ));
+            }
         }
+        website.set_http_client(client.build());
 
         if resume {
             tracing::info!("Resuming crawl from database...");
-            if let Ok(pending) = self
+            let mut pending = Vec::new();
+            for (_, url, _) in self
                 .state_manager
-                .get_pending_frontier(self.crawl_id, 1000)
-                .await
+                .get_pending_frontier(self.crawl_id, i32::MAX)
+                .await?
             {
-                // In a mature implementation, we'd seed these directly into the underlying crawler.
-                // For now, we rely on the database uniqueness to avoid duplicates.
+                if !self.cache_manager.is_cached(&url).await {
+                    pending.push(url);
+                }
+            }
+            if !pending.is_empty() {
                 tracing::info!("Found {} pending URLs to resume.", pending.len());
+                extra_links.extend(pending);
             }
-        } else {
-            // Initial seed
-            self.state_manager
-                .add_to_frontier(self.crawl_id, vec![(start_url.to_string(), 0)])
+        }
+        if !extra_links.is_empty() {
+            website.set_extra_links(extra_links.iter().cloned().map(Into::into).collect());
+        }
+
+        // Seeds are recorded even when resuming so link analysis can measure
+        // click depth from them
+        self.state_manager
+            .add_to_frontier(self.crawl_id, vec![(start_url.to_string(), 0)])
+            .await?;
+        let mut seeds = vec![crate::url_parser::normalize_url(start_url)];
+        seeds.extend(extra_links);
+        self.queue(seeds.iter().cloned()).await;
+
+        if start_url.starts_with("file://") {
+            // Local files are read from disk directly rather than through spider
+            let source = LocalFiles::new(start_url)?;
+            self.crawl_offline(&source, seeds, &selectors, max_depth, &cancel_token)
                 .await?;
+            return self.finish(start_url, cancel_token.is_cancelled()).await;
         }
+        // The site's host, to tell the links to queue from external ones
+        let site = url::Url::parse(start_url)?.host_str().map(str::to_string);
 
         let mut rx2 = website.subscribe(8).unwrap();
 
@@ -109,11 +431,13 @@ impl Crawler {
         });
 
         // Process discovered pages
+        let mut cancelled = false;
         loop {
             tokio::select! {
                 _ = cancel_token.cancelled() => {
                     tracing::info!("Crawl cancelled by token.");
                     website_handle.abort();
+                    cancelled = true;
                     break;
                 }
                 res = rx2.recv() => {
@@ -121,52 +445,124 @@ impl Crawler {
                         Ok(res) => {
                             let raw_url = res.get_url().to_string();
                             let url = crate::url_parser::normalize_url(&raw_url);
+                            // Taken before any page is skipped, or they stay behind
+                            let outcome = self.fetch_times.take(&url);
+                            let redirects = self.redirects.take(&url);
 
-                            if self.cache_manager.is_cached(&url) {
+                            if self.cache_manager.is_cached(&url).await {
+                                self.emit(CrawlEvent::Skipped {
+                                    url,
+                                    reason: SkipReason::Visited,
+                                })
+                                .await;
                                 continue;
                             }
 
-                            let html = res.get_html();
-
-                            let extracted_data = {
-                                let mut cs = ChadSelect::new();
-                                cs.add_html(html);
-
-                                let mut data = serde_json::Map::new();
-                                for (name, selector) in &selectors {
-                                    let final_selector = if selector.starts_with("css:")
-                                        || selector.starts_with("xpath:")
-                                        || selector.starts_with("regex:")
-                                    {
-                                        selector.clone()
-                                    } else {
-                                        format!("css:{}", selector)
-                                    };
-
-                                    let val = cs.select(0, &final_selector);
-                                    // Note: chadselect warns if select(0, ...) is called on 0 results.
-                                    // In a production environment, we should use a safer API if available.
-                                    if !val.is_empty() {
-                                        data.insert(name.clone(), serde_json::json!(val));
-                                    }
-                                }
-                                data
+                            if res.status_code == spider::reqwest::StatusCode::NOT_MODIFIED {
+                                self.state_manager.mark_unchanged(self.crawl_id, &url).await?;
+                                self.cache_manager.cache(url.clone());
+                                tracing::info!("Not modified: {}", url);
+                                self.emit(CrawlEvent::Skipped {
+                                    url,
+                                    reason: SkipReason::NotModified,
+                                })
+                                .await;
+                                continue;
+                            }
+
+                            let body = res.get_html_bytes_u8();
+                            let event = match outcome {
+                                Some(Outcome::Failed(error)) => CrawlEvent::Failed {
+                                    url: Some(url.clone()),
+                                    error,
+                                },
+                                outcome => CrawlEvent::Fetched {
+                                    url: url.clone(),
+                                    status: res.status_code.as_u16(),
+                                    bytes: body.len() as u64,
+                                    latency_ms: match outcome {
+                                        Some(Outcome::Responded(latency)) => Some(latency.as_millis() as u64),
+                                        _ => None,
+                                    },
+                                },
                             };
+                            self.emit(event).await;
+                            let content_hash = hex::encode(Sha256::digest(body));
 
-                            self.state_manager
-                                .save_result(
-                                    self.crawl_id,
-                                    &url,
-                                    &serde_json::Value::Object(extracted_data),
+                            let headers: Vec<(String, String)> = res
+                                .headers
+                                .iter()
+                                .flatten()
+                                .map(|(k, v)| {
+                                    (k.as_str().to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned())
+                                })
+                                .collect();
+
+                            if let Some(raw_store) = &self.raw_store {
+                                // Hashing, compressing and writing the body
+                                // blocks, so it runs off the async workers
+                                let (raw_store, crawl_id, status) =
+                                    (raw_store.clone(), self.crawl_id, res.status_code.as_u16());
+                                let (page, final_url, headers, body) =
+                                    (url.clone(), res.get_url_final().to_string(), headers.clone(), body.to_vec());
+                                let stored = tokio::task::spawn_blocking(move || {
+                                    raw_store.put(crawl_id, &page, &final_url, status, headers, &body)
+                                })
+                                .await?;
+                                if let Err(e) = stored {
+                                    tracing::warn!("Failed to store raw response for {}: {}", url, e);
+                                }
+                            }
+
+                            let (extraction, links) = self
+                                .process_page(
+                                    Fetched {
+                                        url: &url,
+                                        final_url: res.get_url_final(),
+                                        status: res.status_code.as_u16(),
+                                        redirects,
+                                    },
+                                    res.get_html(),
+                                    &content_hash,
+                                    &selectors,
                                 )
                                 .await?;
-                            self.cache_manager.cache(url.clone());
+                            self.queue(links.into_iter().map(|l| l.target).filter(|t| {
+                                url::Url::parse(t).is_ok_and(|u| u.host_str() == site.as_deref())
+                            }))
+                            .await;
 
-                            if let Some(tx) = &status_tx {
-                                let _ = tx.send(url.clone());
+                            if res.status_code.is_success()
+                                && let Some(validators) = conditional::validators(&headers, body.len() as u64)
+                            {
+                                self.state_manager
+                                    .save_validators(self.crawl_id, &url, &validators)
+                                    .await?;
                             }
 
-                            tracing::info!("Processed and persisted: {}", url);
+                            if let Some(warc) = &self.warc {
+                                let warc = warc.clone();
+                                let metadata = vec![(
+                                    "extracted".to_string(),
+                                    serde_json::to_string(&extraction.data)?,
+                                )];
+                                let (final_url, status, body) =
+                                    (res.get_url_final().to_string(), res.status_code, body.to_vec());
+                                let written = tokio::task::spawn_blocking(move || {
+                                    warc.lock().unwrap().write_capture(&Capture {
+                                        url: &final_url,
+                                        status: status.as_u16(),
+                                        reason: status.canonical_reason().unwrap_or(""),
+                                        headers: &headers,
+                                        body: &body,
+                                        metadata,
+                                    })
+                                })
+                                .await?;
+                                if let Err(e) = written {
+                                    tracing::warn!("Failed to write WARC records for {}: {}", url, e);
+                                }
+                            }
                         }
                         Err(_) => break, // Channel closed/end of crawl
                     }
@@ -174,7 +570,230 @@ impl Crawler {
             }
         }
 
+        self.finish(start_url, cancelled).await
+    }
+
+    async fn finish(&self, start_url: &str, cancelled: bool) -> Result<CrawlStatus> {
+        // Saved even when cancelled, so a resumed crawl skips what was done
+        self.cache_manager.persist()?;
+        if cancelled {
+            let status = if self.pause.load(Ordering::SeqCst) {
+                let pending = self.save_frontier(start_url).await?;
+                tracing::info!("Crawl paused with {} pending URLs", pending);
+                CrawlStatus::Paused
+            } else {
+                CrawlStatus::Cancelled
+            };
+            self.state_manager
+                .set_crawl_status(self.crawl_id, status)
+                .await?;
+            return Ok(status);
+        }
+        if self.recrawl {
+            let removed = self
+                .state_manager
+                .mark_unseen_removed(self.crawl_id)
+                .await?;
+            tracing::info!(
+                "Recrawl finished, {} pages no longer reachable",
+                removed.len()
+            );
+        }
+
         let _ = self.state_manager.complete_crawl(self.crawl_id).await;
-        Ok(())
+        Ok(CrawlStatus::Completed)
+    }
+
+    /// Saves the links found so far that lead to unvisited pages on the
+    /// start URL's host to the frontier. Returns how many there were.
+    async fn save_frontier(&self, start_url: &str) -> Result<usize> {
+        let host = url::Url::parse(start_url)?.host_str().map(str::to_string);
+        let mut pending: Vec<String> = self
+            .state_manager
+            .get_links(self.crawl_id)
+            .await?
+            .into_iter()
+            .map(|l| l.target)
+            .filter(|t| url::Url::parse(t).is_ok_and(|u| u.host_str() == host.as_deref()))
+            .collect();
+        pending.sort();
+        pending.dedup();
+
+        let mut frontier = Vec::new();
+        for url in pending {
+            if !self.cache_manager.is_cached(&url).await {
+                // Depth 0 is reserved for seeds
+                frontier.push((url, 1));
+            }
+        }
+        let count = frontier.len();
+        self.state_manager
+            .add_to_frontier(self.crawl_id, frontier)
+            .await?;
+        Ok(count)
+    }
+
+    /// Extracts and persists one fetched page. Live crawls and archive
+    /// replays both go through here, so they store identical results.
+    async fn process_page(
+        &self,
+        page: Fetched<'_>,
+        html: String,
+        content_hash: &str,
+        selectors: &HashMap<String, String>,
+    ) -> Result<(Extraction, Vec<Link>)> {
+        let url = page.url;
+        let links = extract_links(url, page.final_url, &html);
+        let canonical = extract_canonical(page.final_url, &html);
+        let simhash = simhash(&visible_text(&html));
+        // Error pages and empty redirect bodies would all match each other
+        let duplicate_of = if self.dedupe.skip_duplicates && (200..300).contains(&page.status) {
+            self.cache_manager.claim_content(url, content_hash, simhash)
+        } else {
+            None
+        };
+        let extraction = if duplicate_of.is_some() {
+            Extraction::default()
+        } else {
+            extract(html, selectors, &self.search)
+        };
+
+        let result_url = match &canonical {
+            Some(c) if self.canonical_dedupe => c.clone(),
+            _ => url.to_string(),
+        };
+        let duplicate = duplicate_of.is_some()
+            || (result_url != url && self.cache_manager.is_cached(&result_url).await);
+        let change = if duplicate {
+            None
+        } else {
+            self.state_manager
+                .save_result(
+                    self.crawl_id,
+                    &result_url,
+                    &serde_json::Value::Object(extraction.data.clone()),
+                    Some(content_hash),
+                    extraction.search_index(&self.search),
+                )
+                .await?
+        };
+        self.state_manager
+            .save_links(self.crawl_id, url, &links)
+            .await?;
+        self.state_manager
+            .save_page_info(
+                self.crawl_id,
+                &PageInfo {
+                    url: url.to_string(),
+                    final_url: page.final_url.to_string(),
+                    status: page.status,
+                    canonical,
+                    redirects: page.redirects,
+                    content_hash: Some(content_hash.to_string()),
+                    simhash,
+                    duplicate_of: duplicate_of.clone(),
+                },
+            )
+            .await?;
+        self.cache_manager.cache(url.to_string());
+        if result_url != url {
+            self.cache_manager.cache(result_url.clone());
+        }
+
+        if duplicate {
+            self.emit(CrawlEvent::Skipped {
+                url: url.to_string(),
+                reason: SkipReason::Duplicate,
+            })
+            .await;
+        } else {
+            if let Some(metrics) = &self.metrics
+                && (200..300).contains(&page.status)
+            {
+                for field in selectors.keys() {
+                    if !extraction.data.contains_key(field) {
+                        metrics.extraction_failed(field);
+                    }
+                }
+            }
+            if !extraction.data.is_empty() {
+                self.emit(CrawlEvent::Extracted {
+                    url: url.to_string(),
+                    fields: extraction.data.clone(),
+                })
+                .await;
+            }
+        }
+
+        match change {
+            _ if duplicate => {
+                let original = duplicate_of.as_deref().unwrap_or(&result_url);
+                tracing::info!("Processed, duplicate of {}: {}", original, url)
+            }
+            Some(change) => {
+                tracing::info!("Processed and persisted ({}): {}", change.as_str(), url)
+            }
+            None => tracing::info!("Processed, unchanged: {}", url),
+        }
+        Ok((extraction, links))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MemoryStateStore;
+    use axum::Router;
+    use axum::response::Html;
+    use axum::routing::get;
+
+    #[tokio::test]
+    async fn test_fetch_times_are_taken_for_skipped_pages() {
+        let app = Router::new()
+            .route("/", get(|| async { Html("<a href=\"/a\">a</a>") }))
+            .route("/a", get(|| async { Html("<a href=\"/\">home</a>") }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::new());
+        let crawl_id = store.create_crawl("Timing").await.unwrap();
+        let start = format!("http://{}/", addr);
+        // The second run finds every page visited and skips them
+        for resume in [false, true] {
+            let (events, mut progress) = channel();
+            let crawler = Crawler::new(store.clone(), crawl_id, vec![]).with_events(Some(events));
+            crawler
+                .run(
+                    &start,
+                    HashMap::new(),
+                    resume,
+                    false,
+                    Some(20),
+                    1,
+                    vec![],
+                    vec![],
+                    None,
+                    tokio_util::sync::CancellationToken::new(),
+                )
+                .await
+                .unwrap();
+            assert!(crawler.fetch_times.is_empty());
+
+            drop(crawler);
+            let mut skipped = 0;
+            while let Some(event) = progress.recv().await {
+                if matches!(
+                    event,
+                    CrawlEvent::Skipped {
+                        reason: SkipReason::Visited,
+                        ..
+                    }
+                ) {
+                    skipped += 1;
+                }
+            }
+            assert_eq!(skipped > 0, resume);
+        }
     }
 }
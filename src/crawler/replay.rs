@@ -0,0 +1,351 @@
+use super::{CrawlEvent, Crawler, Fetched};
+use crate::config::Compression;
+use crate::features::raw_store::RawStore;
+use crate::features::warc;
+use crate::state::{CrawlStatus, RedirectHop};
+use crate::url_parser::normalize_url;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// A response available offline.
+#[derive(Clone)]
+pub struct ArchivedPage {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Where an offline crawl reads its pages from.
+pub trait PageSource {
+    /// The page stored under a normalized URL, if the source has it.
+    fn fetch(&self, url: &str) -> Option<Cow<'_, ArchivedPage>>;
+
+    /// Whether a normalized link target belongs to the source and should
+    /// be followed.
+    fn contains(&self, url: &str) -> bool;
+}
+
+/// Archived pages keyed by normalized URL.
+pub struct Archive {
+    pages: HashMap<String, ArchivedPage>,
+    first: Option<String>,
+}
+
+impl Archive {
+    /// Loads a `.warc`/`.warc.gz` file, a directory of WARC files, or a raw
+    /// response store written with `store_raw`. Later copies of a URL win.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut archive = Archive {
+            pages: HashMap::new(),
+            first: None,
+        };
+
+        if path.join("crawls").is_dir() && path.join("objects").is_dir() {
+            let store = RawStore::open(path, Compression::default())?;
+            for response in store.all_responses()? {
+                let body = store.body(&response)?;
+                archive.insert(ArchivedPage {
+                    url: response.url,
+                    status: response.status,
+                    headers: response.headers,
+                    body,
+                });
+            }
+        } else if path.is_dir() {
+            let mut files: Vec<_> = std::fs::read_dir(path)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<std::io::Result<_>>()?;
+            files.retain(|p| {
+                let name = p.to_string_lossy();
+                name.ends_with(".warc") || name.ends_with(".warc.gz")
+            });
+            files.sort();
+            for file in files {
+                archive.load_warc(&file)?;
+            }
+        } else {
+            archive.load_warc(path)?;
+        }
+
+        if archive.pages.is_empty() {
+            anyhow::bail!("No archived responses found in {}", path.display());
+        }
+        Ok(archive)
+    }
+
+    fn load_warc(&mut self, path: &Path) -> Result<()> {
+        for response in warc::read_responses(path)? {
+            self.insert(ArchivedPage {
+                url: response.url,
+                status: response.status,
+                headers: response.headers,
+                body: response.body,
+            });
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, page: ArchivedPage) {
+        let key = normalize_url(&page.url);
+        self.first.get_or_insert_with(|| key.clone());
+        self.pages.insert(key, page);
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl PageSource for Archive {
+    fn fetch(&self, url: &str) -> Option<Cow<'_, ArchivedPage>> {
+        self.pages.get(url).map(Cow::Borrowed)
+    }
+
+    fn contains(&self, url: &str) -> bool {
+        self.pages.contains_key(url)
+    }
+}
+
+impl Crawler {
+    /// Crawls an archive instead of the network. Pages go through the same
+    /// extraction and persistence as a live crawl, and only links to other
+    /// archived pages are followed. Returns the number of pages processed.
+    pub async fn replay(
+        &self,
+        archive: &Archive,
+        start_urls: &[String],
+        selectors: HashMap<String, String>,
+        max_depth: Option<usize>,
+    ) -> Result<usize> {
+        let mut seeds: Vec<String> = start_urls
+            .iter()
+            .map(|u| normalize_url(u))
+            .filter(|u| archive.contains(u))
+            .collect();
+        if seeds.is_empty() {
+            let first = archive.first.clone().context("Archive is empty")?;
+            tracing::warn!(
+                "No start URL found in the archive, replaying from {}",
+                first
+            );
+            seeds.push(first);
+        }
+        self.state_manager
+            .add_to_frontier(
+                self.crawl_id,
+                seeds.iter().map(|u| (u.clone(), 0)).collect(),
+            )
+            .await?;
+        self.queue(seeds.iter().cloned()).await;
+
+        let processed = self
+            .crawl_offline(
+                archive,
+                seeds,
+                &selectors,
+                max_depth,
+                &CancellationToken::new(),
+            )
+            .await?;
+        self.cache_manager.persist()?;
+        let _ = self.state_manager.complete_crawl(self.crawl_id).await;
+        self.emit(CrawlEvent::Finished {
+            summary: self.tally.summary(CrawlStatus::Completed),
+        })
+        .await;
+        Ok(processed)
+    }
+
+    /// Breadth-first crawl over a page source, following only links the
+    /// source contains. Returns the number of pages processed.
+    pub(super) async fn crawl_offline<S: PageSource>(
+        &self,
+        source: &S,
+        seeds: Vec<String>,
+        selectors: &HashMap<String, String>,
+        max_depth: Option<usize>,
+        cancel_token: &CancellationToken,
+    ) -> Result<usize> {
+        let mut queue: VecDeque<(String, usize)> = seeds.into_iter().map(|u| (u, 0)).collect();
+        let mut processed = 0;
+        while let Some((url, depth)) = queue.pop_front() {
+            if cancel_token.is_cancelled() {
+                tracing::info!("Crawl cancelled by token.");
+                break;
+            }
+            if self.cache_manager.is_cached(&url).await {
+                continue;
+            }
+            let started = Instant::now();
+            let Some(page) = source.fetch(&url) else {
+                continue;
+            };
+            self.emit(CrawlEvent::Fetched {
+                url: url.clone(),
+                status: page.status,
+                bytes: page.body.len() as u64,
+                latency_ms: Some(started.elapsed().as_millis() as u64),
+            })
+            .await;
+
+            let content_hash = hex::encode(Sha256::digest(&page.body));
+            let html = String::from_utf8_lossy(&page.body).into_owned();
+            // Each archived response is one hop; reports join them into chains
+            let hop = redirect_hop(&page);
+            let (_, links) = self
+                .process_page(
+                    Fetched {
+                        url: &url,
+                        final_url: &page.url,
+                        status: page.status,
+                        redirects: hop.iter().cloned().collect(),
+                    },
+                    html,
+                    &content_hash,
+                    selectors,
+                )
+                .await?;
+            processed += 1;
+
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            let targets = if let Some(hop) = hop {
+                vec![normalize_url(&hop.location)]
+            } else {
+                links.into_iter().map(|l| l.target).collect()
+            };
+            for link in targets {
+                if source.contains(&link) && !self.cache_manager.is_cached(&link).await {
+                    self.queue([link.clone()]).await;
+                    queue.push_back((link, depth + 1));
+                }
+            }
+        }
+        Ok(processed)
+    }
+}
+
+/// Where an archived redirect response points.
+fn redirect_hop(page: &ArchivedPage) -> Option<RedirectHop> {
+    if !(300..400).contains(&page.status) {
+        return None;
+    }
+    let base = url::Url::parse(&page.url).ok()?;
+    let location = page
+        .headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("location"))
+        .and_then(|(_, v)| base.join(v).ok())?;
+    Some(RedirectHop {
+        url: page.url.clone(),
+        status: page.status,
+        location: location.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WarcConfig;
+    use crate::features::warc::{Capture, WarcWriter};
+    use crate::state::{MemoryStateStore, StateStore};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_replay_follows_links_within_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarcConfig {
+            enabled: true,
+            path: dir.path().to_string_lossy().into_owned(),
+            ..WarcConfig::default()
+        };
+        let mut writer = WarcWriter::new(&config, 1, vec![]).unwrap();
+        let pages = [
+            (
+                "http://site.test/",
+                200,
+                "<title>Home</title><a href=\"/a#top\">a</a><a href=\"http://elsewhere.test/\">x</a><a href=\"old\">old</a>",
+            ),
+            (
+                "http://site.test/a",
+                200,
+                "<title>A</title><a href=\"/\">home</a>",
+            ),
+            ("http://site.test/old", 301, ""),
+            ("http://site.test/new", 200, "<title>New</title>"),
+            ("http://site.test/orphan", 200, "<title>Orphan</title>"),
+        ];
+        for (url, status, body) in pages {
+            let headers = if status == 301 {
+                vec![("Location".to_string(), "/new".to_string())]
+            } else {
+                vec![]
+            };
+            writer
+                .write_capture(&Capture {
+                    url,
+                    status,
+                    reason: "",
+                    headers: &headers,
+                    body: body.as_bytes(),
+                    metadata: vec![],
+                })
+                .unwrap();
+        }
+        drop(writer);
+
+        let archive = Archive::load(dir.path()).unwrap();
+        assert_eq!(archive.len(), 5);
+
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::new());
+        let crawl_id = store.create_crawl("Replay").await.unwrap();
+        let crawler = Crawler::new(store.clone(), crawl_id, vec![]);
+        let mut selectors = HashMap::new();
+        selectors.insert("title".to_string(), "title".to_string());
+
+        let processed = crawler
+            .replay(
+                &archive,
+                &["http://site.test/".to_string()],
+                selectors,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(processed, 4);
+
+        let results = store.get_results(crawl_id).await.unwrap();
+        let urls: Vec<_> = results.iter().map(|(u, _)| u.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "http://site.test/",
+                "http://site.test/a",
+                "http://site.test/new",
+                "http://site.test/old"
+            ]
+        );
+        assert_eq!(results[2].1["title"], "New");
+
+        // External and unarchived links are still recorded as edges
+        let counts = store.get_link_counts(crawl_id).await.unwrap();
+        assert_eq!(counts["http://site.test/"].outlinks, 3);
+        assert_eq!(counts["http://site.test/"].inlinks, 1);
+        assert_eq!(counts["http://elsewhere.test/"].inlinks, 1);
+
+        let pages = store.get_page_info(crawl_id).await.unwrap();
+        let old = pages
+            .iter()
+            .find(|p| p.url == "http://site.test/old")
+            .unwrap();
+        assert_eq!(old.status, 301);
+        assert_eq!(old.redirects[0].location, "http://site.test/new");
+    }
+}
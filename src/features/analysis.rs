@@ -0,0 +1,409 @@
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::state::Link;
+use crate::url_parser::{UrlRef, normalize_url};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::Write;
+
+const DAMPING: f64 = 0.85;
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-10;
+
+/// Link-graph metrics for one page of a crawl.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageMetrics {
+    pub url: String,
+    /// Internal PageRank; all pages of a crawl sum to 1
+    pub pagerank: f64,
+    /// Fewest clicks needed to reach the page from a seed, `None` when no
+    /// chain of internal links leads to it
+    pub click_depth: Option<usize>,
+    /// Number of path segments, as `UrlRef::depth` reports it
+    pub path_depth: usize,
+    /// Distinct internal pages linking here
+    pub inlinks: usize,
+    /// Distinct internal pages linked from here
+    pub outlinks: usize,
+    pub crawled: bool,
+    pub in_sitemap: bool,
+    /// Not a seed and not linked from any other internal page
+    pub orphan: bool,
+    /// Crawled, but links to no other internal page
+    pub dead_end: bool,
+}
+
+/// Metrics for every page of a crawl, ordered by URL.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkAnalysis {
+    pub crawl_id: i64,
+    pub pages: Vec<PageMetrics>,
+}
+
+/// Analyses the internal link graph of a crawl. Pages are the crawled URLs
+/// plus any sitemap URLs; links to anything else are external and ignored.
+/// `nofollow` links count towards click depth and link counts but pass no
+/// PageRank.
+pub fn analyze(
+    crawl_id: i64,
+    crawled: &[String],
+    links: &[Link],
+    seeds: &[String],
+    sitemap: &[String],
+) -> LinkAnalysis {
+    let crawled: BTreeSet<String> = crawled.iter().map(|u| normalize_url(u)).collect();
+    let sitemap: BTreeSet<String> = sitemap.iter().map(|u| normalize_url(u)).collect();
+    let urls: Vec<&String> = crawled.union(&sitemap).collect();
+    let index: HashMap<&str, usize> = urls
+        .iter()
+        .enumerate()
+        .map(|(i, u)| (u.as_str(), i))
+        .collect();
+
+    // Distinct internal edges; an edge is followed if any of its links is
+    let mut edges: BTreeMap<(usize, usize), bool> = BTreeMap::new();
+    for link in links {
+        let (Some(&from), Some(&to)) = (
+            index.get(link.source.as_str()),
+            index.get(link.target.as_str()),
+        ) else {
+            continue;
+        };
+        if from != to {
+            *edges.entry((from, to)).or_default() |= !link.nofollow;
+        }
+    }
+
+    let n = urls.len();
+    let mut outgoing = vec![Vec::new(); n];
+    let mut inlinks = vec![0; n];
+    for &(from, to) in edges.keys() {
+        outgoing[from].push(to);
+        inlinks[to] += 1;
+    }
+    let followed: Vec<Vec<usize>> = (0..n)
+        .map(|from| {
+            outgoing[from]
+                .iter()
+                .copied()
+                .filter(|&to| edges[&(from, to)])
+                .collect()
+        })
+        .collect();
+
+    let seeds: Vec<usize> = seeds
+        .iter()
+        .filter_map(|s| index.get(normalize_url(s).as_str()).copied())
+        .collect();
+    let ranks = pagerank(&followed);
+    let depths = click_depths(&outgoing, &seeds);
+
+    let pages = urls
+        .iter()
+        .enumerate()
+        .map(|(i, url)| {
+            let was_crawled = crawled.contains(*url);
+            PageMetrics {
+                url: url.to_string(),
+                pagerank: ranks[i],
+                click_depth: depths[i],
+                path_depth: UrlRef::from_str(url).map(|u| u.depth).unwrap_or(0),
+                inlinks: inlinks[i],
+                outlinks: outgoing[i].len(),
+                crawled: was_crawled,
+                in_sitemap: sitemap.contains(*url),
+                orphan: inlinks[i] == 0 && !seeds.contains(&i),
+                dead_end: was_crawled && outgoing[i].is_empty(),
+            }
+        })
+        .collect();
+
+    LinkAnalysis { crawl_id, pages }
+}
+
+/// Power iteration over the followed links. Rank held by pages without
+/// outgoing links is spread evenly over every page.
+fn pagerank(outgoing: &[Vec<usize>]) -> Vec<f64> {
+    let n = outgoing.len();
+    if n == 0 {
+        return vec![];
+    }
+    let mut ranks = vec![1.0 / n as f64; n];
+    for _ in 0..MAX_ITERATIONS {
+        let dangling: f64 = (0..n)
+            .filter(|&i| outgoing[i].is_empty())
+            .map(|i| ranks[i])
+            .sum();
+        let base = (1.0 - DAMPING) / n as f64 + DAMPING * dangling / n as f64;
+        let mut next = vec![base; n];
+        for (from, targets) in outgoing.iter().enumerate() {
+            let share = DAMPING * ranks[from] / targets.len().max(1) as f64;
+            for &to in targets {
+                next[to] += share;
+            }
+        }
+        let delta: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if delta < TOLERANCE {
+            break;
+        }
+    }
+    ranks
+}
+
+/// Breadth-first distance from the nearest seed.
+fn click_depths(outgoing: &[Vec<usize>], seeds: &[usize]) -> Vec<Option<usize>> {
+    let mut depths = vec![None; outgoing.len()];
+    let mut queue = VecDeque::new();
+    for &seed in seeds {
+        if depths[seed].is_none() {
+            depths[seed] = Some(0);
+            queue.push_back(seed);
+        }
+    }
+    while let Some(page) = queue.pop_front() {
+        let next = depths[page].map(|d| d + 1);
+        for &to in &outgoing[page] {
+            if depths[to].is_none() {
+                depths[to] = next;
+                queue.push_back(to);
+            }
+        }
+    }
+    depths
+}
+
+impl LinkAnalysis {
+    pub fn write_csv(&self, mut out: impl Write) -> Result<()> {
+        writeln!(
+            out,
+            "url,pagerank,click_depth,path_depth,inlinks,outlinks,crawled,in_sitemap,orphan,dead_end"
+        )?;
+        for p in &self.pages {
+            writeln!(
+                out,
+                "{},{:.6},{},{},{},{},{},{},{},{}",
+                csv_field(&p.url),
+                p.pagerank,
+                p.click_depth.map(|d| d.to_string()).unwrap_or_default(),
+                p.path_depth,
+                p.inlinks,
+                p.outlinks,
+                p.crawled,
+                p.in_sitemap,
+                p.orphan,
+                p.dead_end
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn to_report(&self) -> Report {
+        let row = |p: &PageMetrics| ReportRow {
+            url: p.url.clone(),
+            note: format!(
+                "rank {:.4}, click depth {}, path depth {}, {} in / {} out",
+                p.pagerank,
+                p.click_depth
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                p.path_depth,
+                p.inlinks,
+                p.outlinks
+            ),
+            fields: vec![],
+        };
+
+        let mut by_rank: Vec<&PageMetrics> = self.pages.iter().collect();
+        by_rank.sort_by(|a, b| b.pagerank.total_cmp(&a.pagerank));
+        let orphans: Vec<_> = self.pages.iter().filter(|p| p.orphan).collect();
+        let dead_ends: Vec<_> = self.pages.iter().filter(|p| p.dead_end).collect();
+        let unreachable = self
+            .pages
+            .iter()
+            .filter(|p| p.click_depth.is_none())
+            .count();
+        let max_depth = self.pages.iter().filter_map(|p| p.click_depth).max();
+
+        Report {
+            title: format!("Link analysis: crawl {}", self.crawl_id),
+            sections: vec![
+                ReportSection {
+                    heading: "Top pages by PageRank".to_string(),
+                    kind: "added",
+                    rows: by_rank.into_iter().take(20).map(row).collect(),
+                },
+                ReportSection {
+                    heading: "Orphan pages".to_string(),
+                    kind: "removed",
+                    rows: orphans.iter().map(|p| row(p)).collect(),
+                },
+                ReportSection {
+                    heading: "Dead-end pages".to_string(),
+                    kind: "modified",
+                    rows: dead_ends.iter().map(|p| row(p)).collect(),
+                },
+            ],
+            summary: format!(
+                "{} pages, {} orphan, {} dead end, {} unreachable from seeds, max click depth {}",
+                self.pages.len(),
+                orphans.len(),
+                dead_ends.len(),
+                unreachable,
+                max_depth
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            ),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Page URLs listed in a sitemap, read from a local file or fetched over
+/// HTTP. Sitemap indexes are followed one level down.
+pub async fn load_sitemap(source: &str) -> Result<Vec<String>> {
+    let xml = read_source(source).await?;
+    let (is_index, locs) = parse_sitemap(&xml);
+    if !is_index {
+        return Ok(locs);
+    }
+
+    let mut urls = Vec::new();
+    for child in locs {
+        match read_source(&child).await {
+            Ok(xml) => urls.extend(parse_sitemap(&xml).1),
+            Err(e) => tracing::warn!("Skipping sitemap {}: {}", child, e),
+        }
+    }
+    Ok(urls)
+}
+
+async fn read_source(source: &str) -> Result<String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        Ok(reqwest::get(source)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("Failed to read {}", source))
+    }
+}
+
+/// Whether the document is a sitemap index, and the contents of its
+/// `<loc>` elements.
+fn parse_sitemap(xml: &str) -> (bool, Vec<String>) {
+    let mut locs = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<loc>") {
+        rest = &rest[start + "<loc>".len()..];
+        let Some(end) = rest.find("</loc>") else {
+            break;
+        };
+        let loc = rest[..end]
+            .trim()
+            .trim_start_matches("<![CDATA[")
+            .trim_end_matches("]]>")
+            .replace("&amp;", "&")
+            .replace("&apos;", "'")
+            .replace("&quot;", "\"")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">");
+        locs.push(loc);
+        rest = &rest[end..];
+    }
+    (xml.contains("<sitemapindex"), locs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(source: &str, target: &str, nofollow: bool) -> Link {
+        Link {
+            source: source.to_string(),
+            target: target.to_string(),
+            anchor_text: String::new(),
+            rel: String::new(),
+            nofollow,
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn test_analyze_link_graph() {
+        let home = "http://site.test/";
+        let a = "http://site.test/a";
+        let b = "http://site.test/docs/b";
+        let lost = "http://site.test/lost";
+        let crawled = [home, a, b].map(String::from);
+        let links = [
+            link(home, a, false),
+            link(home, a, false),
+            link(home, "http://other.test", false),
+            link(a, b, false),
+            link(a, home, false),
+            link(b, home, true),
+            link(b, b, false),
+        ];
+
+        let analysis = analyze(
+            1,
+            &crawled,
+            &links,
+            &[home.to_string()],
+            &[lost.to_string(), format!("{}/", b)],
+        );
+        let page = |url: &str| analysis.pages.iter().find(|p| p.url == url).unwrap();
+
+        assert_eq!(analysis.pages.len(), 4);
+        let total: f64 = analysis.pages.iter().map(|p| p.pagerank).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(page(home).pagerank > page(lost).pagerank);
+        assert!(page(a).pagerank > page(b).pagerank);
+
+        assert_eq!(page(home).click_depth, Some(0));
+        assert_eq!(page(b).click_depth, Some(2));
+        assert_eq!(page(b).path_depth, 2);
+        assert_eq!(page(lost).click_depth, None);
+
+        assert_eq!((page(home).inlinks, page(home).outlinks), (2, 1));
+        assert_eq!((page(b).inlinks, page(b).outlinks), (1, 1));
+        assert!(page(lost).orphan && page(lost).in_sitemap && !page(lost).crawled);
+        assert!(!page(home).orphan);
+        assert!(!page(lost).dead_end);
+        assert!(!page(b).dead_end);
+
+        let mut csv = Vec::new();
+        analysis.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.contains("\nhttp://site.test/lost,"));
+        assert!(csv.contains(",,1,0,0,false,true,true,false\n"));
+    }
+
+    #[test]
+    fn test_parse_sitemap() {
+        let xml = r#"<?xml version="1.0"?>
+            <urlset><url><loc> http://site.test/?a=1&amp;b=2 </loc></url>
+            <url><loc><![CDATA[http://site.test/x]]></loc></url></urlset>"#;
+        assert_eq!(
+            parse_sitemap(xml),
+            (
+                false,
+                vec![
+                    "http://site.test/?a=1&b=2".to_string(),
+                    "http://site.test/x".to_string()
+                ]
+            )
+        );
+        assert!(parse_sitemap("<sitemapindex><sitemap><loc>x</loc></sitemap></sitemapindex>").0);
+    }
+}
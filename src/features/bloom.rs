@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"SPBLOOM1";
+
+/// Each new filter gets this share of the previous one's error rate, so the
+/// rate over all filters stays below the configured one.
+const TIGHTENING: f64 = 0.5;
+
+/// A Bloom filter that grows as items are added: once the newest filter is
+/// full, another one with twice the capacity and half the error rate is
+/// started. Membership may report false positives, never false negatives.
+pub struct ScalableBloom {
+    error_rate: f64,
+    initial_capacity: u64,
+    filters: Vec<Filter>,
+}
+
+struct Filter {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: u64,
+    len: u64,
+}
+
+impl Filter {
+    fn new(capacity: u64, error_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * error_rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let words = bits.div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity as f64 * ln2)
+            .round()
+            .max(1.0) as u32;
+        Self {
+            bits: vec![0; words as usize],
+            hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// Bit positions by double hashing the two halves of one digest.
+    fn positions(&self, (h1, h2): (u64, u64)) -> impl Iterator<Item = u64> + '_ {
+        let size = self.bits.len() as u64 * 64;
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % size)
+    }
+
+    fn contains(&self, hash: (u64, u64)) -> bool {
+        self.positions(hash)
+            .all(|p| self.bits[(p / 64) as usize] & (1 << (p % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: (u64, u64)) {
+        let positions: Vec<u64> = self.positions(hash).collect();
+        for p in positions {
+            self.bits[(p / 64) as usize] |= 1 << (p % 64);
+        }
+        self.len += 1;
+    }
+}
+
+fn hash(item: &str) -> (u64, u64) {
+    let digest = Sha256::digest(item.as_bytes());
+    let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+    // An odd step visits distinct positions for every hash function
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+    (h1, h2)
+}
+
+impl ScalableBloom {
+    pub fn new(initial_capacity: u64, error_rate: f64) -> Self {
+        Self {
+            error_rate,
+            initial_capacity: initial_capacity.max(1),
+            filters: vec![],
+        }
+    }
+
+    pub fn contains(&self, item: &str) -> bool {
+        let hash = hash(item);
+        self.filters.iter().any(|f| f.contains(hash))
+    }
+
+    /// Adds an item; returns false if it (probably) was already present.
+    pub fn insert(&mut self, item: &str) -> bool {
+        let hash = hash(item);
+        if self.filters.iter().any(|f| f.contains(hash)) {
+            return false;
+        }
+        if self.filters.last().is_none_or(|f| f.len >= f.capacity) {
+            let n = self.filters.len() as i32;
+            self.filters.push(Filter::new(
+                self.initial_capacity << n,
+                self.error_rate * (1.0 - TIGHTENING) * TIGHTENING.powi(n),
+            ));
+        }
+        self.filters.last_mut().unwrap().insert(hash);
+        true
+    }
+
+    pub fn initial_capacity(&self) -> u64 {
+        self.initial_capacity
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// Items added so far.
+    pub fn len(&self) -> u64 {
+        self.filters.iter().map(|f| f.len).sum()
+    }
+
+    /// Bytes held by the bit arrays.
+    pub fn memory_bytes(&self) -> usize {
+        self.filters.iter().map(|f| f.bits.len() * 8).sum()
+    }
+
+    /// Writes the filter to `path` through a temporary file, so an
+    /// interrupted save leaves the previous copy intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&self.error_rate.to_le_bytes())?;
+        out.write_all(&self.initial_capacity.to_le_bytes())?;
+        out.write_all(&(self.filters.len() as u32).to_le_bytes())?;
+        for f in &self.filters {
+            out.write_all(&(f.bits.len() as u64).to_le_bytes())?;
+            out.write_all(&f.hashes.to_le_bytes())?;
+            out.write_all(&f.capacity.to_le_bytes())?;
+            out.write_all(&f.len.to_le_bytes())?;
+            for word in &f.bits {
+                out.write_all(&word.to_le_bytes())?;
+            }
+        }
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut input = std::io::BufReader::new(
+            std::fs::File::open(path).with_context(|| format!("Opening {}", path.display()))?,
+        );
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            anyhow::bail!("{} is not a visited-URL filter", path.display());
+        }
+
+        let mut bloom = Self::new(0, f64::from_le_bytes(read(&mut input)?));
+        bloom.initial_capacity = u64::from_le_bytes(read(&mut input)?);
+        let count = u32::from_le_bytes(read(&mut input)?);
+        for _ in 0..count {
+            let words = u64::from_le_bytes(read(&mut input)?);
+            let hashes = u32::from_le_bytes(read(&mut input)?);
+            let capacity = u64::from_le_bytes(read(&mut input)?);
+            let len = u64::from_le_bytes(read(&mut input)?);
+            let bits = (0..words)
+                .map(|_| read(&mut input).map(u64::from_le_bytes))
+                .collect::<Result<_>>()?;
+            bloom.filters.push(Filter {
+                bits,
+                hashes,
+                capacity,
+                len,
+            });
+        }
+        Ok(bloom)
+    }
+}
+
+fn read<const N: usize>(input: &mut impl Read) -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_grows_and_round_trips() {
+        let mut bloom = ScalableBloom::new(100, 0.01);
+        for i in 0..1000 {
+            bloom.insert(&format!("http://example.com/{}", i));
+        }
+        assert!(!bloom.insert("http://example.com/5"));
+        // An insert that collides with earlier items is not counted
+        assert!(bloom.len() > 990 && bloom.len() <= 1000);
+        // 100 + 200 + 400 + 800
+        assert_eq!(bloom.filters.len(), 4);
+        assert!((0..1000).all(|i| bloom.contains(&format!("http://example.com/{}", i))));
+
+        let false_positives = (1000..11000)
+            .filter(|i| bloom.contains(&format!("http://example.com/{}", i)))
+            .count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("visited").join("1.bloom");
+        bloom.save(&path).unwrap();
+        let loaded = ScalableBloom::load(&path).unwrap();
+        assert_eq!(loaded.len(), bloom.len());
+        assert_eq!(loaded.memory_bytes(), bloom.memory_bytes());
+        assert!((0..1000).all(|i| loaded.contains(&format!("http://example.com/{}", i))));
+    }
+}
@@ -1,44 +1,475 @@
-use std::collections::HashSet;
-use std::sync::RwLock;
+use crate::config::{VisitedConfig, VisitedStrategy};
+use crate::features::bloom::ScalableBloom;
+use crate::features::dedupe::SimHashIndex;
+use crate::state::{PageInfo, StateStore};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 
 pub struct CacheManager {
-    visited_results: RwLock<HashSet<String>>,
+    visited: VisitedSet,
+    contents: RwLock<ContentIndex>,
+}
+
+/// Where visited URLs are remembered, chosen with `visited.strategy`.
+pub enum VisitedSet {
+    Exact(RwLock<HashSet<String>>),
+    Bloom {
+        filter: RwLock<ScalableBloom>,
+        /// Saved here when the crawl finishes
+        path: PathBuf,
+        /// Read from an earlier run, so already holds the crawl's results
+        loaded: bool,
+    },
+    /// The state database's results, with recently visited URLs in memory.
+    /// URLs cached without a saved result are only remembered while they
+    /// stay in the LRU.
+    Index {
+        store: Arc<dyn StateStore>,
+        crawl_id: i64,
+        recent: Mutex<Lru>,
+    },
+}
+
+impl VisitedSet {
+    pub fn from_config(
+        config: &VisitedConfig,
+        store: Arc<dyn StateStore>,
+        crawl_id: i64,
+    ) -> Result<Self> {
+        Ok(match config.strategy {
+            VisitedStrategy::Memory => VisitedSet::Exact(RwLock::new(HashSet::new())),
+            VisitedStrategy::Bloom => {
+                let path = PathBuf::from(&config.path).join(format!("crawl-{}.bloom", crawl_id));
+                let (filter, loaded) = if path.exists() {
+                    (ScalableBloom::load(&path)?, true)
+                } else {
+                    let bloom =
+                        ScalableBloom::new(config.expected_urls, config.false_positive_rate);
+                    (bloom, false)
+                };
+                VisitedSet::Bloom {
+                    filter: RwLock::new(filter),
+                    path,
+                    loaded,
+                }
+            }
+            VisitedStrategy::Sqlite => VisitedSet::Index {
+                store,
+                crawl_id,
+                recent: Mutex::new(Lru::new(config.lru_capacity)),
+            },
+        })
+    }
+}
+
+/// A bounded set that forgets the least recently used entry when full.
+pub struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, u64>,
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Marks `key` as used, adding it if needed. Returns whether it was
+    /// already present.
+    fn touch(&mut self, key: &str) -> bool {
+        self.tick += 1;
+        if let Some(tick) = self.entries.get_mut(key) {
+            self.order.remove(tick);
+            *tick = self.tick;
+            self.order.insert(self.tick, key.to_string());
+            return true;
+        }
+        if self.entries.len() >= self.capacity
+            && let Some((_, oldest)) = self.order.pop_first()
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(key.to_string(), self.tick);
+        self.order.insert(self.tick, key.to_string());
+        false
+    }
+
+    fn contains(&mut self, key: &str) -> bool {
+        self.entries.contains_key(key) && self.touch(key)
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// Size and footprint of the visited set, printed with the crawl stats.
+pub struct VisitedStats {
+    pub strategy: &'static str,
+    /// URLs held in memory (for `sqlite`, only the LRU)
+    pub urls: usize,
+    /// Approximate bytes used
+    pub memory_bytes: usize,
+}
+
+impl std::fmt::Display for VisitedStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {} URLs, {:.1} MiB",
+            self.strategy,
+            self.urls,
+            self.memory_bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+/// Heap and table overhead of a set of strings.
+fn strings_bytes<'a>(strings: impl Iterator<Item = &'a String>, slots: usize) -> usize {
+    strings.map(|s| s.capacity()).sum::<usize>() + slots * (size_of::<String>() + 1)
+}
+
+/// The first URL seen with each page content, by exact hash and by SimHash.
+struct ContentIndex {
+    exact: HashMap<String, String>,
+    near: SimHashIndex<String>,
 }
 
 impl CacheManager {
     pub fn new() -> Self {
         Self {
-            visited_results: RwLock::new(HashSet::new()),
+            visited: VisitedSet::Exact(RwLock::new(HashSet::new())),
+            contents: RwLock::new(ContentIndex {
+                exact: HashMap::new(),
+                near: SimHashIndex::new(0),
+            }),
+        }
+    }
+
+    pub fn with_visited(mut self, visited: VisitedSet) -> Self {
+        self.visited = visited;
+        self
+    }
+
+    /// Content lookups treat SimHashes at most `max_distance` bits apart as
+    /// the same page.
+    pub fn with_near_distance(mut self, max_distance: u32) -> Self {
+        self.contents = RwLock::new(ContentIndex {
+            exact: HashMap::new(),
+            near: SimHashIndex::new(max_distance),
+        });
+        self
+    }
+
+    pub async fn is_cached(&self, url: &str) -> bool {
+        match &self.visited {
+            VisitedSet::Exact(set) => set.read().unwrap().contains(url),
+            VisitedSet::Bloom { filter, .. } => filter.read().unwrap().contains(url),
+            VisitedSet::Index {
+                store,
+                crawl_id,
+                recent,
+            } => {
+                if recent.lock().unwrap().contains(url) {
+                    return true;
+                }
+                match store.is_visited(*crawl_id, url).await {
+                    Ok(visited) => {
+                        if visited {
+                            recent.lock().unwrap().touch(url);
+                        }
+                        visited
+                    }
+                    Err(e) => {
+                        tracing::warn!("Visited lookup failed for {}: {}", url, e);
+                        false
+                    }
+                }
+            }
         }
     }
 
-    pub fn is_cached(&self, url: &str) -> bool {
-        let cache = self.visited_results.read().unwrap();
-        cache.contains(url)
+    /// The URLs among `urls` not visited yet. With the `Index` strategy
+    /// they are looked up in one query rather than one each.
+    pub async fn uncached(&self, urls: Vec<String>) -> Vec<String> {
+        match &self.visited {
+            VisitedSet::Exact(set) => {
+                let set = set.read().unwrap();
+                urls.into_iter().filter(|u| !set.contains(u)).collect()
+            }
+            VisitedSet::Bloom { filter, .. } => {
+                let filter = filter.read().unwrap();
+                urls.into_iter().filter(|u| !filter.contains(u)).collect()
+            }
+            VisitedSet::Index {
+                store,
+                crawl_id,
+                recent,
+            } => {
+                let urls: Vec<String> = {
+                    let mut recent = recent.lock().unwrap();
+                    urls.into_iter().filter(|u| !recent.contains(u)).collect()
+                };
+                if urls.is_empty() {
+                    return urls;
+                }
+                let visited = match store.get_visited_among(*crawl_id, &urls).await {
+                    Ok(visited) => visited,
+                    Err(e) => {
+                        tracing::warn!("Visited lookup failed for {} URLs: {}", urls.len(), e);
+                        return urls;
+                    }
+                };
+                let mut recent = recent.lock().unwrap();
+                for url in &visited {
+                    recent.touch(url);
+                }
+                urls.into_iter().filter(|u| !visited.contains(u)).collect()
+            }
+        }
     }
 
     pub fn cache(&self, url: String) {
-        let mut cache = self.visited_results.write().unwrap();
-        cache.insert(url);
+        match &self.visited {
+            VisitedSet::Exact(set) => {
+                set.write().unwrap().insert(url);
+            }
+            VisitedSet::Bloom { filter, .. } => {
+                filter.write().unwrap().insert(&url);
+            }
+            VisitedSet::Index { recent, .. } => {
+                recent.lock().unwrap().touch(&url);
+            }
+        }
     }
 
     pub fn extend(&self, urls: Vec<String>) {
-        let mut cache = self.visited_results.write().unwrap();
-        cache.extend(urls);
+        match &self.visited {
+            VisitedSet::Exact(set) => set.write().unwrap().extend(urls),
+            VisitedSet::Bloom { filter, .. } => {
+                let mut filter = filter.write().unwrap();
+                for url in urls {
+                    filter.insert(&url);
+                }
+            }
+            // Already in the database
+            VisitedSet::Index { .. } => {}
+        }
+    }
+
+    /// Whether the URLs of earlier runs must be loaded with `extend` before
+    /// crawling. The `sqlite` strategy looks them up instead, and a saved
+    /// Bloom filter already holds them.
+    pub fn needs_preload(&self) -> bool {
+        match &self.visited {
+            VisitedSet::Exact(_) => true,
+            VisitedSet::Bloom { loaded, .. } => !loaded,
+            VisitedSet::Index { .. } => false,
+        }
+    }
+
+    /// Forgets every visited URL, for a recrawl that fetches them again.
+    pub fn clear(&self) {
+        match &self.visited {
+            VisitedSet::Exact(set) => set.write().unwrap().clear(),
+            VisitedSet::Bloom { filter, .. } => {
+                let mut filter = filter.write().unwrap();
+                *filter = ScalableBloom::new(filter.initial_capacity(), filter.error_rate());
+            }
+            // Lookups only match results saved in the current pass
+            VisitedSet::Index { recent, .. } => recent.lock().unwrap().clear(),
+        }
+    }
+
+    /// Saves a Bloom filter for the next run; other strategies keep nothing
+    /// outside the database.
+    pub fn persist(&self) -> Result<()> {
+        if let VisitedSet::Bloom { filter, path, .. } = &self.visited {
+            filter.read().unwrap().save(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> VisitedStats {
+        match &self.visited {
+            VisitedSet::Exact(set) => {
+                let set = set.read().unwrap();
+                VisitedStats {
+                    strategy: "memory",
+                    urls: set.len(),
+                    memory_bytes: strings_bytes(set.iter(), set.capacity()),
+                }
+            }
+            VisitedSet::Bloom { filter, .. } => {
+                let filter = filter.read().unwrap();
+                VisitedStats {
+                    strategy: "bloom",
+                    urls: filter.len() as usize,
+                    memory_bytes: filter.memory_bytes(),
+                }
+            }
+            VisitedSet::Index { recent, .. } => {
+                let recent = recent.lock().unwrap();
+                VisitedStats {
+                    strategy: "sqlite",
+                    urls: recent.entries.len(),
+                    // Each URL is held twice: in the map and in the order
+                    memory_bytes: strings_bytes(recent.entries.keys(), recent.entries.capacity())
+                        + strings_bytes(recent.order.values(), recent.order.len())
+                        + recent.entries.capacity() * size_of::<u64>() * 2,
+                }
+            }
+        }
+    }
+
+    /// Returns the earlier URL whose content matches this page's, exactly or
+    /// nearly. Otherwise the page is remembered as the original of its
+    /// content and `None` is returned.
+    pub fn claim_content(
+        &self,
+        url: &str,
+        content_hash: &str,
+        simhash: Option<u64>,
+    ) -> Option<String> {
+        let mut contents = self.contents.write().unwrap();
+        if let Some(original) = contents.exact.get(content_hash)
+            && original != url
+        {
+            return Some(original.clone());
+        }
+        if let Some(hash) = simhash
+            && let Some(original) = contents.near.near(hash).find(|o| *o != url)
+        {
+            return Some(original.clone());
+        }
+
+        contents
+            .exact
+            .insert(content_hash.to_string(), url.to_string());
+        if let Some(hash) = simhash {
+            contents.near.insert(hash, url.to_string());
+        }
+        None
+    }
+
+    /// Remembers the content of pages from an earlier run, so a resumed
+    /// crawl recognises duplicates of them.
+    pub fn extend_contents(&self, pages: &[PageInfo]) {
+        for page in pages.iter().filter(|p| p.duplicate_of.is_none()) {
+            if let Some(hash) = &page.content_hash {
+                self.claim_content(&page.url, hash, page.simhash);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::{MemoryStateStore, SearchIndex};
 
-    #[test]
-    fn test_cache_functionality() {
+    #[tokio::test]
+    async fn test_cache_functionality() {
         let manager = CacheManager::new();
         let url = "http://example.com".to_string();
 
-        assert!(!manager.is_cached(&url));
+        assert!(!manager.is_cached(&url).await);
         manager.cache(url.clone());
-        assert!(manager.is_cached(&url));
+        assert!(manager.is_cached(&url).await);
+    }
+
+    #[tokio::test]
+    async fn test_visited_strategies() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::new());
+        let crawl_id = store.create_crawl("test").await.unwrap();
+        let saved = "http://example.com/saved";
+        store
+            .save_result(
+                crawl_id,
+                saved,
+                &serde_json::json!({}),
+                None,
+                SearchIndex::Off,
+            )
+            .await
+            .unwrap();
+
+        let config = VisitedConfig {
+            strategy: VisitedStrategy::Bloom,
+            path: dir.path().to_string_lossy().into_owned(),
+            ..VisitedConfig::default()
+        };
+        let bloom = CacheManager::new()
+            .with_visited(VisitedSet::from_config(&config, store.clone(), crawl_id).unwrap());
+        assert!(bloom.needs_preload());
+        bloom.extend(vec![saved.to_string()]);
+        bloom.cache("http://example.com/a".to_string());
+        bloom.persist().unwrap();
+
+        // A second run picks the filter up instead of reloading results
+        let bloom = CacheManager::new()
+            .with_visited(VisitedSet::from_config(&config, store.clone(), crawl_id).unwrap());
+        assert!(!bloom.needs_preload());
+        assert!(bloom.is_cached("http://example.com/a").await);
+        assert!(bloom.is_cached(saved).await);
+        assert!(!bloom.is_cached("http://example.com/b").await);
+        assert_eq!(bloom.stats().urls, 2);
+
+        let config = VisitedConfig {
+            strategy: VisitedStrategy::Sqlite,
+            lru_capacity: 2,
+            ..VisitedConfig::default()
+        };
+        let index = CacheManager::new()
+            .with_visited(VisitedSet::from_config(&config, store.clone(), crawl_id).unwrap());
+        assert!(!index.needs_preload());
+        assert!(index.is_cached(saved).await);
+        index.cache("http://example.com/a".to_string());
+        index.cache("http://example.com/b".to_string());
+        assert!(index.is_cached("http://example.com/b").await);
+        // Evicted from the LRU, but found in the database again
+        assert!(index.is_cached(saved).await);
+        // Evicted in turn and never saved as a result
+        assert!(!index.is_cached("http://example.com/a").await);
+        assert_eq!(index.stats().urls, 2);
+        assert!(index.stats().to_string().starts_with("sqlite, 2 URLs"));
+        // Looked up together, in the LRU or else the database
+        let urls = ["http://example.com/b", saved, "http://example.com/c"];
+        assert_eq!(
+            index.uncached(urls.map(str::to_string).to_vec()).await,
+            vec!["http://example.com/c"]
+        );
+    }
+
+    #[test]
+    fn test_claim_content() {
+        let manager = CacheManager::new().with_near_distance(2);
+        let a = "http://example.com/a";
+
+        assert_eq!(manager.claim_content(a, "h1", Some(0b1111)), None);
+        // The original itself is never its own duplicate
+        assert_eq!(manager.claim_content(a, "h1", Some(0b1111)), None);
+        assert_eq!(
+            manager.claim_content("http://example.com/a?sid=1", "h1", None),
+            Some(a.to_string())
+        );
+        assert_eq!(
+            manager.claim_content("http://example.com/b", "h2", Some(0b0011)),
+            Some(a.to_string())
+        );
+        assert_eq!(
+            manager.claim_content("http://example.com/c", "h3", Some(0b1111 << 8)),
+            None
+        );
     }
 }
@@ -0,0 +1,164 @@
+use crate::state::Validators;
+use crate::url_parser::normalize_url;
+use spider::reqwest::header::{ETAG, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use spider::reqwest::{Method, Request, Response, StatusCode};
+use spider::reqwest_middleware::{Middleware, Next};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The validators in a response's headers, if it has any.
+pub fn validators(headers: &[(String, String)], size: u64) -> Option<Validators> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    };
+    let validators = Validators {
+        etag: header(ETAG.as_str()),
+        last_modified: header(LAST_MODIFIED.as_str()),
+        size,
+    };
+    (validators.etag.is_some() || validators.last_modified.is_some()).then_some(validators)
+}
+
+/// Pages a recrawl did not have to download again.
+#[derive(Default)]
+pub struct Revalidation {
+    not_modified: AtomicU64,
+    bytes_saved: AtomicU64,
+}
+
+impl Revalidation {
+    pub fn not_modified(&self) -> u64 {
+        self.not_modified.load(Ordering::Relaxed)
+    }
+
+    /// Body bytes of the unchanged pages as of their last download.
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_saved.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Display for Revalidation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pages, {:.1} MiB saved",
+            self.not_modified(),
+            self.bytes_saved() as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+/// Client middleware that turns fetches of known pages into conditional
+/// requests (`If-None-Match` / `If-Modified-Since`) and counts the ones the
+/// server answers with 304 Not Modified.
+pub struct ConditionalRequests {
+    /// By normalized URL
+    validators: HashMap<String, Validators>,
+    stats: Arc<Revalidation>,
+}
+
+impl ConditionalRequests {
+    pub fn new(validators: HashMap<String, Validators>, stats: Arc<Revalidation>) -> Self {
+        Self { validators, stats }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ConditionalRequests {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> spider::reqwest_middleware::Result<Response> {
+        let known = if req.method() == Method::GET {
+            self.validators.get(&normalize_url(req.url().as_str()))
+        } else {
+            None
+        };
+        if let Some(v) = known {
+            let headers = req.headers_mut();
+            if let Some(etag) = v
+                .etag
+                .as_deref()
+                .and_then(|e| HeaderValue::from_str(e).ok())
+            {
+                headers.insert(IF_NONE_MATCH, etag);
+            }
+            if let Some(date) = v
+                .last_modified
+                .as_deref()
+                .and_then(|d| HeaderValue::from_str(d).ok())
+            {
+                headers.insert(IF_MODIFIED_SINCE, date);
+            }
+        }
+
+        let res = next.run(req, extensions).await?;
+        if res.status() == StatusCode::NOT_MODIFIED
+            && let Some(v) = known
+        {
+            self.stats.not_modified.fetch_add(1, Ordering::Relaxed);
+            self.stats.bytes_saved.fetch_add(v.size, Ordering::Relaxed);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use spider::reqwest_middleware::ClientBuilder;
+
+    #[tokio::test]
+    async fn test_conditional_requests() {
+        let app = Router::new().route(
+            "/page",
+            get(|headers: HeaderMap| async move {
+                if headers.get("if-none-match").is_some_and(|v| v == "\"v1\"") {
+                    (axum::http::StatusCode::NOT_MODIFIED, "")
+                } else {
+                    (axum::http::StatusCode::OK, "fresh")
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let headers = vec![
+            ("ETag".to_string(), "\"v1\"".to_string()),
+            ("Content-Type".to_string(), "text/html".to_string()),
+        ];
+        let known = validators(&headers, 5000).unwrap();
+        assert_eq!(known.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(validators(&headers[1..], 10), None);
+
+        let stats = Arc::new(Revalidation::default());
+        let mut by_url = HashMap::new();
+        by_url.insert(normalize_url(&format!("{}/page", base)), known);
+        let client = ClientBuilder::new(spider::reqwest::Client::new())
+            .with(ConditionalRequests::new(by_url, stats.clone()))
+            .build();
+
+        let res = client.get(format!("{}/page", base)).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        let res = client
+            .get(format!("{}/page?new", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        assert_eq!(stats.not_modified(), 1);
+        assert_eq!(stats.bytes_saved(), 5000);
+        assert_eq!(stats.to_string(), "1 pages, 0.0 MiB saved");
+    }
+}
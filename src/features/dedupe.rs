@@ -0,0 +1,316 @@
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::state::PageInfo;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Words per shingle fed into the SimHash.
+const SHINGLE: usize = 3;
+
+/// 64-bit SimHash of a page's text over overlapping word shingles, or
+/// `None` for pages without text. Similar texts get hashes a few bits
+/// apart.
+pub fn simhash(text: &str) -> Option<u64> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE.min(words.len())) {
+        let hash = fnv1a(shingle.join(" ").as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| **w > 0)
+            .fold(0, |acc, (bit, _)| acc | (1 << bit)),
+    )
+}
+
+/// FNV-1a, used because hashes are stored and must not change between
+/// builds the way `DefaultHasher` may.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Finds stored hashes within `max_distance` bits without comparing against
+/// every one. The hash is cut into `max_distance + 1` bands; two hashes that
+/// differ in at most `max_distance` bits must agree on at least one band.
+pub struct SimHashIndex<T> {
+    max_distance: u32,
+    bands: Vec<(u32, u32)>,
+    tables: Vec<HashMap<u64, Vec<(u64, T)>>>,
+}
+
+impl<T: Clone> SimHashIndex<T> {
+    pub fn new(max_distance: u32) -> Self {
+        let count = (max_distance + 1).min(64);
+        let bands = (0..count)
+            .map(|i| (i * 64 / count, (i + 1) * 64 / count))
+            .collect();
+        Self {
+            max_distance,
+            bands,
+            tables: (0..count).map(|_| HashMap::new()).collect(),
+        }
+    }
+
+    fn keys(&self, hash: u64) -> impl Iterator<Item = u64> + '_ {
+        self.bands.iter().map(move |&(lo, hi)| {
+            let width = hi - lo;
+            let mask = if width == 64 {
+                u64::MAX
+            } else {
+                (1 << width) - 1
+            };
+            (hash >> lo) & mask
+        })
+    }
+
+    pub fn insert(&mut self, hash: u64, value: T) {
+        let keys: Vec<u64> = self.keys(hash).collect();
+        for (table, key) in self.tables.iter_mut().zip(keys) {
+            table.entry(key).or_default().push((hash, value.clone()));
+        }
+    }
+
+    /// Every stored value whose hash is within `max_distance` of `hash`.
+    /// A value may be returned more than once.
+    pub fn near(&self, hash: u64) -> impl Iterator<Item = &T> + '_ {
+        self.tables
+            .iter()
+            .zip(self.keys(hash).collect::<Vec<_>>())
+            .filter_map(|(table, key)| table.get(&key))
+            .flatten()
+            .filter(move |(h, _)| distance(*h, hash) <= self.max_distance)
+            .map(|(_, v)| v)
+    }
+}
+
+/// Pages with identical or near-identical content.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    /// Every page has the same content hash
+    pub exact: bool,
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicatesReport {
+    pub crawl_id: i64,
+    pub max_distance: u32,
+    pub pages: usize,
+    pub clusters: Vec<DuplicateCluster>,
+}
+
+/// Groups pages sharing a content hash or with SimHashes within
+/// `max_distance` bits. Only successful responses are compared, so error
+/// pages and redirects don't form clusters. Clusters are listed largest
+/// first.
+pub fn duplicate_clusters(
+    crawl_id: i64,
+    pages: &[PageInfo],
+    max_distance: u32,
+) -> DuplicatesReport {
+    let pages: Vec<&PageInfo> = pages
+        .iter()
+        .filter(|p| (200..300).contains(&p.status))
+        .collect();
+    let mut parent: Vec<usize> = (0..pages.len()).collect();
+
+    let mut by_hash: HashMap<&str, usize> = HashMap::new();
+    let mut index = SimHashIndex::new(max_distance);
+    for (i, page) in pages.iter().enumerate() {
+        if let Some(hash) = &page.content_hash {
+            match by_hash.get(hash.as_str()) {
+                Some(&first) => union(&mut parent, first, i),
+                None => {
+                    by_hash.insert(hash, i);
+                }
+            }
+        }
+        if let Some(simhash) = page.simhash {
+            let near: Vec<usize> = index.near(simhash).copied().collect();
+            for j in near {
+                union(&mut parent, j, i);
+            }
+            index.insert(simhash, i);
+        }
+    }
+
+    // Members stay in page order, so the first URL of a cluster is the
+    // earliest by URL
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..pages.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut clusters: Vec<DuplicateCluster> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| DuplicateCluster {
+            exact: members.iter().all(|&i| {
+                pages[i].content_hash.is_some()
+                    && pages[i].content_hash == pages[members[0]].content_hash
+            }),
+            urls: members.iter().map(|&i| pages[i].url.clone()).collect(),
+        })
+        .collect();
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.urls.len()));
+
+    DuplicatesReport {
+        crawl_id,
+        max_distance,
+        pages: pages.len(),
+        clusters,
+    }
+}
+
+fn find(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[i] = root;
+    root
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b)] = a.min(b);
+    }
+}
+
+impl DuplicatesReport {
+    pub fn to_report(&self) -> Report {
+        let duplicated: usize = self.clusters.iter().map(|c| c.urls.len() - 1).sum();
+        Report {
+            title: format!("Duplicates: crawl {}", self.crawl_id),
+            sections: self
+                .clusters
+                .iter()
+                .enumerate()
+                .map(|(i, cluster)| ReportSection {
+                    heading: format!(
+                        "Cluster {}: {} pages",
+                        i + 1,
+                        if cluster.exact {
+                            "identical"
+                        } else {
+                            "similar"
+                        }
+                    ),
+                    kind: if cluster.exact { "removed" } else { "modified" },
+                    rows: cluster
+                        .urls
+                        .iter()
+                        .map(|url| ReportRow {
+                            url: url.clone(),
+                            note: String::new(),
+                            fields: vec![],
+                        })
+                        .collect(),
+                })
+                .collect(),
+            summary: format!(
+                "{} pages, {} clusters, {} redundant pages (SimHash distance ≤ {})",
+                self.pages,
+                self.clusters.len(),
+                duplicated,
+                self.max_distance
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A few hundred words, about the length of a short article.
+    fn article() -> String {
+        (0..300)
+            .map(|i| format!("word{}", i * 7 % 101))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn page(url: &str, body: &str) -> PageInfo {
+        PageInfo {
+            url: url.to_string(),
+            final_url: url.to_string(),
+            status: 200,
+            canonical: None,
+            redirects: vec![],
+            content_hash: Some(format!("{:x}", fnv1a(body.as_bytes()))),
+            simhash: simhash(body),
+            duplicate_of: None,
+        }
+    }
+
+    #[test]
+    fn test_simhash_clusters() {
+        let article = article();
+        // A changed date or ad slot barely moves the hash; different text does
+        let tweaked = article.replacen("word14", "updated", 1);
+        let original = simhash(&article).unwrap();
+        assert!(distance(original, simhash(&tweaked).unwrap()) <= 3);
+        assert!(distance(original, simhash("An entirely different page").unwrap()) > 3);
+        assert_eq!(simhash("  "), None);
+
+        let pages = vec![
+            page("http://site.test/a", &article),
+            page("http://site.test/a?sessionid=1", &article),
+            page("http://site.test/a?utm_source=x", &article),
+            page("http://site.test/b", &tweaked),
+            page(
+                "http://site.test/c",
+                "Completely unrelated text about something else",
+            ),
+        ];
+
+        let exact = duplicate_clusters(1, &pages, 0);
+        assert_eq!(exact.clusters.len(), 1);
+        assert!(exact.clusters[0].exact);
+        assert_eq!(exact.clusters[0].urls.len(), 3);
+
+        let near = duplicate_clusters(1, &pages, 3);
+        assert_eq!(near.clusters.len(), 1);
+        assert!(!near.clusters[0].exact);
+        assert_eq!(near.clusters[0].urls.len(), 4);
+        assert!(
+            near.to_report()
+                .render_text()
+                .contains("Cluster 1: similar pages (4):")
+        );
+    }
+
+    #[test]
+    fn test_simhash_index_finds_all_within_distance() {
+        let mut index = SimHashIndex::new(3);
+        let base = 0x0123_4567_89ab_cdefu64;
+        index.insert(base, "base");
+        index.insert(base ^ 0b111, "three bits");
+        index.insert(base ^ (1 << 63 | 1 << 40 | 1 << 20 | 1), "four bits");
+
+        let mut found: Vec<_> = index.near(base).copied().collect();
+        found.sort();
+        found.dedup();
+        assert_eq!(found, vec!["base", "three bits"]);
+    }
+}
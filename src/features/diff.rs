@@ -0,0 +1,225 @@
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::url_parser::normalize_url;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A single extracted field whose value differs between two results.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Compares two extracted `data` objects field by field. Non-object values
+/// are treated as a single unnamed field.
+pub fn diff_fields(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let (Value::Object(a), Value::Object(b)) = (before, after) else {
+        if before == after {
+            return vec![];
+        }
+        return vec![FieldChange {
+            field: String::new(),
+            before: Some(before.clone()).filter(|v| !v.is_null()),
+            after: Some(after.clone()).filter(|v| !v.is_null()),
+        }];
+    };
+
+    let fields: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    fields
+        .into_iter()
+        .filter(|f| a.get(*f) != b.get(*f))
+        .map(|f| FieldChange {
+            field: f.clone(),
+            before: a.get(f).cloned(),
+            after: b.get(f).cloned(),
+        })
+        .collect()
+}
+
+/// A URL present in both crawls whose extracted data differs.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedPage {
+    pub url: String,
+    pub fields: Vec<FieldChange>,
+}
+
+/// Result of comparing two crawls of the same site.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrawlDiff {
+    pub crawl_a: i64,
+    pub crawl_b: i64,
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub changed: Vec<ChangedPage>,
+    pub unchanged: usize,
+}
+
+/// Compares two crawls' results by normalized URL, so trailing slashes,
+/// query order and host case don't show up as differences.
+pub fn diff_crawls(
+    crawl_a: i64,
+    results_a: Vec<(String, Value)>,
+    crawl_b: i64,
+    results_b: Vec<(String, Value)>,
+) -> CrawlDiff {
+    let index = |results: Vec<(String, Value)>| -> BTreeMap<String, Value> {
+        results
+            .into_iter()
+            .map(|(url, data)| (normalize_url(&url), data))
+            .collect()
+    };
+    let a = index(results_a);
+    let b = index(results_b);
+
+    let mut diff = CrawlDiff {
+        crawl_a,
+        crawl_b,
+        only_in_a: a.keys().filter(|u| !b.contains_key(*u)).cloned().collect(),
+        only_in_b: b.keys().filter(|u| !a.contains_key(*u)).cloned().collect(),
+        changed: vec![],
+        unchanged: 0,
+    };
+
+    for (url, data_a) in &a {
+        let Some(data_b) = b.get(url) else {
+            continue;
+        };
+        let fields = diff_fields(data_a, data_b);
+        if fields.is_empty() {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push(ChangedPage {
+                url: url.clone(),
+                fields,
+            });
+        }
+    }
+
+    diff
+}
+
+impl CrawlDiff {
+    pub fn to_report(&self) -> Report {
+        let urls_only = |urls: &[String]| -> Vec<ReportRow> {
+            urls.iter()
+                .map(|url| ReportRow {
+                    url: url.clone(),
+                    note: String::new(),
+                    fields: vec![],
+                })
+                .collect()
+        };
+
+        Report {
+            title: format!("Diff: crawl {} → crawl {}", self.crawl_a, self.crawl_b),
+            sections: vec![
+                ReportSection {
+                    heading: format!("Only in crawl {}", self.crawl_a),
+                    kind: "removed",
+                    rows: urls_only(&self.only_in_a),
+                },
+                ReportSection {
+                    heading: format!("Only in crawl {}", self.crawl_b),
+                    kind: "added",
+                    rows: urls_only(&self.only_in_b),
+                },
+                ReportSection {
+                    heading: "Extracted data differs".to_string(),
+                    kind: "modified",
+                    rows: self
+                        .changed
+                        .iter()
+                        .map(|page| ReportRow {
+                            url: page.url.clone(),
+                            note: String::new(),
+                            fields: field_rows(&page.fields),
+                        })
+                        .collect(),
+                },
+            ],
+            summary: format!(
+                "{} only in {}, {} only in {}, {} changed, {} unchanged",
+                self.only_in_a.len(),
+                self.crawl_a,
+                self.only_in_b.len(),
+                self.crawl_b,
+                self.changed.len(),
+                self.unchanged
+            ),
+        }
+    }
+}
+
+/// Formats field changes as (field, before, after) report rows.
+pub fn field_rows(fields: &[FieldChange]) -> Vec<(String, String, String)> {
+    fields
+        .iter()
+        .map(|f| {
+            (
+                f.field.clone(),
+                display_value(&f.before),
+                display_value(&f.after),
+            )
+        })
+        .collect()
+}
+
+/// Renders a field value compactly for text reports.
+pub fn display_value(value: &Option<Value>) -> String {
+    match value {
+        None => "∅".to_string(),
+        Some(Value::String(s)) => format!("{:?}", s),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_fields() {
+        let before = json!({"price": "10", "title": "Widget", "stock": "yes"});
+        let after = json!({"price": "12", "title": "Widget", "rating": "5"});
+
+        let changes = diff_fields(&before, &after);
+        let fields: Vec<_> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["price", "rating", "stock"]);
+        assert_eq!(changes[0].before, Some(json!("10")));
+        assert_eq!(changes[0].after, Some(json!("12")));
+        assert_eq!(changes[1].before, None);
+        assert_eq!(changes[2].after, None);
+
+        assert!(diff_fields(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_diff_crawls() {
+        let a = vec![
+            ("https://example.com/a/".to_string(), json!({"title": "A"})),
+            (
+                "https://example.com/b?y=2&x=1".to_string(),
+                json!({"title": "B"}),
+            ),
+            ("https://example.com/gone".to_string(), json!({})),
+        ];
+        let b = vec![
+            ("https://example.com/a".to_string(), json!({"title": "A"})),
+            (
+                "https://example.com/b?x=1&y=2".to_string(),
+                json!({"title": "B2"}),
+            ),
+            ("https://example.com/new".to_string(), json!({})),
+        ];
+
+        let diff = diff_crawls(1, a, 2, b);
+        assert_eq!(diff.only_in_a, vec!["https://example.com/gone"]);
+        assert_eq!(diff.only_in_b, vec!["https://example.com/new"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].url, "https://example.com/b?x=1&y=2");
+        assert_eq!(diff.unchanged, 1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::features::diff::{FieldChange, diff_fields, field_rows};
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::state::{ChangeKind, ResultVersion};
+use anyhow::Result;
+use serde::Serialize;
+
+/// Net change of one page over a reporting window.
+#[derive(Debug, Clone, Serialize)]
+pub struct PageChange {
+    pub url: String,
+    pub change: ChangeKind,
+    pub recorded_at: String,
+    pub fields: Vec<FieldChange>,
+}
+
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` or RFC 3339 and returns the
+/// UTC timestamp in the same format SQLite stores (`CURRENT_TIMESTAMP`).
+pub fn parse_since(input: &str) -> Result<String> {
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    let input = input.trim();
+
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&chrono::Utc).format(FORMAT).to_string());
+    }
+    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(input, FORMAT) {
+        return Ok(dt.format(FORMAT).to_string());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(format!("{} 00:00:00", date.format("%Y-%m-%d")));
+    }
+    anyhow::bail!(
+        "Invalid date '{}': expected YYYY-MM-DD, 'YYYY-MM-DD HH:MM:SS' or RFC 3339",
+        input
+    )
+}
+
+/// Collapses a crawl's version history into the net change per URL since
+/// `since`. A page modified and then restored within the window is omitted.
+pub fn changes_since(versions: &[ResultVersion], since: &str) -> Vec<PageChange> {
+    let mut changes = Vec::new();
+
+    // Versions arrive ordered by URL then version
+    for history in versions.chunk_by(|a, b| a.url == b.url) {
+        let Some(latest) = history.last() else {
+            continue;
+        };
+        if latest.recorded_at.as_str() < since {
+            continue;
+        }
+
+        let baseline = history
+            .iter()
+            .rev()
+            .find(|v| v.recorded_at.as_str() < since)
+            .filter(|v| v.change != ChangeKind::Removed);
+        let current = Some(latest).filter(|v| v.change != ChangeKind::Removed);
+
+        let empty = serde_json::Value::Object(Default::default());
+        let change = match (baseline, current) {
+            (None, Some(_)) => ChangeKind::Added,
+            (Some(_), None) => ChangeKind::Removed,
+            (Some(before), Some(after)) => {
+                if before.data == after.data && before.content_hash == after.content_hash {
+                    continue;
+                }
+                ChangeKind::Modified
+            }
+            (None, None) => continue,
+        };
+
+        let before = baseline.and_then(|v| v.data.as_ref()).unwrap_or(&empty);
+        let after = current.and_then(|v| v.data.as_ref()).unwrap_or(&empty);
+        changes.push(PageChange {
+            url: latest.url.clone(),
+            change,
+            recorded_at: latest.recorded_at.clone(),
+            fields: diff_fields(before, after),
+        });
+    }
+
+    changes
+}
+
+/// Groups net changes into added / modified / removed report sections.
+pub fn changes_report(crawl_id: i64, since: &str, changes: &[PageChange]) -> Report {
+    let section = |kind: ChangeKind, heading: &str| {
+        let rows: Vec<ReportRow> = changes
+            .iter()
+            .filter(|c| c.change == kind)
+            .map(|c| ReportRow {
+                url: c.url.clone(),
+                note: c.recorded_at.clone(),
+                fields: if kind == ChangeKind::Modified {
+                    field_rows(&c.fields)
+                } else {
+                    vec![]
+                },
+            })
+            .collect();
+        ReportSection {
+            heading: heading.to_string(),
+            kind: kind.as_str(),
+            rows,
+        }
+    };
+
+    let sections = vec![
+        section(ChangeKind::Added, "Added"),
+        section(ChangeKind::Modified, "Modified"),
+        section(ChangeKind::Removed, "Removed"),
+    ];
+    let summary = format!(
+        "{} added, {} modified, {} removed",
+        sections[0].rows.len(),
+        sections[1].rows.len(),
+        sections[2].rows.len()
+    );
+
+    Report {
+        title: if since.is_empty() {
+            format!("Changes in crawl {}", crawl_id)
+        } else {
+            format!("Changes in crawl {} since {} UTC", crawl_id, since)
+        },
+        sections,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn version(
+        url: &str,
+        version: i64,
+        change: ChangeKind,
+        data: Option<serde_json::Value>,
+        at: &str,
+    ) -> ResultVersion {
+        ResultVersion {
+            url: url.to_string(),
+            version,
+            change,
+            data,
+            content_hash: None,
+            recorded_at: at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let versions = vec![
+            version(
+                "a",
+                1,
+                ChangeKind::Added,
+                Some(json!({"price": "10"})),
+                "2026-01-01 00:00:00",
+            ),
+            version(
+                "a",
+                2,
+                ChangeKind::Modified,
+                Some(json!({"price": "12"})),
+                "2026-02-01 00:00:00",
+            ),
+            version(
+                "b",
+                1,
+                ChangeKind::Added,
+                Some(json!({})),
+                "2026-01-01 00:00:00",
+            ),
+            version("b", 2, ChangeKind::Removed, None, "2026-02-01 00:00:00"),
+            version(
+                "c",
+                1,
+                ChangeKind::Added,
+                Some(json!({})),
+                "2026-02-01 00:00:00",
+            ),
+            version(
+                "d",
+                1,
+                ChangeKind::Added,
+                Some(json!({})),
+                "2026-01-01 00:00:00",
+            ),
+        ];
+
+        let since = parse_since("2026-01-15").unwrap();
+        let changes = changes_since(&versions, &since);
+        let summary: Vec<_> = changes.iter().map(|c| (c.url.as_str(), c.change)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", ChangeKind::Modified),
+                ("b", ChangeKind::Removed),
+                ("c", ChangeKind::Added),
+            ]
+        );
+        assert_eq!(changes[0].fields[0].field, "price");
+
+        // Everything is "added" when the window covers the whole history
+        let all = changes_since(&versions, "");
+        assert_eq!(
+            all.iter().filter(|c| c.change == ChangeKind::Added).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("2026-03-04").unwrap(), "2026-03-04 00:00:00");
+        assert_eq!(
+            parse_since("2026-03-04T10:00:00+02:00").unwrap(),
+            "2026-03-04 08:00:00"
+        );
+        assert!(parse_since("yesterday").is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::state::Link;
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error as _;
+use std::time::Duration;
+
+/// Outcome of requesting a link target.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "lowercase")]
+pub enum LinkStatus {
+    /// Final status after redirects, below 400
+    Ok(u16),
+    /// 4xx or 5xx
+    Http(u16),
+    Timeout,
+    /// The host name did not resolve
+    Dns,
+    /// Any other failure: refused connections, TLS errors, missing files
+    Error(String),
+}
+
+impl LinkStatus {
+    /// The outcome of a response with status `code`.
+    pub fn from_code(code: u16) -> Self {
+        if code < 400 {
+            LinkStatus::Ok(code)
+        } else {
+            LinkStatus::Http(code)
+        }
+    }
+
+    pub fn is_broken(&self) -> bool {
+        !matches!(self, LinkStatus::Ok(_))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            LinkStatus::Ok(code) | LinkStatus::Http(code) => format!("HTTP {}", code),
+            LinkStatus::Timeout => "timeout".to_string(),
+            LinkStatus::Dns => "DNS lookup failed".to_string(),
+            LinkStatus::Error(e) => e.clone(),
+        }
+    }
+}
+
+pub struct CheckOptions {
+    /// Hosts checked in parallel; requests to one host are always sequential
+    pub concurrency: usize,
+    /// Pause between two requests to the same host
+    pub delay: Duration,
+    pub timeout: Duration,
+}
+
+/// Requests every target once. Targets are grouped by host so each host
+/// sees one request at a time, `delay` apart.
+pub async fn check_links(
+    targets: impl IntoIterator<Item = String>,
+    options: &CheckOptions,
+) -> anyhow::Result<HashMap<String, LinkStatus>> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("spider-cli/", env!("CARGO_PKG_VERSION")))
+        .timeout(options.timeout)
+        .build()?;
+
+    let mut by_host: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for target in targets {
+        let host = url::Url::parse(&target)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        by_host.entry(host).or_default().push(target);
+    }
+
+    let results = futures::stream::iter(by_host.into_values())
+        .map(|urls| {
+            let client = client.clone();
+            async move {
+                let mut statuses = Vec::with_capacity(urls.len());
+                for (i, url) in urls.into_iter().enumerate() {
+                    if i > 0 {
+                        tokio::time::sleep(options.delay).await;
+                    }
+                    let status = check_one(&client, &url, options.delay).await;
+                    statuses.push((url, status));
+                }
+                statuses
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+async fn check_one(client: &reqwest::Client, url: &str, delay: Duration) -> LinkStatus {
+    if url.starts_with("file://") {
+        return match url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+        {
+            Some(path) if path.exists() => LinkStatus::Ok(200),
+            _ => LinkStatus::Http(404),
+        };
+    }
+
+    // Some servers reject or mishandle HEAD, so failures are confirmed with
+    // GET, as a second request to the host
+    match client.head(url).send().await {
+        Ok(res) if res.status().as_u16() < 400 => LinkStatus::Ok(res.status().as_u16()),
+        _ => {
+            tokio::time::sleep(delay).await;
+            match client.get(url).send().await {
+                Ok(res) => LinkStatus::from_code(res.status().as_u16()),
+                Err(e) => classify(&e),
+            }
+        }
+    }
+}
+
+fn classify(error: &reqwest::Error) -> LinkStatus {
+    if error.is_timeout() {
+        return LinkStatus::Timeout;
+    }
+    // hyper reports DNS failures as connect errors; only the source chain
+    // tells them apart. The innermost cause is the most useful message.
+    let mut root = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        root = e.to_string();
+        if root.contains("dns error") || root.contains("failed to lookup address") {
+            return LinkStatus::Dns;
+        }
+        source = e.source();
+    }
+    LinkStatus::Error(root)
+}
+
+/// A broken target and the link pointing at it from one page.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenLink {
+    pub target: String,
+    pub anchor_text: String,
+    pub status: LinkStatus,
+}
+
+/// Broken links grouped by the page that references them.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkCheckReport {
+    pub start_url: String,
+    pub pages_crawled: usize,
+    pub links_checked: usize,
+    /// Source page → its broken links, in page order
+    pub pages: BTreeMap<String, Vec<BrokenLink>>,
+}
+
+impl LinkCheckReport {
+    pub fn new(
+        start_url: &str,
+        pages_crawled: usize,
+        links: &[Link],
+        statuses: &HashMap<String, LinkStatus>,
+    ) -> Self {
+        let mut pages: BTreeMap<String, Vec<BrokenLink>> = BTreeMap::new();
+        for link in links {
+            let Some(status) = statuses.get(&link.target).filter(|s| s.is_broken()) else {
+                continue;
+            };
+            let broken = pages.entry(link.source.clone()).or_default();
+            // A page linking to the same target twice reports it once
+            if broken.iter().any(|b| b.target == link.target) {
+                continue;
+            }
+            broken.push(BrokenLink {
+                target: link.target.clone(),
+                anchor_text: link.anchor_text.clone(),
+                status: status.clone(),
+            });
+        }
+        Self {
+            start_url: start_url.to_string(),
+            pages_crawled,
+            links_checked: statuses.len(),
+            pages,
+        }
+    }
+
+    /// Distinct broken targets across all pages.
+    pub fn broken_count(&self) -> usize {
+        let mut targets: Vec<&str> = self
+            .pages
+            .values()
+            .flatten()
+            .map(|b| b.target.as_str())
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets.len()
+    }
+
+    pub fn to_report(&self) -> Report {
+        Report {
+            title: format!("Broken links: {}", self.start_url),
+            sections: self
+                .pages
+                .iter()
+                .map(|(page, broken)| ReportSection {
+                    heading: page.clone(),
+                    kind: "removed",
+                    rows: broken
+                        .iter()
+                        .map(|b| ReportRow {
+                            url: b.target.clone(),
+                            note: if b.anchor_text.is_empty() {
+                                b.status.describe()
+                            } else {
+                                format!("{}, \"{}\"", b.status.describe(), b.anchor_text)
+                            },
+                            fields: vec![],
+                        })
+                        .collect(),
+                })
+                .collect(),
+            summary: format!(
+                "{} pages crawled, {} links checked, {} broken on {} pages",
+                self.pages_crawled,
+                self.links_checked,
+                self.broken_count(),
+                self.pages.len()
+            ),
+        }
+    }
+
+    /// JUnit XML with one test suite per page that has broken links and a
+    /// failing test case for each of them, for CI test report viewers.
+    pub fn to_junit(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!(
+            "<testsuites name=\"check-links\" tests=\"{}\" failures=\"{}\">\n",
+            self.links_checked,
+            self.broken_count()
+        ));
+        for (page, broken) in &self.pages {
+            out.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+                xml_escape(page),
+                broken.len(),
+                broken.len()
+            ));
+            for b in broken {
+                out.push_str(&format!(
+                    "    <testcase classname=\"{}\" name=\"{}\">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                    xml_escape(page),
+                    xml_escape(&b.target),
+                    xml_escape(&b.status.describe()),
+                    xml_escape(&b.anchor_text)
+                ));
+            }
+            out.push_str("  </testsuite>\n");
+        }
+        out.push_str("</testsuites>\n");
+        out
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::{Method, StatusCode};
+    use axum::routing::{any, get};
+
+    #[tokio::test]
+    async fn test_check_links_and_report() {
+        let app = Router::new()
+            .route("/ok", get(|| async { "fine" }))
+            .route(
+                // Rejects HEAD but serves GET
+                "/no-head",
+                any(|method: Method| async move {
+                    if method == Method::HEAD {
+                        StatusCode::METHOD_NOT_ALLOWED
+                    } else {
+                        StatusCode::OK
+                    }
+                }),
+            )
+            .route(
+                "/error",
+                get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Nothing listens on a freshly released port
+        let closed = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/", l.local_addr().unwrap())
+        };
+
+        let targets = ["/ok", "/no-head", "/missing", "/error"]
+            .map(|p| format!("{}{}", base, p))
+            .to_vec();
+        let options = CheckOptions {
+            concurrency: 2,
+            delay: Duration::from_millis(1),
+            timeout: Duration::from_secs(5),
+        };
+        let statuses = check_links(targets.iter().cloned().chain([closed.clone()]), &options)
+            .await
+            .unwrap();
+
+        assert_eq!(statuses[&targets[0]], LinkStatus::Ok(200));
+        assert_eq!(statuses[&targets[1]], LinkStatus::Ok(200));
+        assert_eq!(statuses[&targets[2]], LinkStatus::Http(404));
+        assert_eq!(statuses[&targets[3]], LinkStatus::Http(500));
+        assert!(matches!(statuses[&closed], LinkStatus::Error(_)));
+
+        let link = |source: &str, target: &str| Link {
+            source: source.to_string(),
+            target: target.to_string(),
+            anchor_text: "a <b>".to_string(),
+            rel: String::new(),
+            nofollow: false,
+            position: 0,
+        };
+        let links = [
+            link("http://site/", &targets[0]),
+            link("http://site/", &targets[2]),
+            link("http://site/", &targets[2]),
+            link("http://site/about", &targets[2]),
+            link("http://site/about", &targets[3]),
+        ];
+        let report = LinkCheckReport::new(&base, 2, &links, &statuses);
+        assert_eq!(report.pages["http://site/"].len(), 1);
+        assert_eq!(report.pages["http://site/about"].len(), 2);
+        assert_eq!(report.broken_count(), 2);
+
+        let junit = report.to_junit();
+        assert!(junit.contains("<testsuites name=\"check-links\" tests=\"5\" failures=\"2\">"));
+        assert!(junit.contains("<failure message=\"HTTP 404\">a &lt;b&gt;</failure>"));
+        assert_eq!(junit.matches("<testcase ").count(), 3);
+    }
+}
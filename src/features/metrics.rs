@@ -0,0 +1,409 @@
+use crate::crawler::CrawlEvent;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds in seconds of the fetch latency buckets.
+const FETCH_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Upper bounds in seconds of the SQLite write latency buckets.
+const WRITE_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0];
+
+/// Counters, gauges and histograms for crawls and the dashboard, rendered in
+/// the Prometheus text format. One instance is shared by everything in the
+/// process that reports to it.
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    pages_fetched: u64,
+    bytes_fetched: u64,
+    /// By class: 4xx, 5xx, or network when no response arrived
+    fetch_errors: BTreeMap<&'static str, u64>,
+    /// By selector name
+    extraction_failures: BTreeMap<String, u64>,
+    /// By crawl ID
+    frontier_size: BTreeMap<i64, usize>,
+    /// By host
+    in_flight: BTreeMap<String, u64>,
+    fetch_latency: Histogram,
+    /// By store operation
+    write_latency: BTreeMap<&'static str, Histogram>,
+    /// Dashboard crawls by state
+    dashboard_crawls: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations in each bucket, not cumulative; the last is +Inf
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], value: Duration) {
+        let seconds = value.as_secs_f64();
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; bounds.len() + 1];
+        }
+        let bucket = bounds
+            .iter()
+            .position(|&b| seconds <= b)
+            .unwrap_or(bounds.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    /// Counts what a crawl reports about the pages it fetched.
+    pub fn record(&self, event: &CrawlEvent) {
+        let mut inner = self.inner.lock().unwrap();
+        match event {
+            CrawlEvent::Fetched { status, bytes, .. } => {
+                inner.pages_fetched += 1;
+                inner.bytes_fetched += bytes;
+                let class = match status {
+                    400..=499 => "4xx",
+                    500.. => "5xx",
+                    _ => return,
+                };
+                *inner.fetch_errors.entry(class).or_default() += 1;
+            }
+            CrawlEvent::Failed { url: Some(_), .. } => {
+                *inner.fetch_errors.entry("network").or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// A selector found nothing on a page that loaded fine.
+    pub fn extraction_failed(&self, field: &str) {
+        *self
+            .inner
+            .lock()
+            .unwrap()
+            .extraction_failures
+            .entry(field.to_string())
+            .or_default() += 1;
+    }
+
+    /// Pages a running crawl has queued but not fetched yet; `None` once it
+    /// stops.
+    pub fn set_frontier(&self, crawl_id: i64, size: Option<usize>) {
+        let mut inner = self.inner.lock().unwrap();
+        match size {
+            Some(size) => inner.frontier_size.insert(crawl_id, size),
+            None => inner.frontier_size.remove(&crawl_id),
+        };
+    }
+
+    /// Counts a request to `host` as in flight until the guard is dropped.
+    pub fn start_request(self: &Arc<Self>, host: &str) -> InFlight {
+        *self
+            .inner
+            .lock()
+            .unwrap()
+            .in_flight
+            .entry(host.to_string())
+            .or_default() += 1;
+        InFlight {
+            metrics: self.clone(),
+            host: host.to_string(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Times a write to the state store until the guard is dropped.
+    pub fn time_write(self: &Arc<Self>, operation: &'static str) -> WriteTimer {
+        WriteTimer {
+            metrics: self.clone(),
+            operation,
+            started: Instant::now(),
+        }
+    }
+
+    /// Dashboard crawls currently in `state` (running or queued).
+    pub fn set_dashboard_crawls(&self, state: &'static str, count: usize) {
+        self.inner
+            .lock()
+            .unwrap()
+            .dashboard_crawls
+            .insert(state, count);
+    }
+
+    /// Everything in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "spider_pages_fetched_total",
+            "counter",
+            "Responses received, whatever their status.",
+        );
+        let _ = writeln!(out, "spider_pages_fetched_total {}", inner.pages_fetched);
+        header(
+            &mut out,
+            "spider_bytes_fetched_total",
+            "counter",
+            "Response body bytes received.",
+        );
+        let _ = writeln!(out, "spider_bytes_fetched_total {}", inner.bytes_fetched);
+
+        header(
+            &mut out,
+            "spider_fetch_errors_total",
+            "counter",
+            "Fetches that failed, by class: 4xx, 5xx, or network when no response arrived.",
+        );
+        for (class, count) in &inner.fetch_errors {
+            let _ = writeln!(
+                out,
+                "spider_fetch_errors_total{{class=\"{}\"}} {}",
+                class, count
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_extraction_failures_total",
+            "counter",
+            "Selectors that matched nothing on a successfully fetched page, by field.",
+        );
+        for (field, count) in &inner.extraction_failures {
+            let _ = writeln!(
+                out,
+                "spider_extraction_failures_total{{field=\"{}\"}} {}",
+                escape(field),
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_frontier_size",
+            "gauge",
+            "Pages a running crawl has queued but not fetched yet.",
+        );
+        for (crawl_id, size) in &inner.frontier_size {
+            let _ = writeln!(
+                out,
+                "spider_frontier_size{{crawl_id=\"{}\"}} {}",
+                crawl_id, size
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_requests_in_flight",
+            "gauge",
+            "Requests waiting for a response, by host.",
+        );
+        for (host, count) in &inner.in_flight {
+            let _ = writeln!(
+                out,
+                "spider_requests_in_flight{{host=\"{}\"}} {}",
+                escape(host),
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "spider_fetch_duration_seconds",
+            "histogram",
+            "Time until response headers arrived.",
+        );
+        histogram(
+            &mut out,
+            "spider_fetch_duration_seconds",
+            "",
+            FETCH_BUCKETS,
+            &inner.fetch_latency,
+        );
+
+        header(
+            &mut out,
+            "spider_sqlite_write_duration_seconds",
+            "histogram",
+            "Time taken by writes to the SQLite state store, by operation.",
+        );
+        for (operation, h) in &inner.write_latency {
+            let labels = format!("operation=\"{}\",", operation);
+            histogram(
+                &mut out,
+                "spider_sqlite_write_duration_seconds",
+                &labels,
+                WRITE_BUCKETS,
+                h,
+            );
+        }
+
+        if !inner.dashboard_crawls.is_empty() {
+            header(
+                &mut out,
+                "spider_dashboard_crawls",
+                "gauge",
+                "Crawls started from the dashboard, by state.",
+            );
+            for (state, count) in &inner.dashboard_crawls {
+                let _ = writeln!(
+                    out,
+                    "spider_dashboard_crawls{{state=\"{}\"}} {}",
+                    state, count
+                );
+            }
+        }
+        out
+    }
+}
+
+/// A request in flight; see `Metrics::start_request`.
+pub struct InFlight {
+    metrics: Arc<Metrics>,
+    host: String,
+    started: Instant,
+}
+
+impl InFlight {
+    /// The response arrived: records how long it took.
+    pub fn responded(&self) {
+        self.metrics
+            .inner
+            .lock()
+            .unwrap()
+            .fetch_latency
+            .observe(FETCH_BUCKETS, self.started.elapsed());
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut inner = self.metrics.inner.lock().unwrap();
+        if let Some(count) = inner.in_flight.get_mut(&self.host) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// A store write being timed; see `Metrics::time_write`.
+pub struct WriteTimer {
+    metrics: Arc<Metrics>,
+    operation: &'static str,
+    started: Instant,
+}
+
+impl Drop for WriteTimer {
+    fn drop(&mut self) {
+        self.metrics
+            .inner
+            .lock()
+            .unwrap()
+            .write_latency
+            .entry(self.operation)
+            .or_default()
+            .observe(WRITE_BUCKETS, self.started.elapsed());
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Writes a histogram's series; `labels` are any others, each followed by a
+/// comma.
+fn histogram(out: &mut String, name: &str, labels: &str, bounds: &[f64], h: &Histogram) {
+    let mut cumulative = 0;
+    for (i, bound) in bounds
+        .iter()
+        .map(|b| b.to_string())
+        .chain(["+Inf".to_string()])
+        .enumerate()
+    {
+        cumulative += h.buckets.get(i).copied().unwrap_or(0);
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}le=\"{}\"}} {}",
+            name, labels, bound, cumulative
+        );
+    }
+    let labels = labels.trim_end_matches(',');
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    };
+    let _ = writeln!(out, "{}_sum{} {}", name, labels, h.sum);
+    let _ = writeln!(out, "{}_count{} {}", name, labels, h.count);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_text_format() {
+        let metrics = Arc::new(Metrics::default());
+        for status in [200, 404, 503] {
+            metrics.record(&CrawlEvent::Fetched {
+                url: "http://a.test/".to_string(),
+                status,
+                bytes: 100,
+                latency_ms: None,
+            });
+        }
+        metrics.record(&CrawlEvent::Failed {
+            url: Some("http://a.test/x".to_string()),
+            error: "connection refused".to_string(),
+        });
+        metrics.extraction_failed("ti\"tle");
+        let request = metrics.start_request("a.test");
+        request.responded();
+        let write = metrics.time_write("save_result");
+        drop(write);
+
+        let text = metrics.render();
+        assert!(text.contains("spider_requests_in_flight{host=\"a.test\"} 1\n"));
+        drop(request);
+        let text_after = metrics.render();
+        assert!(text_after.contains("spider_requests_in_flight{host=\"a.test\"} 0\n"));
+
+        for line in [
+            "# TYPE spider_pages_fetched_total counter",
+            "spider_pages_fetched_total 3",
+            "spider_bytes_fetched_total 300",
+            "spider_fetch_errors_total{class=\"4xx\"} 1",
+            "spider_fetch_errors_total{class=\"5xx\"} 1",
+            "spider_fetch_errors_total{class=\"network\"} 1",
+            "spider_extraction_failures_total{field=\"ti\\\"tle\"} 1",
+            "spider_fetch_duration_seconds_bucket{le=\"+Inf\"} 1",
+            "spider_fetch_duration_seconds_count 1",
+            "spider_sqlite_write_duration_seconds_bucket{operation=\"save_result\",le=\"+Inf\"} 1",
+            "spider_sqlite_write_duration_seconds_count{operation=\"save_result\"} 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {:?} in\n{}",
+                line,
+                text
+            );
+        }
+    }
+}
@@ -1,2 +1,16 @@
+pub mod analysis;
+pub mod bloom;
 pub mod cache;
+pub mod conditional;
+pub mod dedupe;
+pub mod diff;
+pub mod history;
+pub mod link_check;
+pub mod metrics;
 pub mod proxy;
+pub mod raw_store;
+pub mod redirects;
+pub mod report;
+pub mod schedule;
+pub mod timing;
+pub mod warc;
@@ -0,0 +1,268 @@
+use crate::config::{Compression, RawStoreConfig};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Metadata for one archived response. The body is stored once per distinct
+/// content under `objects/`, keyed by its SHA-256.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub url: String,
+    pub final_url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body_hash: String,
+    pub body_len: usize,
+    pub compression: Compression,
+    pub fetched_at: String,
+}
+
+/// On-disk archive of raw responses:
+///
+/// ```text
+/// <root>/objects/ab/abcdef….zst   content-addressed bodies
+/// <root>/crawls/<crawl_id>.jsonl  one StoredResponse per fetch
+/// ```
+pub struct RawStore {
+    root: PathBuf,
+    compression: Compression,
+}
+
+impl RawStore {
+    pub fn open(root: impl Into<PathBuf>, compression: Compression) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join("objects"))
+            .with_context(|| format!("Failed to create raw store at {}", root.display()))?;
+        fs::create_dir_all(root.join("crawls"))?;
+        Ok(Self { root, compression })
+    }
+
+    /// Opens the store described by a config, or `None` when archiving is off.
+    pub fn from_config(config: &RawStoreConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Self::open(&config.path, config.compression).map(Some)
+    }
+
+    fn object_path(&self, hash: &str, compression: Compression) -> PathBuf {
+        let ext = match compression {
+            Compression::Zstd => ".zst",
+            Compression::Gzip => ".gz",
+            Compression::None => "",
+        };
+        self.root
+            .join("objects")
+            .join(&hash[..2])
+            .join(format!("{}{}", hash, ext))
+    }
+
+    fn manifest_path(&self, crawl_id: i64) -> PathBuf {
+        self.root.join("crawls").join(format!("{}.jsonl", crawl_id))
+    }
+
+    /// Archives a response. Bodies already in the store, under any
+    /// compression, are not written again.
+    pub fn put(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        final_url: &str,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: &[u8],
+    ) -> Result<StoredResponse> {
+        let body_hash = hex::encode(Sha256::digest(body));
+
+        let existing = [Compression::Zstd, Compression::Gzip, Compression::None]
+            .into_iter()
+            .find(|c| self.object_path(&body_hash, *c).exists());
+        let compression = match existing {
+            Some(c) => c,
+            None => {
+                let path = self.object_path(&body_hash, self.compression);
+                fs::create_dir_all(path.parent().unwrap())?;
+                // Write then rename so a crash never leaves a truncated object;
+                // the temp name is unique so concurrent writers of the same
+                // body never share one
+                let mut tmp = path.clone().into_os_string();
+                tmp.push(format!(
+                    ".tmp.{}-{}",
+                    std::process::id(),
+                    uuid::Uuid::new_v4().simple()
+                ));
+                let tmp = PathBuf::from(tmp);
+                fs::write(&tmp, compress(body, self.compression)?)?;
+                fs::rename(&tmp, &path)?;
+                self.compression
+            }
+        };
+
+        let record = StoredResponse {
+            url: url.to_string(),
+            final_url: final_url.to_string(),
+            status,
+            headers,
+            body_hash,
+            body_len: body.len(),
+            compression,
+            fetched_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.manifest_path(crawl_id))?;
+        writeln!(manifest, "{}", serde_json::to_string(&record)?)?;
+
+        Ok(record)
+    }
+
+    /// Latest archived response for every URL of a crawl, ordered by URL.
+    pub fn responses(&self, crawl_id: i64) -> Result<Vec<StoredResponse>> {
+        let path = self.manifest_path(crawl_id);
+        if !path.exists() {
+            anyhow::bail!("No raw responses stored for crawl {}", crawl_id);
+        }
+        let mut latest = BTreeMap::new();
+        read_manifest(&path, &mut latest)?;
+        Ok(latest.into_values().collect())
+    }
+
+    /// Latest archived response for every URL across all crawls in the store.
+    pub fn all_responses(&self) -> Result<Vec<StoredResponse>> {
+        let mut manifests: Vec<PathBuf> = fs::read_dir(self.root.join("crawls"))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        manifests.retain(|p| p.extension().is_some_and(|e| e == "jsonl"));
+        // Crawl IDs increase over time, so later crawls win
+        manifests.sort_by_key(|p| {
+            p.file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<i64>().ok())
+        });
+
+        let mut latest = BTreeMap::new();
+        for path in manifests {
+            read_manifest(&path, &mut latest)?;
+        }
+        Ok(latest.into_values().collect())
+    }
+
+    /// Reads and decompresses a response body.
+    pub fn body(&self, response: &StoredResponse) -> Result<Vec<u8>> {
+        let path = self.object_path(&response.body_hash, response.compression);
+        let raw =
+            fs::read(&path).with_context(|| format!("Missing body object {}", path.display()))?;
+        decompress(&raw, response.compression)
+    }
+}
+
+fn read_manifest(path: &Path, latest: &mut BTreeMap<String, StoredResponse>) -> Result<()> {
+    let file = fs::File::open(path)?;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: StoredResponse = serde_json::from_str(&line)
+            .with_context(|| format!("Corrupt manifest entry in {}", path.display()))?;
+        latest.insert(record.url.clone(), record);
+    }
+    Ok(())
+}
+
+fn compress(body: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    Ok(match compression {
+        Compression::Zstd => zstd::encode_all(body, 3)?,
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            enc.write_all(body)?;
+            enc.finish()?
+        }
+        Compression::None => body.to_vec(),
+    })
+}
+
+fn decompress(raw: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    Ok(match compression {
+        Compression::Zstd => zstd::decode_all(raw)?,
+        Compression::Gzip => {
+            let mut out = Vec::new();
+            flate2::read::GzDecoder::new(raw).read_to_end(&mut out)?;
+            out
+        }
+        Compression::None => raw.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_store_dedupes_and_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RawStore::open(dir.path(), Compression::Zstd).unwrap();
+        let body = b"<html><title>Same</title></html>";
+
+        let a = store
+            .put(1, "http://a.test/", "http://a.test/", 200, vec![], body)
+            .unwrap();
+        let b = store
+            .put(
+                1,
+                "http://a.test/copy",
+                "http://a.test/copy",
+                200,
+                vec![("content-type".into(), "text/html".into())],
+                body,
+            )
+            .unwrap();
+        assert_eq!(a.body_hash, b.body_hash);
+
+        let objects: Vec<_> = walk(&dir.path().join("objects"));
+        assert_eq!(objects.len(), 1);
+
+        // A gzip store reuses the zstd object rather than writing a second copy
+        let gz = RawStore::open(dir.path(), Compression::Gzip).unwrap();
+        let c = gz
+            .put(2, "http://a.test/", "http://a.test/", 200, vec![], body)
+            .unwrap();
+        assert_eq!(c.compression, Compression::Zstd);
+        let other = gz
+            .put(
+                2,
+                "http://a.test/x",
+                "http://a.test/x",
+                404,
+                vec![],
+                b"gone",
+            )
+            .unwrap();
+        assert_eq!(gz.body(&other).unwrap(), b"gone");
+
+        let responses = store.responses(1).unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[1].headers[0].1, "text/html");
+        assert_eq!(store.body(&responses[0]).unwrap(), body);
+    }
+
+    fn walk(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .flat_map(|e| {
+                let path = e.unwrap().path();
+                if path.is_dir() {
+                    walk(&path)
+                } else {
+                    vec![path]
+                }
+            })
+            .collect()
+    }
+}
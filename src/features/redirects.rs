@@ -0,0 +1,352 @@
+use crate::features::report::{Report, ReportRow, ReportSection};
+use crate::state::{PageInfo, RedirectHop};
+use crate::url_parser::normalize_url;
+use serde::Serialize;
+use spider::configuration::get_ua;
+use spider::reqwest::{self, header::HeaderMap, redirect::Policy};
+use spider::utils::header_utils::setup_default_headers;
+use spider::website::Website;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Hops followed before a chain is given up on.
+const MAX_HOPS: usize = 20;
+
+/// The redirects each request went through, by normalized URL requested,
+/// until the crawl takes them.
+#[derive(Default)]
+pub struct RedirectLog {
+    chains: Mutex<HashMap<String, Vec<RedirectHop>>>,
+}
+
+impl RedirectLog {
+    /// The hops followed from `url` on its latest request, which are
+    /// forgotten afterwards.
+    pub fn take(&self, url: &str) -> Vec<RedirectHop> {
+        self.chains.lock().unwrap().remove(url).unwrap_or_default()
+    }
+
+    /// Follows up to `limit` redirects like reqwest does, recording every
+    /// hop. A redirect back to a URL already requested (a loop) is recorded
+    /// but not followed, so the redirect itself is the response. URLs are
+    /// compared exactly: `/docs` redirecting to `/docs/` is a hop, not a
+    /// loop, even though both normalize alike.
+    fn policy(self: Arc<Self>, limit: usize) -> Policy {
+        Policy::custom(move |attempt| {
+            let previous = attempt.previous();
+            let (Some(first), Some(from)) = (previous.first(), previous.last()) else {
+                return attempt.stop();
+            };
+            let hop = RedirectHop {
+                url: from.to_string(),
+                status: attempt.status().as_u16(),
+                location: attempt.url().to_string(),
+            };
+            let mut chains = self.chains.lock().unwrap();
+            let chain = chains.entry(normalize_url(first.as_str())).or_default();
+            // A new request for the same URL starts over
+            if previous.len() == 1 {
+                chain.clear();
+            }
+            chain.push(hop);
+            drop(chains);
+
+            if previous.contains(attempt.url()) {
+                attempt.stop()
+            } else {
+                Policy::limited(limit.min(MAX_HOPS)).redirect(attempt)
+            }
+        })
+    }
+}
+
+/// The client spider would build for `website`, except that the redirects
+/// it follows are recorded in `log`. Spider only reports where a page ended
+/// up, so without this every redirected page would be fetched again to
+/// learn the chain.
+pub fn client(website: &Website, log: Arc<RedirectLog>) -> reqwest::Result<reqwest::Client> {
+    let config = &website.configuration;
+    let user_agent = match &config.user_agent {
+        Some(ua) => ua.as_str(),
+        None => get_ua(false),
+    };
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .redirect(log.policy(*config.redirect_limit))
+        .danger_accept_invalid_certs(config.accept_invalid_certs)
+        .tcp_keepalive(Duration::from_millis(500));
+    if config.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+    builder = setup_default_headers(builder, config, HeaderMap::new(), website.get_url_parsed());
+    if let Some(timeout) = &config.request_timeout {
+        builder = builder.timeout(**timeout);
+    }
+    for proxy in config.proxies.iter().flat_map(|p| p.iter()) {
+        if let Ok(proxy) = reqwest::Proxy::all(proxy) {
+            builder = builder.proxy(proxy);
+        }
+    }
+    builder = builder.cookie_store(true);
+    if !config.cookie_str.is_empty()
+        && let Some(url) = website.get_url_parsed()
+    {
+        let jar = reqwest::cookie::Jar::default();
+        jar.add_cookie_str(&config.cookie_str, url);
+        builder = builder.cookie_provider(jar.into());
+    }
+    builder.build()
+}
+
+/// A redirect chain starting at a crawled URL.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedirectChain {
+    pub url: String,
+    pub hops: Vec<RedirectHop>,
+    /// The chain leads back to a URL it already passed through
+    pub looped: bool,
+}
+
+/// A page whose declared canonical URL is not the URL it was crawled at.
+#[derive(Debug, Clone, Serialize)]
+pub struct Canonicalized {
+    pub url: String,
+    pub canonical: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedirectReport {
+    pub crawl_id: i64,
+    pub max_chain: usize,
+    pub loops: Vec<RedirectChain>,
+    /// Chains with more than `max_chain` hops that do end somewhere
+    pub long_chains: Vec<RedirectChain>,
+    pub redirected: usize,
+    pub canonicalized: Vec<Canonicalized>,
+}
+
+/// Builds the full chain for every redirected page. Pages recorded with a
+/// single hop (archive replays see each response separately) are joined
+/// with the hops recorded for the exact URL they redirect to.
+pub fn redirect_report(crawl_id: i64, pages: &[PageInfo], max_chain: usize) -> RedirectReport {
+    let by_url: HashMap<String, &PageInfo> =
+        pages.iter().map(|p| (normalize_url(&p.url), p)).collect();
+
+    let mut report = RedirectReport {
+        crawl_id,
+        max_chain,
+        loops: vec![],
+        long_chains: vec![],
+        redirected: 0,
+        canonicalized: vec![],
+    };
+
+    for page in pages {
+        if let Some(canonical) = &page.canonical
+            && *canonical != normalize_url(&page.url)
+        {
+            report.canonicalized.push(Canonicalized {
+                url: page.url.clone(),
+                canonical: canonical.clone(),
+            });
+        }
+        if page.redirects.is_empty() {
+            continue;
+        }
+        report.redirected += 1;
+
+        let mut hops = page.redirects.clone();
+        let mut seen: HashSet<String> = hops.iter().map(|h| h.url.clone()).collect();
+        let mut looped = false;
+        loop {
+            let next = hops.last().unwrap().location.clone();
+            if seen.contains(&next) {
+                looped = true;
+                break;
+            }
+            match by_url.get(&normalize_url(&next)) {
+                Some(target)
+                    if target.redirects.first().is_some_and(|h| h.url == next)
+                        && hops.len() < MAX_HOPS =>
+                {
+                    seen.extend(target.redirects.iter().map(|h| h.url.clone()));
+                    hops.extend(target.redirects.iter().cloned());
+                }
+                _ => break,
+            }
+        }
+
+        let chain = RedirectChain {
+            url: page.url.clone(),
+            hops,
+            looped,
+        };
+        if chain.looped {
+            report.loops.push(chain);
+        } else if chain.hops.len() > max_chain {
+            report.long_chains.push(chain);
+        }
+    }
+    report
+}
+
+impl RedirectReport {
+    pub fn to_report(&self) -> Report {
+        let chain_row = |c: &RedirectChain| ReportRow {
+            url: c.url.clone(),
+            note: format!("{} hops", c.hops.len()),
+            fields: c
+                .hops
+                .iter()
+                .map(|h| (h.status.to_string(), h.url.clone(), h.location.clone()))
+                .collect(),
+        };
+
+        Report {
+            title: format!("Redirects: crawl {}", self.crawl_id),
+            sections: vec![
+                ReportSection {
+                    heading: "Redirect loops".to_string(),
+                    kind: "removed",
+                    rows: self.loops.iter().map(chain_row).collect(),
+                },
+                ReportSection {
+                    heading: format!("Chains longer than {} hops", self.max_chain),
+                    kind: "modified",
+                    rows: self.long_chains.iter().map(chain_row).collect(),
+                },
+                ReportSection {
+                    heading: "Pages with a different canonical URL".to_string(),
+                    kind: "added",
+                    rows: self
+                        .canonicalized
+                        .iter()
+                        .map(|c| ReportRow {
+                            url: c.url.clone(),
+                            note: format!("canonical {}", c.canonical),
+                            fields: vec![],
+                        })
+                        .collect(),
+                },
+            ],
+            summary: format!(
+                "{} redirected pages, {} loops, {} chains over {} hops, {} canonicalized",
+                self.redirected,
+                self.loops.len(),
+                self.long_chains.len(),
+                self.max_chain,
+                self.canonicalized.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(url: &str, hops: &[(&str, &str)]) -> PageInfo {
+        PageInfo {
+            url: url.to_string(),
+            final_url: hops.last().map_or(url, |h| h.1).to_string(),
+            status: if hops.is_empty() { 200 } else { 301 },
+            canonical: None,
+            redirects: hops
+                .iter()
+                .map(|(from, to)| RedirectHop {
+                    url: from.to_string(),
+                    status: 301,
+                    location: to.to_string(),
+                })
+                .collect(),
+            content_hash: None,
+            simhash: None,
+            duplicate_of: None,
+        }
+    }
+
+    #[test]
+    fn test_redirect_report() {
+        let mut home = page("http://site.test/", &[]);
+        home.canonical = Some("http://site.test/index".to_string());
+        let pages = vec![
+            home,
+            // Traced live: the whole chain on one page
+            page(
+                "http://site.test/a",
+                &[
+                    ("http://site.test/a", "http://site.test/b"),
+                    ("http://site.test/b", "http://site.test/c"),
+                    ("http://site.test/c", "http://site.test/"),
+                ],
+            ),
+            // Replayed: one hop per page, forming a loop
+            page(
+                "http://site.test/x",
+                &[("http://site.test/x", "http://site.test/y")],
+            ),
+            page(
+                "http://site.test/y",
+                &[("http://site.test/y", "http://site.test/x")],
+            ),
+            page(
+                "http://site.test/short",
+                &[("http://site.test/short", "http://site.test/")],
+            ),
+            // Only differs from its target by normalization
+            page(
+                "http://site.test/docs",
+                &[("http://site.test/docs", "http://site.test/docs/")],
+            ),
+        ];
+
+        let report = redirect_report(1, &pages, 2);
+        assert_eq!(report.redirected, 5);
+        assert_eq!(report.long_chains.len(), 1);
+        assert_eq!(report.long_chains[0].hops.len(), 3);
+        assert_eq!(report.loops.len(), 2);
+        assert!(report.loops.iter().all(|c| c.hops.len() == 2));
+        assert_eq!(report.canonicalized.len(), 1);
+
+        let text = report.to_report().render_text();
+        assert!(text.contains("Redirect loops (2):"));
+        assert!(text.contains("301: http://site.test/a -> http://site.test/b"));
+    }
+
+    #[tokio::test]
+    async fn test_client_records_redirects_and_stops_at_loop() {
+        use axum::Router;
+        use axum::response::Redirect;
+        use axum::routing::get;
+
+        let app = Router::new()
+            .route("/start", get(|| async { Redirect::permanent("/next") }))
+            .route("/next", get(|| async { Redirect::temporary("/start") }))
+            .route("/moved", get(|| async { Redirect::to("/done") }))
+            .route("/done", get(|| async { "ok" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let log = Arc::new(RedirectLog::default());
+        let client = client(&Website::new(&base), log.clone()).unwrap();
+
+        let start = format!("{}/start", base);
+        let res = client.get(&start).send().await.unwrap();
+        assert_eq!(res.status(), 307);
+        let hops = log.take(&start);
+        let statuses: Vec<_> = hops.iter().map(|h| h.status).collect();
+        assert_eq!(statuses, vec![308, 307]);
+        assert_eq!(hops[1].location, start);
+        assert!(log.take(&start).is_empty());
+
+        let moved = format!("{}/moved", base);
+        let res = client.get(&moved).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "ok");
+        let hops = log.take(&moved);
+        assert_eq!(hops.len(), 1);
+        assert_eq!(hops[0].status, 303);
+        assert_eq!(hops[0].location, format!("{}/done", base));
+    }
+}
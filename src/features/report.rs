@@ -0,0 +1,102 @@
+use askama::Template;
+
+/// Format-neutral report shared by the `changes` and `diff` commands.
+pub struct Report {
+    pub title: String,
+    pub sections: Vec<ReportSection>,
+    pub summary: String,
+}
+
+pub struct ReportSection {
+    pub heading: String,
+    /// `added`, `removed` or `modified`; picks the marker and colour
+    pub kind: &'static str,
+    pub rows: Vec<ReportRow>,
+}
+
+pub struct ReportRow {
+    pub url: String,
+    pub note: String,
+    /// (field, before, after), already formatted for display
+    pub fields: Vec<(String, String, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "report.html")]
+struct ReportTemplate<'a> {
+    report: &'a Report,
+}
+
+impl ReportSection {
+    fn marker(&self) -> char {
+        match self.kind {
+            "added" => '+',
+            "removed" => '-',
+            _ => '~',
+        }
+    }
+}
+
+impl Report {
+    pub fn render_text(&self) -> String {
+        let mut out = format!("{}\n", self.title);
+        for section in &self.sections {
+            out.push_str(&format!(
+                "\n{} ({}):\n",
+                section.heading,
+                section.rows.len()
+            ));
+            for row in &section.rows {
+                if row.note.is_empty() {
+                    out.push_str(&format!("  {} {}\n", section.marker(), row.url));
+                } else {
+                    out.push_str(&format!(
+                        "  {} {}  ({})\n",
+                        section.marker(),
+                        row.url,
+                        row.note
+                    ));
+                }
+                for (field, before, after) in &row.fields {
+                    out.push_str(&format!("        {}: {} -> {}\n", field, before, after));
+                }
+            }
+        }
+        out.push_str(&format!("\nSummary: {}\n", self.summary));
+        out
+    }
+
+    pub fn render_html(&self) -> anyhow::Result<String> {
+        Ok(ReportTemplate { report: self }.render()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_rendering() {
+        let report = Report {
+            title: "Diff <a>".to_string(),
+            sections: vec![ReportSection {
+                heading: "Changed".to_string(),
+                kind: "modified",
+                rows: vec![ReportRow {
+                    url: "http://example.com/".to_string(),
+                    note: String::new(),
+                    fields: vec![("price".into(), "\"10\"".into(), "\"12\"".into())],
+                }],
+            }],
+            summary: "1 changed".to_string(),
+        };
+
+        let text = report.render_text();
+        assert!(text.contains("Changed (1):\n  ~ http://example.com/\n"));
+        assert!(text.contains("price: \"10\" -> \"12\""));
+
+        let html = report.render_html().unwrap();
+        assert!(html.contains("Diff &#60;a&#62;") || html.contains("Diff &lt;a&gt;"));
+        assert!(html.contains("class=\"section modified\""));
+    }
+}
@@ -0,0 +1,544 @@
+use crate::config::{ConfigLoader, SpiderConfig};
+use crate::crawler::Crawler;
+use crate::features::metrics::Metrics;
+use crate::state::{RunStatus, StateStore};
+use crate::ui::{EventHub, relay};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Longest the scheduler sleeps before looking at the clock again, so a
+/// suspended machine or clock change doesn't delay runs by much.
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// When a config is crawled: a five-field cron expression evaluated in UTC,
+/// or a fixed interval between run starts.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Cron(Cron),
+    Every(Duration),
+}
+
+impl Schedule {
+    /// Parses `0 3 * * *`, `@daily` or `every 6h` (units `m`, `h`, `d`).
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        if let Some(interval) = spec
+            .strip_prefix("@every")
+            .or_else(|| spec.strip_prefix("every"))
+        {
+            return parse_interval(interval.trim())
+                .map(Schedule::Every)
+                .with_context(|| format!("Invalid interval: {}", spec));
+        }
+        Cron::parse(spec)
+            .map(Schedule::Cron)
+            .with_context(|| format!("Invalid cron expression: {}", spec))
+    }
+
+    /// The first run strictly after `after`, or `None` if the expression
+    /// can never match (e.g. February 30th).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Cron(cron) => cron.next_after(after),
+            Schedule::Every(interval) => after.checked_add_signed(*interval),
+        }
+    }
+}
+
+fn parse_interval(s: &str) -> Result<Duration> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .context("Missing unit")?;
+    let (count, unit) = s.split_at(split);
+    let count: i64 = count.parse().context("Missing count")?;
+    let interval = match unit {
+        "m" => Duration::try_minutes(count),
+        "h" => Duration::try_hours(count),
+        "d" => Duration::try_days(count),
+        _ => anyhow::bail!("Unknown unit {:?}, expected m, h or d", unit),
+    }
+    .context("Interval is too long")?;
+    if count == 0 {
+        anyhow::bail!("Interval must be positive");
+    }
+    Ok(interval)
+}
+
+/// A parsed cron expression. Every field is a bit set of allowed values.
+#[derive(Debug, Clone)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day-of-month / day-of-week fields were restricted; when
+    /// both are, a day matching either runs, as in classic cron
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl Cron {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = match spec {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            anyhow::bail!("Expected 5 fields, found {}", fields.len());
+        };
+
+        // Sunday may be written as 7
+        let mut weekdays = field(weekday, 0, 7, &WEEKDAYS)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: field(minute, 0, 59, &[])?,
+            hours: field(hour, 0, 23, &[])?,
+            days: field(day, 1, 31, &[])?,
+            months: field(month, 1, 12, &MONTHS)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.naive_utc().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Every valid expression matches within a leap-year cycle
+        let limit = t + Duration::days(366 * 4 + 1);
+        while t < limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.day_matches(t.date()) {
+                t = midnight(t.date().succ_opt()?);
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(Utc.from_utc_datetime(&t));
+            }
+        }
+        None
+    }
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap()
+}
+
+/// Bit set of the values allowed by one cron field: `*`, `5`, `1-5`, `*/15`,
+/// `10-40/10` or a comma-separated list of those. `names` maps `jan`/`mon`
+/// style names to values starting at `min`.
+fn field(spec: &str, min: u32, max: u32, names: &[&str]) -> Result<u64> {
+    let value = |s: &str| -> Result<u32> {
+        let lower = s.to_ascii_lowercase();
+        let v = match names.iter().position(|n| *n == lower) {
+            Some(i) => i as u32 + min,
+            None => s
+                .parse()
+                .with_context(|| format!("Invalid value {:?}", s))?,
+        };
+        if v < min || v > max {
+            anyhow::bail!("{} is outside {}-{}", v, min, max);
+        }
+        Ok(v)
+    };
+
+    let mut bits = 0u64;
+    for item in spec.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid step")?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            anyhow::bail!("Step must be positive");
+        }
+        let (lo, hi) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((lo, hi)) => (value(lo)?, value(hi)?),
+            // `5/15` means from 5 to the end in steps of 15
+            None if item.contains('/') => (value(range)?, max),
+            None => {
+                let v = value(range)?;
+                (v, v)
+            }
+        };
+        if lo > hi {
+            anyhow::bail!("Empty range {}", range);
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+/// A config the daemon runs on its schedule.
+struct Job {
+    /// The config's `name`, which its runs are recorded under
+    name: String,
+    path: PathBuf,
+    spec: String,
+    schedule: Schedule,
+    next: Mutex<Option<DateTime<Utc>>>,
+    running: AtomicBool,
+}
+
+/// A scheduled config as shown on the dashboard.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UpcomingRun {
+    pub config: String,
+    pub schedule: String,
+    /// UTC, `None` when the schedule never fires again
+    pub next_run: Option<DateTime<Utc>>,
+    pub running: bool,
+}
+
+/// Launches crawls of configs on their `schedule`. Each run is a new crawl,
+/// recorded with the config's name; a run that comes due while the previous
+/// run of the same config is still going is skipped.
+pub struct Scheduler {
+    state_manager: Arc<dyn StateStore>,
+    jobs: Vec<Arc<Job>>,
+    metrics: Option<Arc<Metrics>>,
+    events: Option<EventHub>,
+}
+
+impl Scheduler {
+    /// Loads the configs, each of which must have a valid `schedule`.
+    /// Interval schedules pick up from the config's last recorded run, so
+    /// restarting the daemon doesn't crawl again early.
+    pub async fn new(paths: &[PathBuf], state_manager: Arc<dyn StateStore>) -> Result<Self> {
+        let now = Utc::now();
+        let mut jobs: Vec<Arc<Job>> = Vec::new();
+        for path in paths {
+            let config = ConfigLoader::load(path)?;
+            let spec = config
+                .schedule
+                .clone()
+                .with_context(|| format!("{} has no `schedule`", path.display()))?;
+            let schedule =
+                Schedule::parse(&spec).with_context(|| format!("In {}", path.display()))?;
+            if jobs.iter().any(|j| j.name == config.name) {
+                anyhow::bail!("More than one scheduled config is named {:?}", config.name);
+            }
+
+            let next = match &schedule {
+                Schedule::Every(interval) => {
+                    let last = state_manager
+                        .get_scheduled_runs(Some(&config.name), 1)
+                        .await?
+                        .first()
+                        .and_then(|r| parse_timestamp(&r.started_at));
+                    last.map_or(Some(now), |t| {
+                        t.checked_add_signed(*interval).map(|next| next.max(now))
+                    })
+                }
+                Schedule::Cron(cron) => Some(
+                    cron.next_after(now)
+                        .with_context(|| format!("{:?} never runs", spec))?,
+                ),
+            };
+            jobs.push(Arc::new(Job {
+                name: config.name,
+                path: path.clone(),
+                spec,
+                schedule,
+                next: Mutex::new(next),
+                running: AtomicBool::new(false),
+            }));
+        }
+        Ok(Self {
+            state_manager,
+            jobs,
+            metrics: None,
+            events: None,
+        })
+    }
+
+    /// Report the scheduled crawls to `metrics`.
+    pub fn with_metrics(mut self, metrics: Option<Arc<Metrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Stream the scheduled crawls' progress to the dashboard's live feeds.
+    pub fn with_events(mut self, events: Option<EventHub>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn upcoming(&self) -> Vec<UpcomingRun> {
+        let mut upcoming: Vec<UpcomingRun> = self
+            .jobs
+            .iter()
+            .map(|job| UpcomingRun {
+                config: job.name.clone(),
+                schedule: job.spec.clone(),
+                next_run: *job.next.lock().unwrap(),
+                running: job.running.load(Ordering::SeqCst),
+            })
+            .collect();
+        upcoming.sort_by_key(|u| u.next_run.unwrap_or(DateTime::<Utc>::MAX_UTC));
+        upcoming
+    }
+
+    /// Runs until `cancel` fires, then cancels the running crawls and waits
+    /// for them to stop.
+    pub async fn run(self: Arc<Self>, cancel: CancellationToken) -> Result<()> {
+        for job in self.upcoming() {
+            tracing::info!(
+                "Scheduled {} ({}), next run {}",
+                job.config,
+                job.schedule,
+                job.next_run.map_or("never".to_string(), |t| t
+                    .format("%Y-%m-%d %H:%M UTC")
+                    .to_string())
+            );
+        }
+
+        let mut tasks = tokio::task::JoinSet::new();
+        loop {
+            let now = Utc::now();
+            for job in &self.jobs {
+                let due = {
+                    let mut next = job.next.lock().unwrap();
+                    let due = next.is_some_and(|t| t <= now);
+                    if due {
+                        *next = job.schedule.next_after(now);
+                    }
+                    due
+                };
+                if !due {
+                    continue;
+                }
+                if job.running.swap(true, Ordering::SeqCst) {
+                    tracing::warn!(
+                        "Skipping scheduled run of {}: the previous run is still in progress",
+                        job.name
+                    );
+                    // Returning here would abort the runs still going
+                    if let Err(e) = self
+                        .state_manager
+                        .record_scheduled_run(&job.name, None, RunStatus::Skipped)
+                        .await
+                    {
+                        tracing::error!("Failed to record skipped run of {}: {}", job.name, e);
+                    }
+                    continue;
+                }
+                tasks.spawn(launch(self.clone(), job.clone(), cancel.child_token()));
+            }
+
+            let wait = self
+                .jobs
+                .iter()
+                .filter_map(|j| *j.next.lock().unwrap())
+                .min()
+                .and_then(|t| (t - Utc::now()).to_std().ok())
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                Some(_) = tasks.join_next() => {}
+                _ = cancel.cancelled() => break,
+            }
+        }
+
+        while tasks.join_next().await.is_some() {}
+        Ok(())
+    }
+}
+
+/// Parses SQLite's `CURRENT_TIMESTAMP` format.
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+async fn launch(scheduler: Arc<Scheduler>, job: Arc<Job>, cancel: CancellationToken) {
+    if let Err(e) = run_job(&scheduler, &job.name, &job.path, cancel).await {
+        tracing::error!("Scheduled run of {} failed: {}", job.name, e);
+    }
+    job.running.store(false, Ordering::SeqCst);
+}
+
+/// One run of a scheduled config. The config is reloaded every time, so
+/// edits apply from the next run on.
+async fn run_job(
+    scheduler: &Scheduler,
+    name: &str,
+    path: &Path,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let state_manager = &scheduler.state_manager;
+    let config = match ConfigLoader::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            state_manager
+                .record_scheduled_run(name, None, RunStatus::Failed)
+                .await?;
+            return Err(e);
+        }
+    };
+    let url = config.start_urls[0].clone();
+    let crawl_id = state_manager
+        .create_crawl(&format!("Crawl: {}", url))
+        .await?;
+    let run_id = state_manager
+        .record_scheduled_run(name, Some(crawl_id), RunStatus::Running)
+        .await?;
+    tracing::info!("Scheduled run of {} started as crawl {}", name, crawl_id);
+
+    let result = crawl(scheduler, crawl_id, config, cancel.clone()).await;
+    let status = match &result {
+        Err(_) => RunStatus::Failed,
+        Ok(()) if cancel.is_cancelled() => RunStatus::Cancelled,
+        Ok(()) => RunStatus::Completed,
+    };
+    state_manager.finish_scheduled_run(run_id, status).await?;
+    tracing::info!("Scheduled run of {} {}", name, status.as_str());
+    result
+}
+
+async fn crawl(
+    scheduler: &Scheduler,
+    crawl_id: i64,
+    config: SpiderConfig,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let state_manager = scheduler.state_manager.clone();
+    let events = scheduler.events.as_ref().map(|hub| {
+        let (events, progress) = crate::crawler::channel();
+        tokio::spawn(relay(
+            hub.clone(),
+            state_manager.clone(),
+            crawl_id,
+            config.start_urls[0].clone(),
+            progress,
+        ));
+        events
+    });
+    let crawler = Crawler::from_config(state_manager, crawl_id, &config)?
+        .with_metrics(scheduler.metrics.clone())
+        .with_events(events);
+
+    crawler
+        .run(
+            &config.start_urls[0],
+            config.selector_queries(),
+            true,
+            config.respect_robots,
+            Some(config.delay_ms),
+            config.concurrency,
+            config.blacklist,
+            config.whitelist,
+            config.max_depth,
+            cancel,
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        parse_timestamp(s).unwrap()
+    }
+
+    #[test]
+    fn test_schedule_next_run() {
+        let nightly = Schedule::parse("0 3 * * *").unwrap();
+        assert_eq!(
+            nightly.next_after(at("2026-03-01 02:59:30")),
+            Some(at("2026-03-01 03:00:00"))
+        );
+        assert_eq!(
+            nightly.next_after(at("2026-03-01 03:00:00")),
+            Some(at("2026-03-02 03:00:00"))
+        );
+
+        // Weekdays every 15 minutes from 9 to 17, rolling over a year end
+        let office = Schedule::parse("*/15 9-17 * * mon-fri").unwrap();
+        assert_eq!(
+            office.next_after(at("2026-12-31 17:50:00")),
+            Some(at("2027-01-01 09:00:00"))
+        );
+        assert_eq!(
+            office.next_after(at("2027-01-01 17:45:00")),
+            Some(at("2027-01-04 09:00:00"))
+        );
+
+        // Day of month or day of week when both are given
+        let either = Schedule::parse("0 0 13 * 5").unwrap();
+        assert_eq!(
+            either.next_after(at("2026-03-01 00:00:00")),
+            Some(at("2026-03-06 00:00:00"))
+        );
+
+        assert_eq!(
+            Schedule::parse("@weekly")
+                .unwrap()
+                .next_after(at("2026-03-04 12:00:00")),
+            Some(at("2026-03-08 00:00:00"))
+        );
+        assert_eq!(
+            Schedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(at("2026-01-01 00:00:00")),
+            None
+        );
+        assert_eq!(
+            Schedule::parse("every 6h")
+                .unwrap()
+                .next_after(at("2026-03-01 22:00:00")),
+            Some(at("2026-03-02 04:00:00"))
+        );
+
+        for invalid in [
+            "0 3 * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "every 6",
+            "every 0h",
+            "every 9999999999999999d",
+        ] {
+            assert!(Schedule::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
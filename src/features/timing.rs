@@ -0,0 +1,109 @@
+use crate::features::metrics::Metrics;
+use crate::url_parser::normalize_url;
+use spider::reqwest::{Request, Response};
+use spider::reqwest_middleware::{Middleware, Next};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How a request went.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Response headers arrived after this long
+    Responded(Duration),
+    /// No response at all; spider reports these as status 599
+    Failed(String),
+}
+
+/// The outcome of each request by normalized URL, until the crawl takes it.
+#[derive(Default)]
+pub struct FetchTimes {
+    outcomes: Mutex<HashMap<String, Outcome>>,
+}
+
+impl FetchTimes {
+    /// The latest outcome for `url`, which is forgotten afterwards.
+    pub fn take(&self, url: &str) -> Option<Outcome> {
+        self.outcomes.lock().unwrap().remove(url)
+    }
+
+    /// Whether every outcome has been taken.
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.lock().unwrap().is_empty()
+    }
+}
+
+/// Client middleware timing every request into `FetchTimes`, since spider
+/// reports neither how long a fetch took nor why it failed. With `metrics`,
+/// requests are also counted in flight per host and their latency recorded.
+pub struct Timing {
+    times: Arc<FetchTimes>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Timing {
+    pub fn new(times: Arc<FetchTimes>, metrics: Option<Arc<Metrics>>) -> Self {
+        Self { times, metrics }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for Timing {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> spider::reqwest_middleware::Result<Response> {
+        let url = normalize_url(req.url().as_str());
+        let in_flight = self
+            .metrics
+            .as_ref()
+            .map(|m| m.start_request(req.url().host_str().unwrap_or_default()));
+        let started = Instant::now();
+        let res = next.run(req, extensions).await;
+        let outcome = match &res {
+            Ok(_) => {
+                if let Some(in_flight) = &in_flight {
+                    in_flight.responded();
+                }
+                Outcome::Responded(started.elapsed())
+            }
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        self.times.outcomes.lock().unwrap().insert(url, outcome);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::get;
+    use spider::reqwest_middleware::ClientBuilder;
+
+    #[tokio::test]
+    async fn test_timing_records_outcomes() {
+        let app = Router::new().route("/page", get(|| async { "ok" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let times = Arc::new(FetchTimes::default());
+        let client = ClientBuilder::new(spider::reqwest::Client::new())
+            .with(Timing::new(times.clone(), None))
+            .build();
+
+        let page = format!("http://{}/page", addr);
+        client.get(&page).send().await.unwrap();
+        assert!(matches!(times.take(&page), Some(Outcome::Responded(_))));
+        assert_eq!(times.take(&page), None);
+
+        // Nothing listens on port 9 of the loopback interface
+        let closed = "http://127.0.0.1:9/page".to_string();
+        assert!(client.get(&closed).send().await.is_err());
+        assert!(matches!(times.take(&closed), Some(Outcome::Failed(_))));
+    }
+}
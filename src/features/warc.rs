@@ -0,0 +1,438 @@
+use crate::config::{SpiderConfig, WarcConfig};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// One fetched page, written as a request/response/metadata record group.
+pub struct Capture<'a> {
+    pub url: &'a str,
+    pub status: u16,
+    pub reason: &'a str,
+    pub headers: &'a [(String, String)],
+    pub body: &'a [u8],
+    /// `application/warc-fields` entries for the metadata record
+    pub metadata: Vec<(String, String)>,
+}
+
+/// Writes WARC 1.1 files, rolling over to a new file once `max_file_size_mb`
+/// is reached. Every file starts with a `warcinfo` record describing the crawl.
+pub struct WarcWriter {
+    dir: PathBuf,
+    prefix: String,
+    max_size: u64,
+    gzip: bool,
+    crawl_id: i64,
+    info: Vec<(String, String)>,
+    serial: u32,
+    current: Option<WarcFile>,
+}
+
+struct WarcFile {
+    file: File,
+    written: u64,
+    warcinfo_id: String,
+}
+
+impl WarcWriter {
+    pub fn new(config: &WarcConfig, crawl_id: i64, info: Vec<(String, String)>) -> Result<Self> {
+        fs::create_dir_all(&config.path)
+            .with_context(|| format!("Failed to create WARC directory {}", config.path))?;
+        Ok(Self {
+            dir: PathBuf::from(&config.path),
+            prefix: config.prefix.clone(),
+            max_size: config.max_file_size_mb.max(1) * 1024 * 1024,
+            gzip: config.gzip,
+            crawl_id,
+            info,
+            serial: 0,
+            current: None,
+        })
+    }
+
+    /// Opens the writer described by `output.warc`, or `None` when disabled.
+    /// The crawl ID and full configuration go into every `warcinfo` record.
+    pub fn from_config(config: &SpiderConfig, crawl_id: i64) -> Result<Option<Self>> {
+        if !config.output.warc.enabled {
+            return Ok(None);
+        }
+        let info = vec![
+            ("crawl-id".to_string(), crawl_id.to_string()),
+            ("crawl-name".to_string(), config.name.clone()),
+            ("config".to_string(), serde_json::to_string(config)?),
+        ];
+        Self::new(&config.output.warc, crawl_id, info).map(Some)
+    }
+
+    pub fn write_capture(&mut self, capture: &Capture) -> Result<()> {
+        if self
+            .current
+            .as_ref()
+            .is_none_or(|f| f.written >= self.max_size)
+        {
+            self.roll()?;
+        }
+
+        let date = warc_date();
+        let response_id = record_id();
+
+        let mut response = format!("HTTP/1.1 {} {}\r\n", capture.status, capture.reason);
+        for (name, value) in capture.headers {
+            // The body we hold is already decoded, so the original framing
+            // headers would no longer describe it.
+            if matches!(
+                name.to_ascii_lowercase().as_str(),
+                "content-encoding" | "transfer-encoding" | "content-length"
+            ) {
+                continue;
+            }
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str(&format!("Content-Length: {}\r\n\r\n", capture.body.len()));
+        let mut response = response.into_bytes();
+        response.extend_from_slice(capture.body);
+
+        let payload_digest = digest(capture.body);
+        self.write_record(
+            "response",
+            &response_id,
+            &date,
+            &[
+                ("WARC-Target-URI", capture.url),
+                ("WARC-Payload-Digest", &payload_digest),
+                ("Content-Type", "application/http;msgtype=response"),
+            ],
+            &response,
+        )?;
+
+        // spider doesn't expose the request it sent, so record the request
+        // line and Host that produced this response.
+        let target = url::Url::parse(capture.url).ok();
+        let path = target
+            .as_ref()
+            .map(|u| match u.query() {
+                Some(q) => format!("{}?{}", u.path(), q),
+                None => u.path().to_string(),
+            })
+            .unwrap_or_else(|| "/".to_string());
+        let host = target
+            .as_ref()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host);
+        self.write_record(
+            "request",
+            &record_id(),
+            &date,
+            &[
+                ("WARC-Target-URI", capture.url),
+                ("WARC-Concurrent-To", &response_id),
+                ("Content-Type", "application/http;msgtype=request"),
+            ],
+            request.as_bytes(),
+        )?;
+
+        if !capture.metadata.is_empty() {
+            self.write_record(
+                "metadata",
+                &record_id(),
+                &date,
+                &[
+                    ("WARC-Target-URI", capture.url),
+                    ("WARC-Concurrent-To", &response_id),
+                    ("Content-Type", "application/warc-fields"),
+                ],
+                warc_fields(&capture.metadata).as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn roll(&mut self) -> Result<()> {
+        self.serial += 1;
+        let ext = if self.gzip { "warc.gz" } else { "warc" };
+        let name = format!(
+            "{}-{}-{}-{:05}.{}",
+            self.prefix,
+            self.crawl_id,
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            self.serial,
+            ext
+        );
+        let path = self.dir.join(&name);
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create WARC file {}", path.display()))?;
+        tracing::info!("Writing WARC file {}", path.display());
+
+        let warcinfo_id = record_id();
+        self.current = Some(WarcFile {
+            file,
+            written: 0,
+            warcinfo_id: warcinfo_id.clone(),
+        });
+
+        let mut fields = vec![
+            (
+                "software".to_string(),
+                format!("spider-cli/{}", env!("CARGO_PKG_VERSION")),
+            ),
+            ("format".to_string(), "WARC File Format 1.1".to_string()),
+        ];
+        fields.extend(self.info.iter().cloned());
+        self.write_record(
+            "warcinfo",
+            &warcinfo_id,
+            &warc_date(),
+            &[
+                ("WARC-Filename", &name),
+                ("Content-Type", "application/warc-fields"),
+            ],
+            warc_fields(&fields).as_bytes(),
+        )
+    }
+
+    fn write_record(
+        &mut self,
+        kind: &str,
+        id: &str,
+        date: &str,
+        extra: &[(&str, &str)],
+        block: &[u8],
+    ) -> Result<()> {
+        let current = self.current.as_mut().context("No open WARC file")?;
+
+        let mut record = format!(
+            "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n",
+            kind, id, date
+        );
+        if kind != "warcinfo" {
+            record.push_str(&format!("WARC-Warcinfo-ID: {}\r\n", current.warcinfo_id));
+        }
+        for (name, value) in extra {
+            record.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        record.push_str(&format!(
+            "WARC-Block-Digest: {}\r\nContent-Length: {}\r\n\r\n",
+            digest(block),
+            block.len()
+        ));
+        let mut record = record.into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+
+        let bytes = if self.gzip {
+            let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            enc.write_all(&record)?;
+            enc.finish()?
+        } else {
+            record
+        };
+        current.file.write_all(&bytes)?;
+        current.written += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// An HTTP response read back from a WARC file.
+pub struct WarcResponse {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Reads every `response` record of a WARC file, compressed per record or
+/// not. Chunked and gzip-encoded payloads written by other tools are decoded.
+pub fn read_responses(path: &Path) -> Result<Vec<WarcResponse>> {
+    let raw = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let data = if raw.starts_with(&[0x1f, 0x8b]) {
+        let mut out = Vec::new();
+        flate2::read::MultiGzDecoder::new(raw.as_slice()).read_to_end(&mut out)?;
+        out
+    } else {
+        raw
+    };
+
+    let mut responses = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        // Records are separated by CRLF CRLF; tolerate extra blank lines
+        if data[pos] == b'\r' || data[pos] == b'\n' {
+            pos += 1;
+            continue;
+        }
+        let (headers, header_len) = split_headers(&data[pos..])
+            .with_context(|| format!("Malformed WARC record in {}", path.display()))?;
+        let length: usize = header(&headers, "content-length")
+            .and_then(|v| v.parse().ok())
+            .context("WARC record without Content-Length")?;
+        let start = pos + header_len;
+        let block = data
+            .get(start..start + length)
+            .context("Truncated WARC record")?;
+        pos = start + length;
+
+        let is_response = header(&headers, "warc-type") == Some("response")
+            && header(&headers, "content-type").is_some_and(|t| t.starts_with("application/http"));
+        if !is_response {
+            continue;
+        }
+        let Some(url) = header(&headers, "warc-target-uri") else {
+            continue;
+        };
+        if let Some(response) = parse_http_response(url.trim_matches(['<', '>']), block) {
+            responses.push(response);
+        }
+    }
+    Ok(responses)
+}
+
+/// Splits a `Name: value` header block ending in a blank line, after the
+/// first (version or status) line. Returns the headers and bytes consumed.
+fn split_headers(data: &[u8]) -> Option<(Vec<(String, String)>, usize)> {
+    let end = data.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let text = String::from_utf8_lossy(&data[..end]);
+    let headers = text
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    Some((headers, end + 4))
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn parse_http_response(url: &str, block: &[u8]) -> Option<WarcResponse> {
+    let status_line_end = block.windows(2).position(|w| w == b"\r\n")?;
+    let status = String::from_utf8_lossy(&block[..status_line_end])
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    let (headers, header_len) = split_headers(block)?;
+    let mut body = block[header_len..].to_vec();
+
+    if header(&headers, "transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        body = dechunk(&body);
+    }
+    if header(&headers, "content-encoding").is_some_and(|v| v.eq_ignore_ascii_case("gzip")) {
+        let mut decoded = Vec::new();
+        if flate2::read::MultiGzDecoder::new(body.as_slice())
+            .read_to_end(&mut decoded)
+            .is_ok()
+        {
+            body = decoded;
+        }
+    }
+
+    Some(WarcResponse {
+        url: url.to_string(),
+        status,
+        headers,
+        body,
+    })
+}
+
+fn dechunk(mut data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(line_end) = data.windows(2).position(|w| w == b"\r\n") {
+        let size_str = String::from_utf8_lossy(&data[..line_end]);
+        let size_hex = size_str.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_hex, 16) else {
+            break;
+        };
+        let start = line_end + 2;
+        if size == 0 || start + size > data.len() {
+            break;
+        }
+        out.extend_from_slice(&data[start..start + size]);
+        data = data.get(start + size + 2..).unwrap_or_default();
+    }
+    out
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", uuid::Uuid::new_v4())
+}
+
+fn warc_date() -> String {
+    chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn digest(bytes: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(bytes)))
+}
+
+fn warc_fields(fields: &[(String, String)]) -> String {
+    fields
+        .iter()
+        .map(|(k, v)| format!("{}: {}\r\n", k, v.replace(['\r', '\n'], " ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warc_records_and_rolling() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WarcConfig {
+            enabled: true,
+            path: dir.path().to_string_lossy().into_owned(),
+            max_file_size_mb: 1,
+            ..WarcConfig::default()
+        };
+        let mut writer =
+            WarcWriter::new(&config, 7, vec![("crawl-id".into(), "7".into())]).unwrap();
+        // Force a roll after the first capture
+        writer.max_size = 1;
+
+        let headers = vec![
+            ("content-type".to_string(), "text/html".to_string()),
+            ("content-encoding".to_string(), "gzip".to_string()),
+        ];
+        for url in ["http://example.com/a?x=1", "http://example.com/b"] {
+            writer
+                .write_capture(&Capture {
+                    url,
+                    status: 200,
+                    reason: "OK",
+                    headers: &headers,
+                    body: b"<html>hi</html>",
+                    metadata: vec![("extracted".into(), "{\"title\":\"hi\"}".into())],
+                })
+                .unwrap();
+        }
+
+        let mut files: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].to_string_lossy().ends_with("-00001.warc.gz"));
+
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(&files[0]).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        let types: Vec<_> = text
+            .lines()
+            .filter_map(|l| l.strip_prefix("WARC-Type: "))
+            .collect();
+        assert_eq!(types, vec!["warcinfo", "response", "request", "metadata"]);
+        assert!(text.starts_with("WARC/1.1\r\n"));
+        assert!(text.contains("crawl-id: 7\r\n"));
+        assert!(text.contains("GET /a?x=1 HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(text.contains("HTTP/1.1 200 OK\r\ncontent-type: text/html\r\nContent-Length: 15\r\n\r\n<html>hi</html>"));
+        assert!(!text.contains("content-encoding"));
+    }
+}
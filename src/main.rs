@@ -10,7 +10,8 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use tracing_subscriber::{EnvFilter, fmt};
 
-use std::collections::HashMap;
+use state::StateStore;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -48,15 +49,288 @@ enum Commands {
         /// Run the web dashboard during the crawl
         #[arg(long)]
         dashboard: bool,
+
+        /// Keep crawl state in memory only (nothing is written to crawl_state.db)
+        #[arg(long)]
+        ephemeral: bool,
+
+        /// Index extracted values for `spider-cli search`
+        #[arg(long)]
+        search_index: bool,
+
+        /// Incrementally recrawl an existing crawl: re-fetch its known URLs and
+        /// record a new version only for pages that changed
+        #[arg(long, value_name = "CRAWL_ID")]
+        recrawl: Option<i64>,
+
+        /// Archive raw responses (body, headers, status) for `spider-cli reextract`
+        #[arg(long)]
+        store_raw: bool,
+
+        /// Also write the crawl to WARC files (see `output.warc` in the config)
+        #[arg(long)]
+        warc: bool,
+
+        /// Store pages declaring a different `<link rel="canonical">` under that URL, once
+        #[arg(long)]
+        dedupe_canonical: bool,
+
+        /// Skip extraction for pages whose content duplicates an earlier page
+        #[arg(long)]
+        skip_duplicates: bool,
+
+        /// Serve Prometheus metrics at /metrics on this port, without the dashboard
+        #[arg(long)]
+        metrics_port: Option<u16>,
+
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Re-run extraction over a crawl's archived responses without refetching
+    Reextract {
+        /// ID of the crawl whose responses were stored with `--store-raw`
+        crawl_id: i64,
+
+        /// Configuration providing the new selectors
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// Raw store directory (defaults to `store_raw.path` from the config)
+        #[arg(long)]
+        raw_dir: Option<PathBuf>,
+    },
+    /// Replay archived responses through extraction and persistence, offline
+    Replay {
+        /// A .warc/.warc.gz file, a directory of WARC files, or a raw response store
+        archive: PathBuf,
+
+        /// Configuration providing start URLs and selectors
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// Keep crawl state in memory only (nothing is written to crawl_state.db)
+        #[arg(long)]
+        ephemeral: bool,
+    },
+    /// Full-text search over the results of a crawl
+    Search {
+        /// ID of the crawl to search
+        crawl_id: i64,
+
+        /// Words to look for (all must match)
+        query: String,
+
+        /// Maximum number of pages to list
+        #[arg(short, long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Report pages added, removed and modified by recrawls
+    Changes {
+        /// ID of the crawl to report on
+        crawl_id: i64,
+
+        /// Only include changes recorded at or after this date (UTC)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Compare the results of two crawls of the same site
+    Diff {
+        /// Baseline crawl ID
+        a: i64,
+
+        /// Crawl ID to compare against the baseline
+        b: i64,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// List clusters of pages with identical or near-identical content
+    Duplicates {
+        /// ID of the crawl to report on
+        crawl_id: i64,
+
+        /// Maximum SimHash distance in bits for pages to count as similar;
+        /// 0 only groups pages with identical text
+        #[arg(long, default_value_t = 3)]
+        distance: u32,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Report redirect loops, long redirect chains and canonicalized pages
+    Redirects {
+        /// ID of the crawl to report on
+        crawl_id: i64,
+
+        /// Report chains with more hops than this
+        #[arg(long, default_value_t = 3)]
+        max_chain: usize,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Link-graph analysis of a crawl: PageRank, click depth, orphan and
+    /// dead-end pages
+    Analyze {
+        /// ID of the crawl to analyse
+        crawl_id: i64,
+
+        /// Pages click depth is measured from (defaults to the crawl's start URLs)
+        #[arg(long)]
+        seed: Vec<String>,
+
+        /// Sitemap URL or file whose pages are checked for inlinks
+        #[arg(long)]
+        sitemap: Vec<String>,
+
+        /// Also write per-page metrics to this CSV file
+        #[arg(long)]
+        csv: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Crawl a site and report broken internal and outbound links. Exits
+    /// non-zero when any are found
+    CheckLinks {
+        /// Site to check
+        url: String,
+
+        /// Respect robots.txt
+        #[arg(short, long)]
+        respect_robots: bool,
+
+        /// Delay between requests to the same host, in milliseconds
+        #[arg(short, long, default_value_t = 250)]
+        delay: u64,
+
+        /// Number of hosts checked in parallel
+        #[arg(short = 'j', long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Seconds to wait for each link before reporting a timeout
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+
+        /// Maximum crawl depth for internal pages
+        #[arg(long)]
+        max_depth: Option<usize>,
+
+        /// Only check links to the site's own host
+        #[arg(long)]
+        internal_only: bool,
+
+        /// Also write a JUnit XML report to this file
+        #[arg(long)]
+        junit: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Run crawls on the `schedule` of each config until stopped
+    Daemon {
+        /// Configuration files, each with a `schedule`
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
+
+        /// Also serve the dashboard, with upcoming and previous runs
+        #[arg(long)]
+        dashboard: bool,
+
+        /// Port to run the dashboard on
+        #[arg(short, long, default_value_t = 3030)]
+        port: u16,
+
+        /// Serve Prometheus metrics at /metrics on this port, without the dashboard
+        #[arg(long)]
+        metrics_port: Option<u16>,
+
+        #[command(flatten)]
+        server: ServerArgs,
     },
     /// Just launch the monitoring dashboard
     Serve {
         /// Port to run the dashboard on
         #[arg(short, long, default_value_t = 3030)]
         port: u16,
+
+        /// Crawls started from the dashboard that may run at once; the rest
+        /// wait in a queue that survives restarts
+        #[arg(long, default_value_t = ui::DEFAULT_MAX_CRAWLS)]
+        max_crawls: usize,
+
+        #[command(flatten)]
+        server: ServerArgs,
     },
 }
 
+/// Who the dashboard and metrics servers listen for and let in.
+#[derive(clap::Args)]
+struct ServerArgs {
+    /// Address to listen on; anything but loopback should come with auth
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: IpAddr,
+
+    /// Require `Authorization: Bearer <token>` on every request
+    #[arg(long, env = "SPIDER_DASHBOARD_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+
+    /// Require HTTP basic auth with these credentials on every request
+    #[arg(
+        long,
+        value_name = "USER:PASSWORD",
+        env = "SPIDER_DASHBOARD_BASIC_AUTH",
+        hide_env_values = true
+    )]
+    basic_auth: Option<String>,
+
+    /// Directory crawls started from the dashboard may load configs from;
+    /// repeat for more than one
+    #[arg(long = "config-dir", default_value = "configs")]
+    config_dirs: Vec<PathBuf>,
+}
+
+impl ServerArgs {
+    fn auth(&self) -> ui::Auth {
+        ui::Auth {
+            token: self.auth_token.clone(),
+            basic: self.basic_auth.clone(),
+        }
+    }
+
+    fn dashboard(&self, state_manager: Arc<dyn state::StateStore>) -> ui::DashboardServer {
+        ui::DashboardServer::new(state_manager)
+            .with_bind(self.bind)
+            .with_auth(self.auth())
+            .with_config_dirs(self.config_dirs.clone())
+    }
+
+    /// Where the dashboard can be opened, for logs.
+    fn url(&self, port: u16) -> String {
+        if self.bind.is_unspecified() {
+            format!("http://localhost:{}", port)
+        } else {
+            format!("http://{}", SocketAddr::new(self.bind, port))
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -66,9 +340,6 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    // 1. Initialize State Manager
-    let state_manager = Arc::new(state::StateManager::new("crawl_state.db").await?);
-
     match cli.command {
         Commands::Crawl {
             url,
@@ -77,21 +348,42 @@ async fn main() -> Result<()> {
             delay,
             concurrency,
             dashboard,
+            ephemeral,
+            search_index,
+            recrawl,
+            store_raw,
+            warc,
+            dedupe_canonical,
+            skip_duplicates,
+            metrics_port,
+            server,
         } => {
+            let metrics = Arc::new(features::metrics::Metrics::default());
+            if let Some(port) = metrics_port {
+                serve_metrics(metrics.clone(), &server, port);
+            }
+
+            // 1. Initialize State Store
+            let state_manager: Arc<dyn state::StateStore> = if ephemeral {
+                tracing::info!("Ephemeral crawl: state is kept in memory only");
+                Arc::new(state::MemoryStateStore::new())
+            } else {
+                Arc::new(
+                    state::StateManager::new("crawl_state.db")
+                        .await?
+                        .with_metrics(Some(metrics.clone())),
+                )
+            };
+
             let mut final_config = if let Some(config_path) = config {
                 config::ConfigLoader::load(config_path)?
             } else {
                 config::SpiderConfig {
                     name: "default".to_string(),
-                    start_urls: vec![],
-                    selectors: HashMap::new(),
                     concurrency,
                     delay_ms: delay.unwrap_or(0),
                     respect_robots,
-                    blacklist: vec![],
-                    whitelist: vec![],
-                    max_depth: None,
-                    extends: None,
+                    ..config::SpiderConfig::default()
                 }
             };
 
@@ -108,6 +400,33 @@ async fn main() -> Result<()> {
             if concurrency != 1 {
                 final_config.concurrency = concurrency;
             }
+            if search_index {
+                final_config.search.enabled = true;
+            }
+            if store_raw {
+                final_config.store_raw.enabled = true;
+            }
+            if warc {
+                final_config.output.warc.enabled = true;
+            }
+            if dedupe_canonical {
+                final_config.dedupe_by_canonical = true;
+            }
+            if skip_duplicates {
+                final_config.dedupe.skip_duplicates = true;
+            }
+
+            // A recrawl can start from the URL recorded in the crawl's name
+            if let Some(id) = recrawl
+                && final_config.start_urls.is_empty()
+                && let Some(c) = state_manager
+                    .get_all_crawls()
+                    .await?
+                    .into_iter()
+                    .find(|c| c.id == id)
+            {
+                final_config.start_urls = vec![ui::crawl_url(&c.name)];
+            }
 
             if final_config.start_urls.is_empty() {
                 anyhow::bail!(
@@ -118,7 +437,10 @@ async fn main() -> Result<()> {
             let first_url = final_config.start_urls[0].clone();
             tracing::info!("Starting spider-cli crawl for: {}", first_url);
 
-            let crawl_id = if let Some(id) = state_manager.get_active_crawl().await? {
+            let crawl_id = if let Some(id) = recrawl {
+                tracing::info!("Incremental recrawl of crawl ID: {}", id);
+                id
+            } else if let Some(id) = state_manager.get_active_crawl().await? {
                 tracing::info!("Found active crawl, resuming ID: {}", id);
                 id
             } else {
@@ -129,29 +451,64 @@ async fn main() -> Result<()> {
                 id
             };
 
+            // With the dashboard up, the crawl's events also go to its live
+            // feeds
+            let mut relayed = None;
             if dashboard {
-                let ds = ui::DashboardServer::new(state_manager.clone());
+                let hub = ui::EventHub::new();
+                let ds = server
+                    .dashboard(state_manager.clone())
+                    .with_metrics(metrics.clone())
+                    .with_events(hub.clone());
+                let (events, progress) = crawler::channel();
+                tokio::spawn(ui::relay(
+                    hub,
+                    state_manager.clone(),
+                    crawl_id,
+                    first_url.clone(),
+                    progress,
+                ));
+                relayed = Some(events);
                 tokio::spawn(async move {
                     if let Err(e) = ds.run(3030).await {
                         tracing::error!("Dashboard server error: {}", e);
                     }
                 });
-                tracing::info!("Dashboard active at http://localhost:3030");
+                tracing::info!("Dashboard active at {}", server.url(3030));
             }
 
-            let crawler = crawler::Crawler::new(state_manager.clone(), crawl_id, vec![]);
+            // Pages that went wrong are reported as they happen, the rest
+            // only in the final summary
+            let (events, mut progress) = crawler::channel();
+            let reporter = tokio::spawn(async move {
+                while let Some(event) = progress.recv().await {
+                    if let Some(relayed) = &relayed {
+                        let _ = relayed.send(event.clone()).await;
+                    }
+                    match event {
+                        crawler::CrawlEvent::Fetched { url, status, .. }
+                            if state::is_failure(status) =>
+                        {
+                            tracing::warn!("{} {}", status, url)
+                        }
+                        crawler::CrawlEvent::Failed {
+                            url: Some(url),
+                            error,
+                        } => tracing::warn!("Failed {}: {}", url, error),
+                        crawler::CrawlEvent::Finished { summary } => return Some(summary),
+                        _ => {}
+                    }
+                }
+                None
+            });
 
-            let selectors = if final_config.selectors.is_empty() {
-                let mut s = HashMap::new();
-                s.insert("title".to_string(), "title".to_string());
-                s
-            } else {
-                final_config
-                    .selectors
-                    .into_iter()
-                    .map(|(k, v)| (k, v.to_query_string()))
-                    .collect()
-            };
+            let crawler =
+                crawler::Crawler::from_config(state_manager.clone(), crawl_id, &final_config)?
+                    .with_events(Some(events))
+                    .with_metrics(Some(metrics))
+                    .with_recrawl(recrawl.is_some());
+
+            let selectors = final_config.selector_queries();
 
             tokio::select! {
                 res = crawler.run(
@@ -164,7 +521,6 @@ async fn main() -> Result<()> {
                     final_config.blacklist,
                     final_config.whitelist,
                     final_config.max_depth,
-                    None,
                     tokio_util::sync::CancellationToken::new()
                 ) => {
                     if let Err(e) = res {
@@ -181,10 +537,20 @@ async fn main() -> Result<()> {
                         println!("\n📊 Crawl Hierarchy Results:");
                         collection.display_trees();
                         println!("\n{}", collection.stats());
+                        if let Ok(Some(summary)) = reporter.await {
+                            println!("  Pages:                {}", summary);
+                        }
+                        println!("  Visited Set:          {}", crawler.visited_stats());
+                        if recrawl.is_some() {
+                            println!("  Not Modified:         {}", crawler.revalidation());
+                        }
                     }
 
                     if dashboard {
-                        tracing::info!("Crawl finished. Dashboard remains active at http://localhost:3030. Press Ctrl+C to stop.");
+                        tracing::info!(
+                            "Crawl finished. Dashboard remains active at {}. Press Ctrl+C to stop.",
+                            server.url(3030)
+                        );
                         tokio::signal::ctrl_c().await?;
                     }
                 }
@@ -193,12 +559,391 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Serve { port } => {
+        Commands::Reextract {
+            crawl_id,
+            config,
+            raw_dir,
+        } => {
+            let config = config::ConfigLoader::load(config)?;
+            let raw_dir = raw_dir.unwrap_or_else(|| PathBuf::from(&config.store_raw.path));
+            let raw_store =
+                features::raw_store::RawStore::open(&raw_dir, config.store_raw.compression)?;
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let selectors = config.selector_queries();
+
+            let (mut changed, mut unchanged) = (0, 0);
+            for response in raw_store.responses(crawl_id)? {
+                let body = raw_store.body(&response)?;
+                let html = String::from_utf8_lossy(&body).into_owned();
+                let extraction = crawler::extract(html, &selectors, &config.search);
+                let change = state_manager
+                    .save_result(
+                        crawl_id,
+                        &response.url,
+                        &serde_json::Value::Object(extraction.data.clone()),
+                        Some(&response.body_hash),
+                        extraction.search_index(&config.search),
+                    )
+                    .await?;
+                match change {
+                    Some(_) => changed += 1,
+                    None => unchanged += 1,
+                }
+            }
+            println!(
+                "Re-extracted crawl {} from {}: {} changed, {} unchanged",
+                crawl_id,
+                raw_dir.display(),
+                changed,
+                unchanged
+            );
+        }
+        Commands::Replay {
+            archive,
+            config,
+            ephemeral,
+        } => {
+            let config = config::ConfigLoader::load(config)?;
+            let state_manager: Arc<dyn state::StateStore> = if ephemeral {
+                Arc::new(state::MemoryStateStore::new())
+            } else {
+                Arc::new(state::StateManager::new("crawl_state.db").await?)
+            };
+
+            let pages = crawler::Archive::load(&archive)?;
+            tracing::info!(
+                "Loaded {} archived pages from {}",
+                pages.len(),
+                archive.display()
+            );
+
+            let crawl_id = state_manager
+                .create_crawl(&format!("Replay: {}", archive.display()))
+                .await?;
+            let crawler = crawler::Crawler::new(state_manager.clone(), crawl_id, vec![])
+                .with_search(config.search.clone())
+                .with_canonical_dedupe(config.dedupe_by_canonical)
+                .with_dedupe(config.dedupe.clone());
+            let processed = crawler
+                .replay(
+                    &pages,
+                    &config.start_urls,
+                    config.selector_queries(),
+                    config.max_depth,
+                )
+                .await?;
+            println!(
+                "Replayed {} of {} archived pages into crawl {}",
+                processed,
+                pages.len(),
+                crawl_id
+            );
+        }
+        Commands::Search {
+            crawl_id,
+            query,
+            limit,
+        } => {
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let hits = state_manager
+                .search_results(crawl_id, &query, limit)
+                .await?;
+            if hits.is_empty() {
+                println!(
+                    "No matches for \"{}\" in crawl {} (was it crawled with search enabled?)",
+                    query, crawl_id
+                );
+            }
+            for hit in hits {
+                println!("{}\n    {}\n", hit.url, hit.snippet);
+            }
+        }
+        Commands::Changes {
+            crawl_id,
+            since,
+            format,
+        } => {
+            let since = since
+                .as_deref()
+                .map(features::history::parse_since)
+                .transpose()?
+                .unwrap_or_default();
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let versions = state_manager.get_result_versions(crawl_id).await?;
+            let changes = features::history::changes_since(&versions, &since);
+
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&changes)?),
+                ReportFormat::Text => print!(
+                    "{}",
+                    features::history::changes_report(crawl_id, &since, &changes).render_text()
+                ),
+                ReportFormat::Html => println!(
+                    "{}",
+                    features::history::changes_report(crawl_id, &since, &changes).render_html()?
+                ),
+            }
+        }
+        Commands::Diff { a, b, format } => {
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let diff = features::diff::diff_crawls(
+                a,
+                state_manager.get_results(a).await?,
+                b,
+                state_manager.get_results(b).await?,
+            );
+
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+                ReportFormat::Text => print!("{}", diff.to_report().render_text()),
+                ReportFormat::Html => println!("{}", diff.to_report().render_html()?),
+            }
+        }
+        Commands::Duplicates {
+            crawl_id,
+            distance,
+            format,
+        } => {
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let report = features::dedupe::duplicate_clusters(
+                crawl_id,
+                &state_manager.get_page_info(crawl_id).await?,
+                distance,
+            );
+
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                ReportFormat::Text => print!("{}", report.to_report().render_text()),
+                ReportFormat::Html => println!("{}", report.to_report().render_html()?),
+            }
+        }
+        Commands::Redirects {
+            crawl_id,
+            max_chain,
+            format,
+        } => {
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let report = features::redirects::redirect_report(
+                crawl_id,
+                &state_manager.get_page_info(crawl_id).await?,
+                max_chain,
+            );
+
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                ReportFormat::Text => print!("{}", report.to_report().render_text()),
+                ReportFormat::Html => println!("{}", report.to_report().render_html()?),
+            }
+        }
+        Commands::Analyze {
+            crawl_id,
+            seed,
+            sitemap,
+            csv,
+            format,
+        } => {
+            let state_manager = state::StateManager::new("crawl_state.db").await?;
+            let seeds = if seed.is_empty() {
+                state_manager.get_seed_urls(crawl_id).await?
+            } else {
+                seed
+            };
+            let mut sitemap_urls = Vec::new();
+            for source in &sitemap {
+                sitemap_urls.extend(features::analysis::load_sitemap(source).await?);
+            }
+
+            let analysis = features::analysis::analyze(
+                crawl_id,
+                &state_manager.get_results_urls(crawl_id).await?,
+                &state_manager.get_links(crawl_id).await?,
+                &seeds,
+                &sitemap_urls,
+            );
+
+            if let Some(path) = csv {
+                analysis.write_csv(std::io::BufWriter::new(std::fs::File::create(&path)?))?;
+                tracing::info!(
+                    "Wrote metrics for {} pages to {}",
+                    analysis.pages.len(),
+                    path.display()
+                );
+            }
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&analysis)?),
+                ReportFormat::Text => print!("{}", analysis.to_report().render_text()),
+                ReportFormat::Html => println!("{}", analysis.to_report().render_html()?),
+            }
+        }
+        Commands::CheckLinks {
+            url,
+            respect_robots,
+            delay,
+            concurrency,
+            timeout,
+            max_depth,
+            internal_only,
+            junit,
+            format,
+        } => {
+            use features::link_check::{CheckOptions, LinkCheckReport, LinkStatus, check_links};
+            use std::collections::HashMap;
+
+            let state_manager: Arc<dyn state::StateStore> =
+                Arc::new(state::MemoryStateStore::new());
+            let crawl_id = state_manager
+                .create_crawl(&format!("Check: {}", url))
+                .await?;
+            crawler::Crawler::new(state_manager.clone(), crawl_id, vec![])
+                .run(
+                    &url,
+                    Default::default(),
+                    false,
+                    respect_robots,
+                    Some(delay),
+                    concurrency,
+                    vec![],
+                    vec![],
+                    max_depth,
+                    tokio_util::sync::CancellationToken::new(),
+                )
+                .await?;
+
+            let pages = state_manager.get_results_urls(crawl_id).await?;
+            let links = state_manager.get_links(crawl_id).await?;
+            // Pages the crawl fetched keep the status it got; spider reports
+            // requests that got no response as 599, and those are retried
+            let mut statuses: HashMap<String, LinkStatus> = state_manager
+                .get_page_info(crawl_id)
+                .await?
+                .into_iter()
+                .filter(|p| p.status != 599)
+                .map(|p| (p.url, LinkStatus::from_code(p.status)))
+                .collect();
+            let host = url::Url::parse(&url)?.host_str().map(str::to_string);
+            let mut targets: Vec<String> = links
+                .iter()
+                .map(|l| l.target.clone())
+                .filter(|t| {
+                    !internal_only
+                        || url::Url::parse(t)
+                            .ok()
+                            .map(|u| u.host_str().map(str::to_string))
+                            == Some(host.clone())
+                })
+                .collect();
+            targets.sort();
+            targets.dedup();
+            statuses.retain(|url, _| targets.binary_search(url).is_ok());
+            // External links and pages past max_depth weren't crawled
+            targets.retain(|t| !statuses.contains_key(t));
+            tracing::info!(
+                "Checking {} more links found on {} pages",
+                targets.len(),
+                pages.len()
+            );
+
+            statuses.extend(
+                check_links(
+                    targets,
+                    &CheckOptions {
+                        concurrency,
+                        delay: std::time::Duration::from_millis(delay),
+                        timeout: std::time::Duration::from_secs(timeout),
+                    },
+                )
+                .await?,
+            );
+            let report = LinkCheckReport::new(&url, pages.len(), &links, &statuses);
+
+            if let Some(path) = junit {
+                std::fs::write(&path, report.to_junit())?;
+            }
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                ReportFormat::Text => print!("{}", report.to_report().render_text()),
+                ReportFormat::Html => println!("{}", report.to_report().render_html()?),
+            }
+            if report.broken_count() > 0 {
+                anyhow::bail!("{} broken links found", report.broken_count());
+            }
+        }
+        Commands::Daemon {
+            configs,
+            dashboard,
+            port,
+            metrics_port,
+            server,
+        } => {
+            let metrics = Arc::new(features::metrics::Metrics::default());
+            if let Some(port) = metrics_port {
+                serve_metrics(metrics.clone(), &server, port);
+            }
+            let state_manager: Arc<dyn state::StateStore> = Arc::new(
+                state::StateManager::new("crawl_state.db")
+                    .await?
+                    .with_metrics(Some(metrics.clone())),
+            );
+            // Scheduled runs stream their events to the dashboard
+            let hub = dashboard.then(ui::EventHub::new);
+            let scheduler = Arc::new(
+                features::schedule::Scheduler::new(&configs, state_manager.clone())
+                    .await?
+                    .with_metrics(Some(metrics.clone()))
+                    .with_events(hub.clone()),
+            );
+
+            if let Some(hub) = hub {
+                let ds = server
+                    .dashboard(state_manager.clone())
+                    .with_scheduler(scheduler.clone())
+                    .with_metrics(metrics)
+                    .with_events(hub);
+                tokio::spawn(async move {
+                    if let Err(e) = ds.run(port).await {
+                        tracing::error!("Dashboard server error: {}", e);
+                    }
+                });
+                tracing::info!("Dashboard active at {}", server.url(port));
+            }
+
+            let cancel = tokio_util::sync::CancellationToken::new();
+            let daemon = tokio::spawn(scheduler.run(cancel.clone()));
+            tokio::signal::ctrl_c().await?;
+            tracing::info!("Shutting down, stopping running crawls...");
+            cancel.cancel();
+            daemon.await??;
+        }
+        Commands::Serve {
+            port,
+            max_crawls,
+            server,
+        } => {
             tracing::info!("Starting spider-cli Flawless Dashboard...");
-            let ds = ui::DashboardServer::new(state_manager.clone());
+            let metrics = Arc::new(features::metrics::Metrics::default());
+            let state_manager = Arc::new(
+                state::StateManager::new("crawl_state.db")
+                    .await?
+                    .with_metrics(Some(metrics.clone())),
+            );
+            let ds = server
+                .dashboard(state_manager.clone())
+                .with_max_crawls(max_crawls)
+                .with_metrics(metrics);
             ds.run(port).await?;
         }
     }
 
     Ok(())
 }
+
+/// Serves `/metrics` on its own port in the background, behind the same
+/// address and credentials as the dashboard.
+fn serve_metrics(metrics: Arc<features::metrics::Metrics>, server: &ServerArgs, port: u16) {
+    let (bind, auth) = (server.bind, server.auth());
+    tokio::spawn(async move {
+        if let Err(e) = ui::serve_metrics(metrics, bind, port, auth).await {
+            tracing::error!("Metrics server error: {}", e);
+        }
+    });
+}
@@ -0,0 +1,845 @@
+use super::{
+    ChangeKind, CrawlCounts, CrawlJob, CrawlStatus, CrawlSummary, FrontierEntry, JobStatus, Link,
+    LinkCounts, PageInfo, ResultQuery, ResultVersion, RunStatus, SNIPPET_CLOSE, SNIPPET_OPEN,
+    ScheduledRun, SearchHit, SearchIndex, StateStore, StoredResult, Validators, is_failure,
+    searchable_text,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// In-process `StateStore` used by tests and `--ephemeral` crawls.
+/// Nothing survives the process; semantics mirror the SQLite schema.
+#[derive(Default)]
+pub struct MemoryStateStore {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    crawls: Vec<CrawlRow>,
+    frontier: Vec<FrontierRow>,
+    results: Vec<ResultRow>,
+    /// (crawl_id, version) pairs in insertion order
+    versions: Vec<(i64, ResultVersion)>,
+    search_docs: Vec<SearchDoc>,
+    /// (crawl_id, link) pairs
+    links: Vec<(i64, Link)>,
+    /// (crawl_id, page) pairs
+    pages: Vec<(i64, PageInfo)>,
+    /// (crawl_id, url) → validators
+    validators: HashMap<(i64, String), Validators>,
+    scheduled_runs: Vec<ScheduledRun>,
+    jobs: Vec<CrawlJob>,
+}
+
+struct CrawlRow {
+    id: i64,
+    name: String,
+    status: String,
+    pass: i64,
+    updated_at: String,
+}
+
+struct FrontierRow {
+    id: i64,
+    crawl_id: i64,
+    url: String,
+    depth: usize,
+    status: String,
+    added_at: String,
+}
+
+struct ResultRow {
+    crawl_id: i64,
+    url: String,
+    data: serde_json::Value,
+    content_hash: Option<String>,
+    version: i64,
+    last_pass: i64,
+    found_at: String,
+}
+
+struct SearchDoc {
+    crawl_id: i64,
+    url: String,
+    text: String,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Same format as SQLite's `CURRENT_TIMESTAMP`.
+fn now() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Naive stand-in for FTS5's `snippet()`: a window of words around the first
+/// matching term, with every matching word wrapped in the snippet markers.
+fn snippet(text: &str, terms: &[String]) -> String {
+    const WINDOW: usize = 12;
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let is_match = |w: &str| {
+        let w = w.to_lowercase();
+        terms.iter().any(|t| w.contains(t.as_str()))
+    };
+    let first = words.iter().position(|w| is_match(w)).unwrap_or(0);
+    let start = first.saturating_sub(WINDOW / 2);
+    let end = (start + WINDOW).min(words.len());
+
+    let mut out: Vec<String> = words[start..end]
+        .iter()
+        .map(|w| {
+            if is_match(w) {
+                format!("{}{}{}", SNIPPET_OPEN, w, SNIPPET_CLOSE)
+            } else {
+                w.to_string()
+            }
+        })
+        .collect();
+    if start > 0 {
+        out.insert(0, "…".to_string());
+    }
+    if end < words.len() {
+        out.push("…".to_string());
+    }
+    out.join(" ")
+}
+
+impl Inner {
+    fn touch(&mut self, crawl_id: i64) {
+        if let Some(c) = self.crawls.iter_mut().find(|c| c.id == crawl_id) {
+            c.updated_at = now();
+        }
+    }
+
+    fn pass(&self, crawl_id: i64) -> i64 {
+        self.crawls
+            .iter()
+            .find(|c| c.id == crawl_id)
+            .map(|c| c.pass)
+            .unwrap_or(1)
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStateStore {
+    async fn create_crawl_with_status(&self, name: &str, status: CrawlStatus) -> Result<i64> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.crawls.len() as i64 + 1;
+        inner.crawls.push(CrawlRow {
+            id,
+            name: name.to_string(),
+            status: status.as_str().to_string(),
+            pass: 1,
+            updated_at: now(),
+        });
+        Ok(id)
+    }
+
+    async fn get_active_crawl(&self) -> Result<Option<i64>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .crawls
+            .iter()
+            .filter(|c| c.status == "active")
+            .max_by(|a, b| a.updated_at.cmp(&b.updated_at).then(a.id.cmp(&b.id)))
+            .map(|c| c.id))
+    }
+
+    async fn get_all_crawls(&self) -> Result<Vec<CrawlSummary>> {
+        let inner = self.inner.lock().unwrap();
+        let mut crawls: Vec<CrawlSummary> = inner
+            .crawls
+            .iter()
+            .map(|c| CrawlSummary {
+                id: c.id,
+                name: c.name.clone(),
+                status: c.status.clone(),
+                updated_at: c.updated_at.clone(),
+            })
+            .collect();
+        crawls.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+        Ok(crawls)
+    }
+
+    async fn set_crawl_status(&self, crawl_id: i64, status: CrawlStatus) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(c) = inner.crawls.iter_mut().find(|c| c.id == crawl_id) {
+            c.status = status.as_str().to_string();
+        }
+        inner.touch(crawl_id);
+        Ok(())
+    }
+
+    async fn get_crawl_counts(&self, crawl_id: i64) -> Result<CrawlCounts> {
+        let inner = self.inner.lock().unwrap();
+        let pages = inner.pages.iter().filter(|(c, _)| *c == crawl_id);
+        Ok(CrawlCounts {
+            results: inner
+                .results
+                .iter()
+                .filter(|r| r.crawl_id == crawl_id)
+                .count() as i64,
+            pages: pages.clone().count() as i64,
+            failed_pages: pages.filter(|(_, p)| is_failure(p.status)).count() as i64,
+            frontier_pending: inner
+                .frontier
+                .iter()
+                .filter(|f| f.crawl_id == crawl_id && f.status == "pending")
+                .count() as i64,
+            links: inner.links.iter().filter(|(c, _)| *c == crawl_id).count() as i64,
+        })
+    }
+
+    async fn add_to_frontier(&self, crawl_id: i64, urls: Vec<(String, usize)>) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        for (url, depth) in urls {
+            let exists = inner
+                .frontier
+                .iter()
+                .any(|f| f.crawl_id == crawl_id && f.url == url);
+            if !exists {
+                let id = inner.frontier.len() as i64 + 1;
+                inner.frontier.push(FrontierRow {
+                    id,
+                    crawl_id,
+                    url,
+                    depth,
+                    status: "pending".to_string(),
+                    added_at: now(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_pending_frontier(
+        &self,
+        crawl_id: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, String, usize)>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .frontier
+            .iter()
+            .filter(|f| f.crawl_id == crawl_id && f.status == "pending")
+            .take(limit.max(0) as usize)
+            .map(|f| (f.id, f.url.clone(), f.depth))
+            .collect())
+    }
+
+    async fn get_frontier(
+        &self,
+        crawl_id: i64,
+        status: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<FrontierEntry>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .frontier
+            .iter()
+            .filter(|f| f.crawl_id == crawl_id && status.is_none_or(|s| f.status == s))
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .map(|f| FrontierEntry {
+                url: f.url.clone(),
+                depth: f.depth,
+                status: f.status.clone(),
+                added_at: f.added_at.clone(),
+            })
+            .collect())
+    }
+
+    async fn get_seed_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .frontier
+            .iter()
+            .filter(|f| f.crawl_id == crawl_id && f.depth == 0)
+            .map(|f| f.url.clone())
+            .collect())
+    }
+
+    async fn save_result(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        data: &serde_json::Value,
+        content_hash: Option<&str>,
+        index: SearchIndex<'_>,
+    ) -> Result<Option<ChangeKind>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.touch(crawl_id);
+        let pass = inner.pass(crawl_id);
+
+        let (version, change) = match inner
+            .results
+            .iter_mut()
+            .find(|r| r.crawl_id == crawl_id && r.url == url)
+        {
+            None => {
+                inner.results.push(ResultRow {
+                    crawl_id,
+                    url: url.to_string(),
+                    data: data.clone(),
+                    content_hash: content_hash.map(str::to_string),
+                    version: 1,
+                    last_pass: pass,
+                    found_at: now(),
+                });
+                (1, ChangeKind::Added)
+            }
+            Some(row) => {
+                row.last_pass = pass;
+                let hash_changed =
+                    matches!((&row.content_hash, content_hash), (Some(a), Some(b)) if a != b);
+                if row.data == *data && !hash_changed {
+                    return Ok(None);
+                }
+                row.data = data.clone();
+                row.content_hash = content_hash.map(str::to_string);
+                row.version += 1;
+                row.found_at = now();
+                (row.version, ChangeKind::Modified)
+            }
+        };
+
+        inner.versions.push((
+            crawl_id,
+            ResultVersion {
+                url: url.to_string(),
+                version,
+                change,
+                data: Some(data.clone()),
+                content_hash: content_hash.map(str::to_string),
+                recorded_at: now(),
+            },
+        ));
+
+        inner
+            .search_docs
+            .retain(|d| !(d.crawl_id == crawl_id && d.url == url));
+        let text = match index {
+            SearchIndex::Off => return Ok(Some(change)),
+            SearchIndex::Values => searchable_text(data),
+            SearchIndex::ValuesAndText(page_text) => {
+                format!("{}\n{}", searchable_text(data), page_text)
+            }
+        };
+        inner.search_docs.push(SearchDoc {
+            crawl_id,
+            url: url.to_string(),
+            text,
+        });
+        Ok(Some(change))
+    }
+
+    async fn begin_recrawl(&self, crawl_id: i64) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(c) = inner.crawls.iter_mut().find(|c| c.id == crawl_id) {
+            c.status = "active".to_string();
+            c.pass += 1;
+        }
+        inner.touch(crawl_id);
+        Ok(())
+    }
+
+    async fn mark_unchanged(&self, crawl_id: i64, url: &str) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let pass = inner.pass(crawl_id);
+        if let Some(row) = inner
+            .results
+            .iter_mut()
+            .find(|r| r.crawl_id == crawl_id && r.url == url)
+        {
+            row.last_pass = pass;
+        }
+        Ok(())
+    }
+
+    async fn mark_unseen_removed(&self, crawl_id: i64) -> Result<Vec<String>> {
+        let mut inner = self.inner.lock().unwrap();
+        let pass = inner.pass(crawl_id);
+
+        let (unseen, kept): (Vec<ResultRow>, Vec<ResultRow>) = std::mem::take(&mut inner.results)
+            .into_iter()
+            .partition(|r| r.crawl_id == crawl_id && r.last_pass < pass);
+        inner.results = kept;
+
+        let mut removed = Vec::new();
+        for row in unseen {
+            inner
+                .search_docs
+                .retain(|d| !(d.crawl_id == crawl_id && d.url == row.url));
+            inner
+                .links
+                .retain(|(c, l)| !(*c == crawl_id && l.source == row.url));
+            inner
+                .pages
+                .retain(|(c, p)| !(*c == crawl_id && p.url == row.url));
+            inner.validators.remove(&(crawl_id, row.url.clone()));
+            inner.versions.push((
+                crawl_id,
+                ResultVersion {
+                    url: row.url.clone(),
+                    version: row.version + 1,
+                    change: ChangeKind::Removed,
+                    data: None,
+                    content_hash: None,
+                    recorded_at: now(),
+                },
+            ));
+            removed.push(row.url);
+        }
+        Ok(removed)
+    }
+
+    async fn get_result_versions(&self, crawl_id: i64) -> Result<Vec<ResultVersion>> {
+        let inner = self.inner.lock().unwrap();
+        let mut versions: Vec<ResultVersion> = inner
+            .versions
+            .iter()
+            .filter(|(c, _)| *c == crawl_id)
+            .map(|(_, v)| v.clone())
+            .collect();
+        versions.sort_by(|a, b| a.url.cmp(&b.url).then(a.version.cmp(&b.version)));
+        Ok(versions)
+    }
+
+    async fn search_results(
+        &self,
+        crawl_id: i64,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .search_docs
+            .iter()
+            .filter(|d| d.crawl_id == crawl_id)
+            .filter(|d| {
+                let text = d.text.to_lowercase();
+                terms.iter().all(|t| text.contains(t.as_str()))
+            })
+            .take(limit.max(0) as usize)
+            .map(|d| SearchHit {
+                url: d.url.clone(),
+                snippet: snippet(&d.text, &terms),
+            })
+            .collect())
+    }
+
+    async fn save_links(&self, crawl_id: i64, source: &str, links: &[Link]) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .links
+            .retain(|(c, l)| !(*c == crawl_id && l.source == source));
+        inner
+            .links
+            .extend(links.iter().map(|l| (crawl_id, l.clone())));
+        Ok(())
+    }
+
+    async fn get_links(&self, crawl_id: i64) -> Result<Vec<Link>> {
+        let inner = self.inner.lock().unwrap();
+        let mut links: Vec<Link> = inner
+            .links
+            .iter()
+            .filter(|(c, _)| *c == crawl_id)
+            .map(|(_, l)| l.clone())
+            .collect();
+        links.sort_by(|a, b| (&a.source, a.position).cmp(&(&b.source, b.position)));
+        Ok(links)
+    }
+
+    async fn get_link_counts(&self, crawl_id: i64) -> Result<HashMap<String, LinkCounts>> {
+        let inner = self.inner.lock().unwrap();
+        let edges: HashSet<(&str, &str)> = inner
+            .links
+            .iter()
+            .filter(|(c, l)| *c == crawl_id && l.source != l.target)
+            .map(|(_, l)| (l.source.as_str(), l.target.as_str()))
+            .collect();
+
+        let mut counts = HashMap::<String, LinkCounts>::new();
+        for (source, target) in edges {
+            counts.entry(source.to_string()).or_default().outlinks += 1;
+            counts.entry(target.to_string()).or_default().inlinks += 1;
+        }
+        Ok(counts)
+    }
+
+    async fn save_page_info(&self, crawl_id: i64, info: &PageInfo) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .pages
+            .retain(|(c, p)| !(*c == crawl_id && p.url == info.url));
+        inner.pages.push((crawl_id, info.clone()));
+        Ok(())
+    }
+
+    async fn count_unvisited_links(&self, crawl_id: i64, authority: &str) -> Result<i64> {
+        let inner = self.inner.lock().unwrap();
+        let prefixes = [
+            format!("http://{}/", authority),
+            format!("https://{}/", authority),
+        ];
+        let visited: HashSet<&str> = inner
+            .pages
+            .iter()
+            .filter(|(c, _)| *c == crawl_id)
+            .map(|(_, p)| p.url.as_str())
+            .chain(
+                inner
+                    .results
+                    .iter()
+                    .filter(|r| r.crawl_id == crawl_id)
+                    .map(|r| r.url.as_str()),
+            )
+            .collect();
+        let unvisited: HashSet<&str> = inner
+            .links
+            .iter()
+            .filter(|(c, l)| {
+                *c == crawl_id
+                    && prefixes.iter().any(|p| l.target.starts_with(p.as_str()))
+                    && !visited.contains(l.target.as_str())
+            })
+            .map(|(_, l)| l.target.as_str())
+            .collect();
+        Ok(unvisited.len() as i64)
+    }
+
+    async fn get_page_info(&self, crawl_id: i64) -> Result<Vec<PageInfo>> {
+        let inner = self.inner.lock().unwrap();
+        let mut pages: Vec<PageInfo> = inner
+            .pages
+            .iter()
+            .filter(|(c, _)| *c == crawl_id)
+            .map(|(_, p)| p.clone())
+            .collect();
+        pages.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(pages)
+    }
+
+    async fn get_failed_pages(
+        &self,
+        crawl_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<PageInfo>> {
+        Ok(self
+            .get_page_info(crawl_id)
+            .await?
+            .into_iter()
+            .filter(|p| is_failure(p.status))
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn get_recent_pages(&self, crawl_id: i64, limit: i64) -> Result<Vec<PageInfo>> {
+        let inner = self.inner.lock().unwrap();
+        let pages: Vec<PageInfo> = inner
+            .pages
+            .iter()
+            .filter(|(c, _)| *c == crawl_id)
+            .map(|(_, p)| p.clone())
+            .collect();
+        let skip = pages.len().saturating_sub(limit.max(0) as usize);
+        Ok(pages.into_iter().skip(skip).collect())
+    }
+
+    async fn record_scheduled_run(
+        &self,
+        config: &str,
+        crawl_id: Option<i64>,
+        status: RunStatus,
+    ) -> Result<i64> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.scheduled_runs.len() as i64 + 1;
+        let started_at = now();
+        inner.scheduled_runs.push(ScheduledRun {
+            id,
+            config: config.to_string(),
+            crawl_id,
+            status,
+            finished_at: (status != RunStatus::Running).then(|| started_at.clone()),
+            started_at,
+        });
+        Ok(id)
+    }
+
+    async fn finish_scheduled_run(&self, run_id: i64, status: RunStatus) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(run) = inner.scheduled_runs.iter_mut().find(|r| r.id == run_id) {
+            run.status = status;
+            run.finished_at = Some(now());
+        }
+        Ok(())
+    }
+
+    async fn get_scheduled_runs(
+        &self,
+        config: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledRun>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .scheduled_runs
+            .iter()
+            .rev()
+            .filter(|r| config.is_none_or(|c| r.config == c))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn enqueue_job(&self, crawl_id: i64, url: &str, config: Option<&str>) -> Result<i64> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.jobs.len() as i64 + 1;
+        inner.jobs.push(CrawlJob {
+            id,
+            crawl_id,
+            url: url.to_string(),
+            config: config.map(str::to_string),
+            status: JobStatus::Queued,
+            created_at: now(),
+        });
+        if let Some(c) = inner.crawls.iter_mut().find(|c| c.id == crawl_id) {
+            c.status = CrawlStatus::Queued.as_str().to_string();
+        }
+        inner.touch(crawl_id);
+        Ok(id)
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<CrawlJob>> {
+        let mut inner = self.inner.lock().unwrap();
+        Ok(inner
+            .jobs
+            .iter_mut()
+            .find(|j| j.status == JobStatus::Queued)
+            .map(|job| {
+                job.status = JobStatus::Running;
+                job.clone()
+            }))
+    }
+
+    async fn finish_job(&self, job_id: i64) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(job) = inner.jobs.iter_mut().find(|j| j.id == job_id) {
+            job.status = JobStatus::Done;
+        }
+        Ok(())
+    }
+
+    async fn cancel_queued_job(&self, job_id: i64) -> Result<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        Ok(inner
+            .jobs
+            .iter_mut()
+            .find(|j| j.id == job_id && j.status == JobStatus::Queued)
+            .map(|job| job.status = JobStatus::Done)
+            .is_some())
+    }
+
+    async fn get_crawl_job(&self, crawl_id: i64) -> Result<Option<CrawlJob>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .jobs
+            .iter()
+            .rev()
+            .find(|j| j.crawl_id == crawl_id)
+            .cloned())
+    }
+
+    async fn requeue_running_jobs(&self) -> Result<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut requeued = vec![];
+        for job in inner
+            .jobs
+            .iter_mut()
+            .filter(|j| j.status == JobStatus::Running)
+        {
+            job.status = JobStatus::Queued;
+            requeued.push(job.crawl_id);
+        }
+        for c in inner.crawls.iter_mut().filter(|c| requeued.contains(&c.id)) {
+            c.status = CrawlStatus::Queued.as_str().to_string();
+        }
+        Ok(requeued.len() as u64)
+    }
+
+    async fn get_queued_jobs(&self) -> Result<Vec<CrawlJob>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .jobs
+            .iter()
+            .filter(|j| j.status == JobStatus::Queued)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_visited_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .results
+            .iter()
+            .filter(|r| r.crawl_id == crawl_id)
+            .map(|r| r.url.clone())
+            .collect())
+    }
+
+    async fn save_validators(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .validators
+            .insert((crawl_id, url.to_string()), validators.clone());
+        Ok(())
+    }
+
+    async fn get_validators(&self, crawl_id: i64) -> Result<HashMap<String, Validators>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .validators
+            .iter()
+            .filter(|((c, _), _)| *c == crawl_id)
+            .map(|((_, url), v)| (url.clone(), v.clone()))
+            .collect())
+    }
+
+    async fn is_visited(&self, crawl_id: i64, url: &str) -> Result<bool> {
+        let inner = self.inner.lock().unwrap();
+        let pass = inner.pass(crawl_id);
+        Ok(inner
+            .results
+            .iter()
+            .any(|r| r.crawl_id == crawl_id && r.url == url && r.last_pass == pass))
+    }
+
+    async fn get_results(&self, crawl_id: i64) -> Result<Vec<(String, serde_json::Value)>> {
+        let inner = self.inner.lock().unwrap();
+        let mut results: Vec<(String, serde_json::Value)> = inner
+            .results
+            .iter()
+            .filter(|r| r.crawl_id == crawl_id)
+            .map(|r| (r.url.clone(), r.data.clone()))
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(results)
+    }
+
+    async fn query_results(
+        &self,
+        crawl_id: i64,
+        query: &ResultQuery,
+    ) -> Result<(Vec<StoredResult>, i64)> {
+        let inner = self.inner.lock().unwrap();
+        let mut matching: Vec<&ResultRow> = inner
+            .results
+            .iter()
+            .filter(|r| {
+                r.crawl_id == crawl_id
+                    && query
+                        .url_contains
+                        .as_ref()
+                        .is_none_or(|text| r.url.contains(text.as_str()))
+                    && query
+                        .since
+                        .as_ref()
+                        .is_none_or(|since| r.found_at >= *since)
+            })
+            .collect();
+        matching.sort_by(|a, b| a.url.cmp(&b.url));
+
+        let total = matching.len() as i64;
+        let results = matching
+            .into_iter()
+            .skip(query.offset.max(0) as usize)
+            .take(query.limit.max(0) as usize)
+            .map(|r| StoredResult {
+                url: r.url.clone(),
+                data: r.data.clone(),
+                version: r.version,
+                found_at: r.found_at.clone(),
+            })
+            .collect();
+        Ok((results, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store_crawl_lifecycle() -> Result<()> {
+        let store = MemoryStateStore::new();
+        let crawl_id = store.create_crawl("test").await?;
+        assert_eq!(crawl_id, 1);
+        assert_eq!(store.get_active_crawl().await?, Some(crawl_id));
+
+        store.complete_crawl(crawl_id).await?;
+        assert_eq!(store.get_active_crawl().await?, None);
+        assert_eq!(store.get_all_crawls().await?[0].status, "completed");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_dedupes_frontier_and_results() -> Result<()> {
+        let store = MemoryStateStore::new();
+        let crawl_id = store.create_crawl("test").await?;
+
+        let seed = vec![("http://example.com".to_string(), 0)];
+        store.add_to_frontier(crawl_id, seed.clone()).await?;
+        store.add_to_frontier(crawl_id, seed).await?;
+        assert_eq!(store.get_pending_frontier(crawl_id, 10).await?.len(), 1);
+
+        let data = serde_json::json!({"title": "Example"});
+        store
+            .save_result(
+                crawl_id,
+                "http://example.com",
+                &data,
+                None,
+                SearchIndex::Values,
+            )
+            .await?;
+        store
+            .save_result(
+                crawl_id,
+                "http://example.com",
+                &data,
+                None,
+                SearchIndex::Values,
+            )
+            .await?;
+        assert_eq!(
+            store.get_visited_urls(crawl_id).await?,
+            vec!["http://example.com".to_string()]
+        );
+
+        let hits = store.search_results(crawl_id, "EXAMPLE", 10).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "«Example»");
+
+        Ok(())
+    }
+}
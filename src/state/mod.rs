@@ -1,11 +1,12 @@
-use anyhow::{Context, Result};
-use sqlx::{ConnectOptions, Pool, Sqlite, sqlite::SqliteConnectOptions, sqlite::SqlitePoolOptions};
-use std::path::Path;
-use std::str::FromStr;
+mod memory;
+mod sqlite;
 
-pub struct StateManager {
-    pool: Pool<Sqlite>,
-}
+pub use memory::MemoryStateStore;
+pub use sqlite::StateManager;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct CrawlSummary {
@@ -15,228 +16,530 @@ pub struct CrawlSummary {
     pub updated_at: String,
 }
 
-impl StateManager {
-    pub async fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
-        let db_url = format!("sqlite:{}", db_path.as_ref().to_string_lossy());
+/// Lifecycle of a crawl, stored in `crawls.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrawlStatus {
+    /// Waiting in the dashboard's job queue to start
+    Queued,
+    /// Running, or interrupted without a chance to record why
+    Active,
+    /// Stopped on request with its frontier saved, to be resumed
+    Paused,
+    Cancelled,
+    Failed,
+    Completed,
+}
 
-        let connection_options = SqliteConnectOptions::from_str(&db_url)?
-            .create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-            .busy_timeout(std::time::Duration::from_millis(5000))
-            .disable_statement_logging();
+impl CrawlStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CrawlStatus::Queued => "queued",
+            CrawlStatus::Active => "active",
+            CrawlStatus::Paused => "paused",
+            CrawlStatus::Cancelled => "cancelled",
+            CrawlStatus::Failed => "failed",
+            CrawlStatus::Completed => "completed",
+        }
+    }
 
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect_with(connection_options)
-            .await
-            .context("Failed to connect to SQLite")?;
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(CrawlStatus::Queued),
+            "active" => Some(CrawlStatus::Active),
+            "paused" => Some(CrawlStatus::Paused),
+            "cancelled" => Some(CrawlStatus::Cancelled),
+            "failed" => Some(CrawlStatus::Failed),
+            "completed" => Some(CrawlStatus::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// Marks placed around matched terms in `SearchHit::snippet`.
+pub const SNIPPET_OPEN: &str = "«";
+pub const SNIPPET_CLOSE: &str = "»";
+
+/// What `save_result` adds to the full-text search index for a page.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SearchIndex<'a> {
+    #[default]
+    Off,
+    /// Index the extracted selector values only.
+    Values,
+    /// Index the extracted values and the visible page text.
+    ValuesAndText(&'a str),
+}
 
-        let manager = Self { pool };
-        manager.initialize_schema().await?;
+/// How a page's stored result changed between two visits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
 
-        Ok(manager)
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
     }
 
-    async fn initialize_schema(&self) -> Result<()> {
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS crawls (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                status TEXT NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            );",
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS frontier (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                crawl_id INTEGER NOT NULL,
-                url TEXT NOT NULL,
-                depth INTEGER DEFAULT 0,
-                status TEXT DEFAULT 'pending', -- pending, processing, completed, failed
-                added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
-                UNIQUE(crawl_id, url)
-            );",
-        )
-        .execute(&self.pool)
-        .await?;
-
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS results (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                crawl_id INTEGER NOT NULL,
-                url TEXT NOT NULL,
-                data TEXT NOT NULL, -- JSON
-                found_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
-                UNIQUE(crawl_id, url)
-            );",
-        )
-        .execute(&self.pool)
-        .await?;
-
-        Ok(())
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "added" => Some(ChangeKind::Added),
+            "modified" => Some(ChangeKind::Modified),
+            "removed" => Some(ChangeKind::Removed),
+            _ => None,
+        }
     }
+}
 
-    pub async fn get_visited_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
-        let urls = sqlx::query_scalar::<_, String>("SELECT url FROM results WHERE crawl_id = ?")
-            .bind(crawl_id)
-            .fetch_all(&self.pool)
-            .await?;
-        Ok(urls)
+/// Where a scheduled run stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Completed,
+    Failed,
+    /// Stopped by shutting down the daemon
+    Cancelled,
+    /// Not started because the previous run of the config was still going
+    Skipped,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Completed => "completed",
+            RunStatus::Failed => "failed",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::Skipped => "skipped",
+        }
     }
 
-    pub async fn get_all_crawls(&self) -> Result<Vec<CrawlSummary>> {
-        let rows = sqlx::query_as::<_, (i64, String, String, String)>(
-            "SELECT id, name, status, updated_at FROM crawls ORDER BY updated_at DESC",
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "running" => Some(RunStatus::Running),
+            "completed" => Some(RunStatus::Completed),
+            "failed" => Some(RunStatus::Failed),
+            "cancelled" => Some(RunStatus::Cancelled),
+            "skipped" => Some(RunStatus::Skipped),
+            _ => None,
+        }
+    }
+}
 
-        Ok(rows
-            .into_iter()
-            .map(|(id, name, status, updated_at)| CrawlSummary {
-                id,
-                name,
-                status,
-                updated_at,
-            })
-            .collect())
+/// A crawl launched by `spider-cli daemon` on a config's schedule.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScheduledRun {
+    pub id: i64,
+    /// `name` of the config that was run
+    pub config: String,
+    /// `None` when the run never got as far as creating a crawl
+    pub crawl_id: Option<i64>,
+    pub status: RunStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// Where a dashboard-started crawl is in the job queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+}
+
+impl JobStatus {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "done" => Some(JobStatus::Done),
+            _ => None,
+        }
     }
+}
 
-    pub async fn get_results_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
-        // Alias for get_visited_urls but specifically for results table discovery
-        self.get_visited_urls(crawl_id).await
+/// A crawl waiting for, or holding, one of the dashboard's crawl slots.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CrawlJob {
+    pub id: i64,
+    pub crawl_id: i64,
+    pub url: String,
+    /// Config file the crawl was started with, if any
+    pub config: Option<String>,
+    pub status: JobStatus,
+    pub created_at: String,
+}
+
+/// One stored version of a page's result. `data` is `None` for removals.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ResultVersion {
+    pub url: String,
+    pub version: i64,
+    pub change: ChangeKind,
+    pub data: Option<serde_json::Value>,
+    pub content_hash: Option<String>,
+    pub recorded_at: String,
+}
+
+/// A hyperlink found on a crawled page. Both ends are normalized URLs.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Link {
+    pub source: String,
+    pub target: String,
+    pub anchor_text: String,
+    /// Raw `rel` attribute, empty when absent
+    pub rel: String,
+    pub nofollow: bool,
+    /// Order of the anchor on the source page, starting at 0
+    pub position: i64,
+}
+
+/// Distinct pages linking to (`inlinks`) and linked from (`outlinks`) a
+/// URL, not counting links from a page to itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct LinkCounts {
+    pub inlinks: i64,
+    pub outlinks: i64,
+}
+
+/// Response headers that let a later fetch ask for a page only if it
+/// changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Body size in bytes when the validators were recorded
+    pub size: u64,
+}
+
+/// One response in a redirect chain.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status: u16,
+    /// Absolute URL from the `Location` header
+    pub location: String,
+}
+
+/// How a crawled page was reached: the redirects followed to get there,
+/// where it ended up, the canonical URL it declares and what its content
+/// looks like for duplicate detection.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PageInfo {
+    pub url: String,
+    pub final_url: String,
+    /// Status of the final response
+    pub status: u16,
+    pub canonical: Option<String>,
+    pub redirects: Vec<RedirectHop>,
+    /// SHA-256 of the response body
+    pub content_hash: Option<String>,
+    /// SimHash of the visible text, see `features::dedupe`
+    pub simhash: Option<u64>,
+    /// Earlier page with the same or nearly the same content, when
+    /// extraction was skipped because of it
+    pub duplicate_of: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchHit {
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Which of a crawl's results `query_results` returns.
+#[derive(Debug, Clone, Default)]
+pub struct ResultQuery {
+    /// Only URLs containing this text
+    pub url_contains: Option<String>,
+    /// Only results added or changed at or after this `CURRENT_TIMESTAMP`
+    /// style time; a bare date works too
+    pub since: Option<String>,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+/// A crawl's current result for one URL.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StoredResult {
+    pub url: String,
+    pub data: serde_json::Value,
+    pub version: i64,
+    /// When this version was stored
+    pub found_at: String,
+}
+
+/// A URL in a crawl's frontier.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FrontierEntry {
+    pub url: String,
+    pub depth: usize,
+    pub status: String,
+    pub added_at: String,
+}
+
+/// Row counts summarising a crawl's progress.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CrawlCounts {
+    pub results: i64,
+    /// Pages fetched, whatever their status
+    pub pages: i64,
+    /// Pages that came back with an error status, see `is_failure`
+    pub failed_pages: i64,
+    pub frontier_pending: i64,
+    pub links: i64,
+}
+
+/// Whether a fetched page's status means it failed: 4xx, 5xx, or 0 when no
+/// response arrived at all.
+pub fn is_failure(status: u16) -> bool {
+    status == 0 || status >= 400
+}
+
+/// Flattens every string leaf of an extracted `data` object into one
+/// newline-separated document for indexing.
+fn searchable_text(data: &serde_json::Value) -> String {
+    fn collect(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(s) => out.push(s.clone()),
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            serde_json::Value::Object(map) => map.values().for_each(|v| collect(v, out)),
+            serde_json::Value::Null => {}
+            other => out.push(other.to_string()),
+        }
+    }
+
+    let mut parts = Vec::new();
+    collect(data, &mut parts);
+    parts.join("\n")
+}
+
+/// Durable storage for crawls, their frontier and extracted results.
+///
+/// `StateManager` (SQLite) is the default backend; `MemoryStateStore` keeps
+/// everything in process memory for tests and `--ephemeral` crawls.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn create_crawl(&self, name: &str) -> Result<i64> {
+        self.create_crawl_with_status(name, CrawlStatus::Active)
+            .await
+    }
+
+    async fn create_crawl_with_status(&self, name: &str, status: CrawlStatus) -> Result<i64>;
+
+    /// The most recently updated running crawl, for the CLI to resume;
+    /// queued crawls belong to the job queue and are never returned.
+    async fn get_active_crawl(&self) -> Result<Option<i64>>;
+
+    async fn get_all_crawls(&self) -> Result<Vec<CrawlSummary>>;
+
+    async fn get_crawl(&self, crawl_id: i64) -> Result<Option<CrawlSummary>> {
+        Ok(self
+            .get_all_crawls()
+            .await?
+            .into_iter()
+            .find(|c| c.id == crawl_id))
     }
 
-    pub async fn add_to_frontier(&self, crawl_id: i64, urls: Vec<(String, usize)>) -> Result<()> {
-        for (url, depth) in urls {
-            sqlx::query(
-                "INSERT OR IGNORE INTO frontier (crawl_id, url, depth, status) 
-                 VALUES (?, ?, ?, 'pending')",
-            )
-            .bind(crawl_id)
-            .bind(url)
-            .bind(depth as i32)
-            .execute(&self.pool)
-            .await?;
+    async fn get_crawl_counts(&self, crawl_id: i64) -> Result<CrawlCounts>;
+
+    /// `get_crawl_counts` of several crawls at once, by crawl ID.
+    async fn get_crawls_counts(&self, crawl_ids: &[i64]) -> Result<HashMap<i64, CrawlCounts>> {
+        let mut counts = HashMap::new();
+        for &crawl_id in crawl_ids {
+            counts.insert(crawl_id, self.get_crawl_counts(crawl_id).await?);
         }
-        Ok(())
+        Ok(counts)
     }
 
-    pub async fn get_pending_frontier(
+    async fn set_crawl_status(&self, crawl_id: i64, status: CrawlStatus) -> Result<()>;
+
+    async fn complete_crawl(&self, crawl_id: i64) -> Result<()> {
+        self.set_crawl_status(crawl_id, CrawlStatus::Completed)
+            .await
+    }
+
+    async fn add_to_frontier(&self, crawl_id: i64, urls: Vec<(String, usize)>) -> Result<()>;
+
+    async fn get_pending_frontier(
         &self,
         crawl_id: i64,
         limit: i32,
-    ) -> Result<Vec<(i64, String, usize)>> {
-        let rows = sqlx::query_as::<_, (i64, String, i32)>(
-            "SELECT id, url, depth FROM frontier 
-             WHERE crawl_id = ? AND status = 'pending' 
-             LIMIT ?",
-        )
-        .bind(crawl_id)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
-
-        Ok(rows
-            .into_iter()
-            .map(|(id, url, depth)| (id, url, depth as usize))
-            .collect())
-    }
+    ) -> Result<Vec<(i64, String, usize)>>;
 
-    pub async fn save_result(
+    /// A page of the frontier in the order URLs were added, optionally only
+    /// those with `status`.
+    async fn get_frontier(
+        &self,
+        crawl_id: i64,
+        status: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<FrontierEntry>>;
+
+    /// URLs the crawl was seeded with (depth 0 in the frontier), in the
+    /// order they were added.
+    async fn get_seed_urls(&self, crawl_id: i64) -> Result<Vec<String>>;
+
+    /// Stores the result for `url`, recording a new version only when the
+    /// extracted data or content hash differs from the current one.
+    /// Returns `None` when nothing changed.
+    async fn save_result(
         &self,
         crawl_id: i64,
         url: &str,
         data: &serde_json::Value,
-    ) -> Result<()> {
-        let data_str = serde_json::to_string(data)?;
-
-        // Update updated_at for the crawl
-        sqlx::query("UPDATE crawls SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-            .bind(crawl_id)
-            .execute(&self.pool)
-            .await?;
-
-        sqlx::query("INSERT OR IGNORE INTO results (crawl_id, url, data) VALUES (?, ?, ?)")
-            .bind(crawl_id)
-            .bind(url)
-            .bind(data_str)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
-    }
+        content_hash: Option<&str>,
+        index: SearchIndex<'_>,
+    ) -> Result<Option<ChangeKind>>;
 
-    pub async fn complete_crawl(&self, crawl_id: i64) -> Result<()> {
-        sqlx::query(
-            "UPDATE crawls SET status = 'completed', updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-        )
-        .bind(crawl_id)
-        .execute(&self.pool)
-        .await?;
-        Ok(())
-    }
+    /// Reopens a crawl for an incremental pass over its known URLs.
+    async fn begin_recrawl(&self, crawl_id: i64) -> Result<()>;
 
-    pub async fn create_crawl(&self, name: &str) -> Result<i64> {
-        let row =
-            sqlx::query("INSERT INTO crawls (name, status) VALUES (?, 'active') RETURNING id")
-                .bind(name)
-                .fetch_one(&self.pool)
-                .await?;
+    /// Counts a result as seen in the current pass without changing it, for
+    /// pages the server reported as not modified.
+    async fn mark_unchanged(&self, crawl_id: i64, url: &str) -> Result<()>;
 
-        use sqlx::Row;
-        Ok(row.get(0))
-    }
+    /// Records every result not seen since `begin_recrawl` as removed and
+    /// drops it from the current results. Returns the removed URLs.
+    async fn mark_unseen_removed(&self, crawl_id: i64) -> Result<Vec<String>>;
 
-    pub async fn get_active_crawl(&self) -> Result<Option<i64>> {
-        let row = sqlx::query_scalar::<_, i64>(
-            "SELECT id FROM crawls WHERE status = 'active' ORDER BY updated_at DESC LIMIT 1",
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(row)
-    }
-}
+    /// Full version history of a crawl, ordered by URL then version.
+    async fn get_result_versions(&self, crawl_id: i64) -> Result<Vec<ResultVersion>>;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+    /// Full-text search over indexed results, best matches first.
+    async fn search_results(
+        &self,
+        crawl_id: i64,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>>;
 
-    #[tokio::test]
-    async fn test_state_manager_init() -> Result<()> {
-        let tmp_file = NamedTempFile::new()?;
-        let db_path = tmp_file.path();
+    /// Replaces the outgoing links recorded for `source`.
+    async fn save_links(&self, crawl_id: i64, source: &str, links: &[Link]) -> Result<()>;
 
-        let manager = StateManager::new(db_path).await?;
-        let crawl_id = manager.create_crawl("test").await?;
-        assert_eq!(crawl_id, 1);
+    /// Every link recorded for a crawl, ordered by source then position.
+    async fn get_links(&self, crawl_id: i64) -> Result<Vec<Link>>;
 
-        Ok(())
-    }
+    /// Inlink and outlink counts for every URL that has either.
+    async fn get_link_counts(&self, crawl_id: i64) -> Result<HashMap<String, LinkCounts>>;
 
-    #[tokio::test]
-    async fn test_frontier_management() -> Result<()> {
-        let tmp_file = NamedTempFile::new()?;
-        let manager = StateManager::new(tmp_file.path()).await?;
-        let crawl_id = manager.create_crawl("test").await?;
+    /// Records or replaces how `info.url` was fetched.
+    async fn save_page_info(&self, crawl_id: i64, info: &PageInfo) -> Result<()>;
 
-        manager
-            .add_to_frontier(crawl_id, vec![("http://example.com".to_string(), 0)])
-            .await?;
+    /// Distinct URLs under `authority` (host, and port if any) that the
+    /// crawl's pages link to but that have no page or result yet: roughly
+    /// what a live crawl has left to fetch.
+    async fn count_unvisited_links(&self, crawl_id: i64, authority: &str) -> Result<i64>;
 
-        let pending = manager.get_pending_frontier(crawl_id, 10).await?;
-        assert_eq!(pending.len(), 1);
-        assert_eq!(pending[0].1, "http://example.com");
+    /// Fetch details of every page in a crawl, ordered by URL.
+    async fn get_page_info(&self, crawl_id: i64) -> Result<Vec<PageInfo>>;
 
-        let pending_after = manager.get_pending_frontier(crawl_id, 10).await?;
-        assert_eq!(pending_after.len(), 1);
+    /// A page of the fetched pages that failed, ordered by URL.
+    async fn get_failed_pages(
+        &self,
+        crawl_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<PageInfo>>;
+
+    /// The `limit` pages of a crawl saved most recently, oldest first.
+    async fn get_recent_pages(&self, crawl_id: i64, limit: i64) -> Result<Vec<PageInfo>>;
+
+    /// Records or replaces the validators of a fetched page.
+    async fn save_validators(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<()>;
+
+    /// Validators of every page that had any, by URL.
+    async fn get_validators(&self, crawl_id: i64) -> Result<HashMap<String, Validators>>;
+
+    /// Records a scheduled run of `config`; runs not `Running` are recorded
+    /// as finished. Returns the run's ID.
+    async fn record_scheduled_run(
+        &self,
+        config: &str,
+        crawl_id: Option<i64>,
+        status: RunStatus,
+    ) -> Result<i64>;
+
+    async fn finish_scheduled_run(&self, run_id: i64, status: RunStatus) -> Result<()>;
+
+    /// Most recent scheduled runs, of one config or all, newest first.
+    async fn get_scheduled_runs(
+        &self,
+        config: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledRun>>;
+
+    /// Adds a crawl to the end of the job queue and marks it queued. Returns
+    /// the job's ID.
+    async fn enqueue_job(&self, crawl_id: i64, url: &str, config: Option<&str>) -> Result<i64>;
+
+    /// Marks the oldest queued job as running and returns it.
+    async fn claim_next_job(&self) -> Result<Option<CrawlJob>>;
+
+    async fn finish_job(&self, job_id: i64) -> Result<()>;
 
-        Ok(())
+    /// Takes a job out of the queue unless it has been claimed since.
+    /// Returns whether it was still queued.
+    async fn cancel_queued_job(&self, job_id: i64) -> Result<bool>;
+
+    /// The most recent job of a crawl, to start it again with the same URL
+    /// and config.
+    async fn get_crawl_job(&self, crawl_id: i64) -> Result<Option<CrawlJob>>;
+
+    /// Puts jobs left running by a previous process back in the queue, ahead
+    /// of the ones that were waiting, and marks their crawls queued. Returns
+    /// how many were requeued.
+    async fn requeue_running_jobs(&self) -> Result<u64>;
+
+    /// Queued jobs, next to run first.
+    async fn get_queued_jobs(&self) -> Result<Vec<CrawlJob>>;
+
+    async fn get_visited_urls(&self, crawl_id: i64) -> Result<Vec<String>>;
+
+    /// Whether `url` has a result saved in the crawl's current pass. A point
+    /// lookup, so callers need not load every visited URL.
+    async fn is_visited(&self, crawl_id: i64, url: &str) -> Result<bool>;
+
+    /// The URLs among `urls` that `is_visited`, looked up together.
+    async fn get_visited_among(&self, crawl_id: i64, urls: &[String]) -> Result<HashSet<String>> {
+        let mut visited = HashSet::new();
+        for url in urls {
+            if self.is_visited(crawl_id, url).await? {
+                visited.insert(url.clone());
+            }
+        }
+        Ok(visited)
+    }
+
+    /// Current (url, data) of every result in a crawl, ordered by URL.
+    async fn get_results(&self, crawl_id: i64) -> Result<Vec<(String, serde_json::Value)>>;
+
+    /// A page of the results matching `query`, ordered by URL, and how many
+    /// match in all.
+    async fn query_results(
+        &self,
+        crawl_id: i64,
+        query: &ResultQuery,
+    ) -> Result<(Vec<StoredResult>, i64)>;
+
+    async fn get_results_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
+        // Alias for get_visited_urls but specifically for results table discovery
+        self.get_visited_urls(crawl_id).await
     }
 }
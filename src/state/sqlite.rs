@@ -0,0 +1,1678 @@
+use super::{
+    ChangeKind, CrawlCounts, CrawlJob, CrawlStatus, CrawlSummary, FrontierEntry, JobStatus, Link,
+    LinkCounts, PageInfo, ResultQuery, ResultVersion, RunStatus, SNIPPET_CLOSE, SNIPPET_OPEN,
+    ScheduledRun, SearchHit, SearchIndex, StateStore, StoredResult, Validators, searchable_text,
+};
+use crate::features::metrics::{Metrics, WriteTimer};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::{ConnectOptions, Pool, Sqlite, sqlite::SqliteConnectOptions, sqlite::SqlitePoolOptions};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub struct StateManager {
+    pool: Pool<Sqlite>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl StateManager {
+    pub async fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let db_url = format!("sqlite:{}", db_path.as_ref().to_string_lossy());
+
+        let connection_options = SqliteConnectOptions::from_str(&db_url)?
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .busy_timeout(std::time::Duration::from_millis(5000))
+            .disable_statement_logging();
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(connection_options)
+            .await
+            .context("Failed to connect to SQLite")?;
+
+        let manager = Self {
+            pool,
+            metrics: None,
+        };
+        manager.initialize_schema().await?;
+
+        Ok(manager)
+    }
+
+    /// Record how long the writes made for every crawled page take.
+    pub fn with_metrics(mut self, metrics: Option<Arc<Metrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    fn time_write(&self, operation: &'static str) -> Option<WriteTimer> {
+        self.metrics.as_ref().map(|m| m.time_write(operation))
+    }
+
+    async fn initialize_schema(&self) -> Result<()> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS crawls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                status TEXT NOT NULL,
+                pass INTEGER NOT NULL DEFAULT 1, -- bumped by every incremental recrawl
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS frontier (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                depth INTEGER DEFAULT 0,
+                status TEXT DEFAULT 'pending', -- pending, processing, completed, failed
+                added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
+                UNIQUE(crawl_id, url)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS results (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                data TEXT NOT NULL, -- JSON
+                content_hash TEXT,
+                version INTEGER NOT NULL DEFAULT 1,
+                last_pass INTEGER NOT NULL DEFAULT 1, -- crawls.pass that last saw this URL
+                found_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
+                UNIQUE(crawl_id, url)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS result_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                change TEXT NOT NULL, -- added, modified, removed
+                data TEXT, -- JSON, NULL for removed
+                content_hash TEXT,
+                recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
+                UNIQUE(crawl_id, url, version)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS links (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                target TEXT NOT NULL,
+                anchor_text TEXT NOT NULL DEFAULT '',
+                rel TEXT NOT NULL DEFAULT '',
+                nofollow BOOLEAN NOT NULL DEFAULT 0,
+                position INTEGER NOT NULL, -- order of the anchor on the source page
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_source ON links (crawl_id, source)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_links_target ON links (crawl_id, target)")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS pages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                final_url TEXT NOT NULL,
+                status INTEGER NOT NULL,
+                canonical TEXT,
+                redirects TEXT NOT NULL DEFAULT '[]', -- JSON array of hops
+                content_hash TEXT,
+                simhash INTEGER, -- u64 stored bit for bit
+                duplicate_of TEXT,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
+                UNIQUE(crawl_id, url)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS validators (
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                etag TEXT,
+                last_modified TEXT,
+                size INTEGER NOT NULL DEFAULT 0, -- body bytes, what a 304 saves
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id),
+                UNIQUE(crawl_id, url)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS scheduled_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                config TEXT NOT NULL, -- name of the scheduled config
+                crawl_id INTEGER,
+                status TEXT NOT NULL, -- running, completed, failed, cancelled, skipped
+                started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                finished_at DATETIME,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                crawl_id INTEGER NOT NULL,
+                url TEXT NOT NULL,
+                config TEXT, -- config file path
+                status TEXT NOT NULL DEFAULT 'queued', -- queued, running, done
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                started_at DATETIME,
+                finished_at DATETIME,
+                FOREIGN KEY(crawl_id) REFERENCES crawls(id)
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Databases created before result history existed
+        self.add_column_if_missing("crawls", "pass", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+        self.add_column_if_missing("results", "content_hash", "TEXT")
+            .await?;
+        self.add_column_if_missing("results", "last_pass", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+        for (column, decl) in [
+            ("content_hash", "TEXT"),
+            ("simhash", "INTEGER"),
+            ("duplicate_of", "TEXT"),
+        ] {
+            self.add_column_if_missing("pages", column, decl).await?;
+        }
+        if self
+            .add_column_if_missing("results", "version", "INTEGER NOT NULL DEFAULT 1")
+            .await?
+        {
+            sqlx::query(
+                "INSERT OR IGNORE INTO result_versions (crawl_id, url, version, change, data, recorded_at)
+                 SELECT crawl_id, url, 1, 'added', data, found_at FROM results",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        // Full-text index over extracted values (and optionally page text),
+        // filled by save_result for crawls that enable `search`.
+        sqlx::query(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS results_fts USING fts5(
+                content,
+                page_text,
+                crawl_id UNINDEXED,
+                url UNINDEXED,
+                tokenize = 'porter unicode61'
+            );",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns true when the column had to be added.
+    async fn add_column_if_missing(&self, table: &str, column: &str, decl: &str) -> Result<bool> {
+        let columns = sqlx::query_scalar::<_, String>("SELECT name FROM pragma_table_info(?)")
+            .bind(table)
+            .fetch_all(&self.pool)
+            .await?;
+        if columns.iter().any(|c| c == column) {
+            return Ok(false);
+        }
+
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, decl
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(true)
+    }
+}
+
+/// Turns free text into an FTS5 query: every term is quoted so punctuation
+/// in user input can't be parsed as FTS syntax, and terms are implicitly ANDed.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `pages` columns in `PageInfo` field order.
+type PageRow = (
+    String,
+    String,
+    i64,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+);
+
+fn page_info(
+    (url, final_url, status, canonical, redirects, content_hash, simhash, duplicate_of): PageRow,
+) -> Result<PageInfo> {
+    Ok(PageInfo {
+        url,
+        final_url,
+        status: status as u16,
+        canonical,
+        redirects: serde_json::from_str(&redirects)?,
+        content_hash,
+        simhash: simhash.map(|h| h as u64),
+        duplicate_of,
+    })
+}
+
+#[async_trait]
+impl StateStore for StateManager {
+    async fn record_scheduled_run(
+        &self,
+        config: &str,
+        crawl_id: Option<i64>,
+        status: RunStatus,
+    ) -> Result<i64> {
+        let id = sqlx::query(
+            "INSERT INTO scheduled_runs (config, crawl_id, status, finished_at)
+             VALUES (?, ?, ?, CASE WHEN ? THEN NULL ELSE CURRENT_TIMESTAMP END)",
+        )
+        .bind(config)
+        .bind(crawl_id)
+        .bind(status.as_str())
+        .bind(status == RunStatus::Running)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    async fn finish_scheduled_run(&self, run_id: i64, status: RunStatus) -> Result<()> {
+        sqlx::query(
+            "UPDATE scheduled_runs SET status = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(status.as_str())
+        .bind(run_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_scheduled_runs(
+        &self,
+        config: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ScheduledRun>> {
+        let rows = sqlx::query_as::<_, (i64, String, Option<i64>, String, String, Option<String>)>(
+            "SELECT id, config, crawl_id, status, started_at, finished_at FROM scheduled_runs
+             WHERE ? IS NULL OR config = ?
+             ORDER BY id DESC LIMIT ?",
+        )
+        .bind(config)
+        .bind(config)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(id, config, crawl_id, status, started_at, finished_at)| {
+                Ok(ScheduledRun {
+                    id,
+                    config,
+                    crawl_id,
+                    status: RunStatus::parse(&status)
+                        .with_context(|| format!("Unknown run status: {}", status))?,
+                    started_at,
+                    finished_at,
+                })
+            })
+            .collect()
+    }
+
+    async fn enqueue_job(&self, crawl_id: i64, url: &str, config: Option<&str>) -> Result<i64> {
+        let id = sqlx::query("INSERT INTO jobs (crawl_id, url, config) VALUES (?, ?, ?)")
+            .bind(crawl_id)
+            .bind(url)
+            .bind(config)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+        self.set_crawl_status(crawl_id, CrawlStatus::Queued).await?;
+        Ok(id)
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<CrawlJob>> {
+        // A single statement, so two claims can't take the same job
+        let row = sqlx::query_as::<_, (i64, i64, String, Option<String>, String)>(
+            "UPDATE jobs SET status = 'running', started_at = CURRENT_TIMESTAMP
+             WHERE id = (SELECT id FROM jobs WHERE status = 'queued' ORDER BY id LIMIT 1)
+             RETURNING id, crawl_id, url, config, created_at",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(id, crawl_id, url, config, created_at)| CrawlJob {
+            id,
+            crawl_id,
+            url,
+            config,
+            status: JobStatus::Running,
+            created_at,
+        }))
+    }
+
+    async fn finish_job(&self, job_id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'done', finished_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn cancel_queued_job(&self, job_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE jobs SET status = 'done', finished_at = CURRENT_TIMESTAMP
+             WHERE id = ? AND status = 'queued'",
+        )
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_crawl_job(&self, crawl_id: i64) -> Result<Option<CrawlJob>> {
+        let row = sqlx::query_as::<_, (i64, i64, String, Option<String>, String, String)>(
+            "SELECT id, crawl_id, url, config, status, created_at FROM jobs
+             WHERE crawl_id = ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(crawl_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|(id, crawl_id, url, config, status, created_at)| {
+            Ok(CrawlJob {
+                id,
+                crawl_id,
+                url,
+                config,
+                status: JobStatus::parse(&status)
+                    .with_context(|| format!("Unknown job status: {}", status))?,
+                created_at,
+            })
+        })
+        .transpose()
+    }
+
+    async fn requeue_running_jobs(&self) -> Result<u64> {
+        sqlx::query(
+            "UPDATE crawls SET status = 'queued', updated_at = CURRENT_TIMESTAMP
+             WHERE id IN (SELECT crawl_id FROM jobs WHERE status = 'running')",
+        )
+        .execute(&self.pool)
+        .await?;
+        // Queue order is by ID, and these were claimed before any still queued
+        let result = sqlx::query(
+            "UPDATE jobs SET status = 'queued', started_at = NULL WHERE status = 'running'",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn get_queued_jobs(&self) -> Result<Vec<CrawlJob>> {
+        let rows = sqlx::query_as::<_, (i64, i64, String, Option<String>, String)>(
+            "SELECT id, crawl_id, url, config, created_at FROM jobs
+             WHERE status = 'queued' ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, crawl_id, url, config, created_at)| CrawlJob {
+                id,
+                crawl_id,
+                url,
+                config,
+                status: JobStatus::Queued,
+                created_at,
+            })
+            .collect())
+    }
+
+    async fn get_visited_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
+        let urls = sqlx::query_scalar::<_, String>("SELECT url FROM results WHERE crawl_id = ?")
+            .bind(crawl_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(urls)
+    }
+
+    async fn save_validators(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<()> {
+        let _timer = self.time_write("save_validators");
+        sqlx::query(
+            "INSERT INTO validators (crawl_id, url, etag, last_modified, size)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(crawl_id, url) DO UPDATE SET
+                etag = excluded.etag,
+                last_modified = excluded.last_modified,
+                size = excluded.size",
+        )
+        .bind(crawl_id)
+        .bind(url)
+        .bind(&validators.etag)
+        .bind(&validators.last_modified)
+        .bind(validators.size as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_validators(&self, crawl_id: i64) -> Result<HashMap<String, Validators>> {
+        let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>, i64)>(
+            "SELECT url, etag, last_modified, size FROM validators WHERE crawl_id = ?",
+        )
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(url, etag, last_modified, size)| {
+                (
+                    url,
+                    Validators {
+                        etag,
+                        last_modified,
+                        size: size as u64,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    async fn is_visited(&self, crawl_id: i64, url: &str) -> Result<bool> {
+        let visited = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM results
+             WHERE crawl_id = ? AND url = ? AND last_pass = (SELECT pass FROM crawls WHERE id = ?))",
+        )
+        .bind(crawl_id)
+        .bind(url)
+        .bind(crawl_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(visited)
+    }
+
+    async fn get_visited_among(&self, crawl_id: i64, urls: &[String]) -> Result<HashSet<String>> {
+        let visited = sqlx::query_scalar::<_, String>(
+            "SELECT url FROM results
+             WHERE crawl_id = ?1 AND url IN (SELECT value FROM json_each(?2))
+               AND last_pass = (SELECT pass FROM crawls WHERE id = ?1)",
+        )
+        .bind(crawl_id)
+        .bind(serde_json::to_string(urls)?)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(visited.into_iter().collect())
+    }
+
+    async fn get_results(&self, crawl_id: i64) -> Result<Vec<(String, serde_json::Value)>> {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT url, data FROM results WHERE crawl_id = ? ORDER BY url",
+        )
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(url, data)| Ok((url, serde_json::from_str(&data)?)))
+            .collect()
+    }
+
+    async fn query_results(
+        &self,
+        crawl_id: i64,
+        query: &ResultQuery,
+    ) -> Result<(Vec<StoredResult>, i64)> {
+        const FILTER: &str = "WHERE crawl_id = ?1
+             AND (?2 IS NULL OR instr(url, ?2) > 0)
+             AND (?3 IS NULL OR found_at >= ?3)";
+
+        let total =
+            sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM results {}", FILTER))
+                .bind(crawl_id)
+                .bind(&query.url_contains)
+                .bind(&query.since)
+                .fetch_one(&self.pool)
+                .await?;
+        let rows = sqlx::query_as::<_, (String, String, i64, String)>(&format!(
+            "SELECT url, data, version, found_at FROM results {}
+             ORDER BY url LIMIT ?4 OFFSET ?5",
+            FILTER
+        ))
+        .bind(crawl_id)
+        .bind(&query.url_contains)
+        .bind(&query.since)
+        .bind(query.limit)
+        .bind(query.offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let results = rows
+            .into_iter()
+            .map(|(url, data, version, found_at)| {
+                Ok(StoredResult {
+                    url,
+                    data: serde_json::from_str(&data)?,
+                    version,
+                    found_at,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok((results, total))
+    }
+
+    async fn get_all_crawls(&self) -> Result<Vec<CrawlSummary>> {
+        let rows = sqlx::query_as::<_, (i64, String, String, String)>(
+            "SELECT id, name, status, updated_at FROM crawls ORDER BY updated_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, status, updated_at)| CrawlSummary {
+                id,
+                name,
+                status,
+                updated_at,
+            })
+            .collect())
+    }
+
+    async fn get_crawl(&self, crawl_id: i64) -> Result<Option<CrawlSummary>> {
+        let row = sqlx::query_as::<_, (i64, String, String, String)>(
+            "SELECT id, name, status, updated_at FROM crawls WHERE id = ?",
+        )
+        .bind(crawl_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(id, name, status, updated_at)| CrawlSummary {
+            id,
+            name,
+            status,
+            updated_at,
+        }))
+    }
+
+    async fn get_crawl_counts(&self, crawl_id: i64) -> Result<CrawlCounts> {
+        let (results, pages, failed_pages, frontier_pending, links) =
+            sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
+                "SELECT
+                    (SELECT COUNT(*) FROM results WHERE crawl_id = ?1),
+                    (SELECT COUNT(*) FROM pages WHERE crawl_id = ?1),
+                    (SELECT COUNT(*) FROM pages WHERE crawl_id = ?1 AND (status = 0 OR status >= 400)),
+                    (SELECT COUNT(*) FROM frontier WHERE crawl_id = ?1 AND status = 'pending'),
+                    (SELECT COUNT(*) FROM links WHERE crawl_id = ?1)",
+            )
+            .bind(crawl_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(CrawlCounts {
+            results,
+            pages,
+            failed_pages,
+            frontier_pending,
+            links,
+        })
+    }
+
+    async fn get_crawls_counts(&self, crawl_ids: &[i64]) -> Result<HashMap<i64, CrawlCounts>> {
+        // One pass over each table, grouped, rather than a query per crawl
+        let rows = sqlx::query_as::<_, (i64, i64, i64, i64, i64, i64)>(
+            "WITH ids AS (SELECT value AS id FROM json_each(?1))
+             SELECT ids.id, COALESCE(r.n, 0), COALESCE(p.n, 0), COALESCE(p.failed, 0),
+                    COALESCE(f.n, 0), COALESCE(l.n, 0)
+             FROM ids
+             LEFT JOIN (SELECT crawl_id, COUNT(*) AS n FROM results
+                        WHERE crawl_id IN (SELECT id FROM ids) GROUP BY crawl_id) r
+                 ON r.crawl_id = ids.id
+             LEFT JOIN (SELECT crawl_id, COUNT(*) AS n,
+                               SUM(status = 0 OR status >= 400) AS failed
+                        FROM pages
+                        WHERE crawl_id IN (SELECT id FROM ids) GROUP BY crawl_id) p
+                 ON p.crawl_id = ids.id
+             LEFT JOIN (SELECT crawl_id, COUNT(*) AS n FROM frontier
+                        WHERE crawl_id IN (SELECT id FROM ids) AND status = 'pending'
+                        GROUP BY crawl_id) f
+                 ON f.crawl_id = ids.id
+             LEFT JOIN (SELECT crawl_id, COUNT(*) AS n FROM links
+                        WHERE crawl_id IN (SELECT id FROM ids) GROUP BY crawl_id) l
+                 ON l.crawl_id = ids.id",
+        )
+        .bind(serde_json::to_string(crawl_ids)?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(id, results, pages, failed_pages, frontier_pending, links)| {
+                    (
+                        id,
+                        CrawlCounts {
+                            results,
+                            pages,
+                            failed_pages,
+                            frontier_pending,
+                            links,
+                        },
+                    )
+                },
+            )
+            .collect())
+    }
+
+    async fn add_to_frontier(&self, crawl_id: i64, urls: Vec<(String, usize)>) -> Result<()> {
+        let _timer = self.time_write("add_to_frontier");
+        for (url, depth) in urls {
+            sqlx::query(
+                "INSERT OR IGNORE INTO frontier (crawl_id, url, depth, status) 
+                 VALUES (?, ?, ?, 'pending')",
+            )
+            .bind(crawl_id)
+            .bind(url)
+            .bind(depth as i32)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn get_pending_frontier(
+        &self,
+        crawl_id: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, String, usize)>> {
+        let rows = sqlx::query_as::<_, (i64, String, i32)>(
+            "SELECT id, url, depth FROM frontier 
+             WHERE crawl_id = ? AND status = 'pending' 
+             LIMIT ?",
+        )
+        .bind(crawl_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, url, depth)| (id, url, depth as usize))
+            .collect())
+    }
+
+    async fn get_frontier(
+        &self,
+        crawl_id: i64,
+        status: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<FrontierEntry>> {
+        let rows = sqlx::query_as::<_, (String, i64, String, String)>(
+            "SELECT url, depth, status, added_at FROM frontier
+             WHERE crawl_id = ?1 AND (?2 IS NULL OR status = ?2)
+             ORDER BY id LIMIT ?3 OFFSET ?4",
+        )
+        .bind(crawl_id)
+        .bind(status)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(url, depth, status, added_at)| FrontierEntry {
+                url,
+                depth: depth as usize,
+                status,
+                added_at,
+            })
+            .collect())
+    }
+
+    async fn get_seed_urls(&self, crawl_id: i64) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar::<_, String>(
+            "SELECT url FROM frontier WHERE crawl_id = ? AND depth = 0 ORDER BY id",
+        )
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn save_result(
+        &self,
+        crawl_id: i64,
+        url: &str,
+        data: &serde_json::Value,
+        content_hash: Option<&str>,
+        index: SearchIndex<'_>,
+    ) -> Result<Option<ChangeKind>> {
+        let _timer = self.time_write("save_result");
+        let data_str = serde_json::to_string(data)?;
+        let mut tx = self.pool.begin().await?;
+
+        // Update updated_at for the crawl
+        sqlx::query("UPDATE crawls SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(crawl_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let existing = sqlx::query_as::<_, (i64, String, Option<String>, i64)>(
+            "SELECT id, data, content_hash, version FROM results WHERE crawl_id = ? AND url = ?",
+        )
+        .bind(crawl_id)
+        .bind(url)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let (row_id, version, change) = match existing {
+            None => {
+                let row_id = sqlx::query_scalar::<_, i64>(
+                    "INSERT INTO results (crawl_id, url, data, content_hash, version, last_pass)
+                     VALUES (?, ?, ?, ?, 1, (SELECT pass FROM crawls WHERE id = ?))
+                     RETURNING id",
+                )
+                .bind(crawl_id)
+                .bind(url)
+                .bind(&data_str)
+                .bind(content_hash)
+                .bind(crawl_id)
+                .fetch_one(&mut *tx)
+                .await?;
+                (row_id, 1, ChangeKind::Added)
+            }
+            Some((row_id, old_data, old_hash, old_version)) => {
+                let old_data: serde_json::Value = serde_json::from_str(&old_data)?;
+                let hash_changed =
+                    matches!((&old_hash, content_hash), (Some(a), Some(b)) if a != b);
+
+                if old_data == *data && !hash_changed {
+                    sqlx::query(
+                        "UPDATE results SET last_pass = (SELECT pass FROM crawls WHERE id = ?)
+                         WHERE id = ?",
+                    )
+                    .bind(crawl_id)
+                    .bind(row_id)
+                    .execute(&mut *tx)
+                    .await?;
+                    tx.commit().await?;
+                    return Ok(None);
+                }
+
+                sqlx::query(
+                    "UPDATE results SET data = ?, content_hash = ?, version = ?,
+                        last_pass = (SELECT pass FROM crawls WHERE id = ?),
+                        found_at = CURRENT_TIMESTAMP
+                     WHERE id = ?",
+                )
+                .bind(&data_str)
+                .bind(content_hash)
+                .bind(old_version + 1)
+                .bind(crawl_id)
+                .bind(row_id)
+                .execute(&mut *tx)
+                .await?;
+
+                // The index row shares the result's rowid; drop the stale copy
+                sqlx::query("DELETE FROM results_fts WHERE rowid = ?")
+                    .bind(row_id)
+                    .execute(&mut *tx)
+                    .await?;
+                (row_id, old_version + 1, ChangeKind::Modified)
+            }
+        };
+
+        sqlx::query(
+            "INSERT INTO result_versions (crawl_id, url, version, change, data, content_hash)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(crawl_id)
+        .bind(url)
+        .bind(version)
+        .bind(change.as_str())
+        .bind(&data_str)
+        .bind(content_hash)
+        .execute(&mut *tx)
+        .await?;
+
+        let page_text = match index {
+            SearchIndex::Off => None,
+            SearchIndex::Values => Some(""),
+            SearchIndex::ValuesAndText(text) => Some(text),
+        };
+        if let Some(page_text) = page_text {
+            sqlx::query(
+                "INSERT INTO results_fts (rowid, content, page_text, crawl_id, url)
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(row_id)
+            .bind(searchable_text(data))
+            .bind(page_text)
+            .bind(crawl_id)
+            .bind(url)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(Some(change))
+    }
+
+    async fn begin_recrawl(&self, crawl_id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE crawls SET status = 'active', pass = pass + 1, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?",
+        )
+        .bind(crawl_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_unchanged(&self, crawl_id: i64, url: &str) -> Result<()> {
+        let _timer = self.time_write("mark_unchanged");
+        sqlx::query(
+            "UPDATE results SET last_pass = (SELECT pass FROM crawls WHERE id = ?)
+             WHERE crawl_id = ? AND url = ?",
+        )
+        .bind(crawl_id)
+        .bind(crawl_id)
+        .bind(url)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_unseen_removed(&self, crawl_id: i64) -> Result<Vec<String>> {
+        let mut tx = self.pool.begin().await?;
+
+        let unseen = sqlx::query_as::<_, (i64, String, i64)>(
+            "SELECT id, url, version FROM results
+             WHERE crawl_id = ? AND last_pass < (SELECT pass FROM crawls WHERE id = ?)",
+        )
+        .bind(crawl_id)
+        .bind(crawl_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for (row_id, url, version) in &unseen {
+            sqlx::query(
+                "INSERT INTO result_versions (crawl_id, url, version, change)
+                 VALUES (?, ?, ?, 'removed')",
+            )
+            .bind(crawl_id)
+            .bind(url)
+            .bind(version + 1)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM results_fts WHERE rowid = ?")
+                .bind(row_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM results WHERE id = ?")
+                .bind(row_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM links WHERE crawl_id = ? AND source = ?")
+                .bind(crawl_id)
+                .bind(url)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM pages WHERE crawl_id = ? AND url = ?")
+                .bind(crawl_id)
+                .bind(url)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM validators WHERE crawl_id = ? AND url = ?")
+                .bind(crawl_id)
+                .bind(url)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(unseen.into_iter().map(|(_, url, _)| url).collect())
+    }
+
+    async fn get_result_versions(&self, crawl_id: i64) -> Result<Vec<ResultVersion>> {
+        let rows =
+            sqlx::query_as::<_, (String, i64, String, Option<String>, Option<String>, String)>(
+                "SELECT url, version, change, data, content_hash, recorded_at FROM result_versions
+             WHERE crawl_id = ?
+             ORDER BY url, version",
+            )
+            .bind(crawl_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|(url, version, change, data, content_hash, recorded_at)| {
+                Ok(ResultVersion {
+                    url,
+                    version,
+                    change: ChangeKind::parse(&change)
+                        .with_context(|| format!("Unknown change kind: {}", change))?,
+                    data: data.map(|d| serde_json::from_str(&d)).transpose()?,
+                    content_hash,
+                    recorded_at,
+                })
+            })
+            .collect()
+    }
+
+    async fn search_results(
+        &self,
+        crawl_id: i64,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>> {
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT url, snippet(results_fts, -1, ?, ?, '…', 12) FROM results_fts
+             WHERE results_fts MATCH ? AND crawl_id = ?
+             ORDER BY rank
+             LIMIT ?",
+        )
+        .bind(SNIPPET_OPEN)
+        .bind(SNIPPET_CLOSE)
+        .bind(query)
+        .bind(crawl_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(url, snippet)| SearchHit { url, snippet })
+            .collect())
+    }
+
+    async fn save_links(&self, crawl_id: i64, source: &str, links: &[Link]) -> Result<()> {
+        let _timer = self.time_write("save_links");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM links WHERE crawl_id = ? AND source = ?")
+            .bind(crawl_id)
+            .bind(source)
+            .execute(&mut *tx)
+            .await?;
+        for link in links {
+            sqlx::query(
+                "INSERT INTO links (crawl_id, source, target, anchor_text, rel, nofollow, position)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(crawl_id)
+            .bind(&link.source)
+            .bind(&link.target)
+            .bind(&link.anchor_text)
+            .bind(&link.rel)
+            .bind(link.nofollow)
+            .bind(link.position)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_links(&self, crawl_id: i64) -> Result<Vec<Link>> {
+        let rows = sqlx::query_as::<_, (String, String, String, String, bool, i64)>(
+            "SELECT source, target, anchor_text, rel, nofollow, position FROM links
+             WHERE crawl_id = ? ORDER BY source, position, id",
+        )
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(source, target, anchor_text, rel, nofollow, position)| Link {
+                    source,
+                    target,
+                    anchor_text,
+                    rel,
+                    nofollow,
+                    position,
+                },
+            )
+            .collect())
+    }
+
+    async fn get_link_counts(&self, crawl_id: i64) -> Result<HashMap<String, LinkCounts>> {
+        let rows = sqlx::query_as::<_, (String, i64, i64)>(
+            "SELECT url, SUM(inlinks), SUM(outlinks) FROM (
+                SELECT target AS url, COUNT(DISTINCT source) AS inlinks, 0 AS outlinks
+                FROM links WHERE crawl_id = ? AND source != target GROUP BY target
+                UNION ALL
+                SELECT source, 0, COUNT(DISTINCT target)
+                FROM links WHERE crawl_id = ? AND source != target GROUP BY source
+             ) GROUP BY url",
+        )
+        .bind(crawl_id)
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(url, inlinks, outlinks)| (url, LinkCounts { inlinks, outlinks }))
+            .collect())
+    }
+
+    async fn save_page_info(&self, crawl_id: i64, info: &PageInfo) -> Result<()> {
+        let _timer = self.time_write("save_page_info");
+        sqlx::query(
+            "INSERT INTO pages (crawl_id, url, final_url, status, canonical, redirects,
+                                content_hash, simhash, duplicate_of)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(crawl_id, url) DO UPDATE SET
+                final_url = excluded.final_url,
+                status = excluded.status,
+                canonical = excluded.canonical,
+                redirects = excluded.redirects,
+                content_hash = excluded.content_hash,
+                simhash = excluded.simhash,
+                duplicate_of = excluded.duplicate_of",
+        )
+        .bind(crawl_id)
+        .bind(&info.url)
+        .bind(&info.final_url)
+        .bind(info.status as i64)
+        .bind(&info.canonical)
+        .bind(serde_json::to_string(&info.redirects)?)
+        .bind(&info.content_hash)
+        .bind(info.simhash.map(|h| h as i64))
+        .bind(&info.duplicate_of)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn count_unvisited_links(&self, crawl_id: i64, authority: &str) -> Result<i64> {
+        Ok(sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(DISTINCT target) FROM links
+             WHERE crawl_id = ?1
+               AND (target LIKE 'http://' || ?2 || '/%' OR target LIKE 'https://' || ?2 || '/%')
+               AND target NOT IN (SELECT url FROM pages WHERE crawl_id = ?1)
+               AND target NOT IN (SELECT url FROM results WHERE crawl_id = ?1)",
+        )
+        .bind(crawl_id)
+        .bind(authority)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    async fn get_page_info(&self, crawl_id: i64) -> Result<Vec<PageInfo>> {
+        let rows = sqlx::query_as::<_, PageRow>(
+            "SELECT url, final_url, status, canonical, redirects, content_hash, simhash, duplicate_of
+             FROM pages WHERE crawl_id = ? ORDER BY url",
+        )
+        .bind(crawl_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(page_info).collect()
+    }
+
+    async fn get_failed_pages(
+        &self,
+        crawl_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<PageInfo>> {
+        let rows = sqlx::query_as::<_, PageRow>(
+            "SELECT url, final_url, status, canonical, redirects, content_hash, simhash, duplicate_of
+             FROM pages WHERE crawl_id = ? AND (status = 0 OR status >= 400)
+             ORDER BY url LIMIT ? OFFSET ?",
+        )
+        .bind(crawl_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(page_info).collect()
+    }
+
+    async fn get_recent_pages(&self, crawl_id: i64, limit: i64) -> Result<Vec<PageInfo>> {
+        let rows = sqlx::query_as::<_, PageRow>(
+            "SELECT url, final_url, status, canonical, redirects, content_hash, simhash, duplicate_of
+             FROM (SELECT * FROM pages WHERE crawl_id = ? ORDER BY id DESC LIMIT ?)
+             ORDER BY id",
+        )
+        .bind(crawl_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(page_info).collect()
+    }
+
+    async fn set_crawl_status(&self, crawl_id: i64, status: CrawlStatus) -> Result<()> {
+        let _timer = self.time_write("set_crawl_status");
+        sqlx::query("UPDATE crawls SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(status.as_str())
+            .bind(crawl_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_crawl_with_status(&self, name: &str, status: CrawlStatus) -> Result<i64> {
+        let row = sqlx::query("INSERT INTO crawls (name, status) VALUES (?, ?) RETURNING id")
+            .bind(name)
+            .bind(status.as_str())
+            .fetch_one(&self.pool)
+            .await?;
+
+        use sqlx::Row;
+        Ok(row.get(0))
+    }
+
+    async fn get_active_crawl(&self) -> Result<Option<i64>> {
+        let row = sqlx::query_scalar::<_, i64>(
+            "SELECT id FROM crawls WHERE status = 'active' ORDER BY updated_at DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_state_manager_init() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let db_path = tmp_file.path();
+
+        let manager = StateManager::new(db_path).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+        assert_eq!(crawl_id, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frontier_management() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+
+        manager
+            .add_to_frontier(crawl_id, vec![("http://example.com".to_string(), 0)])
+            .await?;
+
+        let pending = manager.get_pending_frontier(crawl_id, 10).await?;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1, "http://example.com");
+
+        let pending_after = manager.get_pending_frontier(crawl_id, 10).await?;
+        assert_eq!(pending_after.len(), 1);
+
+        manager
+            .add_to_frontier(crawl_id, vec![("http://example.com/a".to_string(), 1)])
+            .await?;
+        assert_eq!(
+            manager.get_seed_urls(crawl_id).await?,
+            vec!["http://example.com"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_frontier_paged() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+        manager
+            .add_to_frontier(
+                crawl_id,
+                vec![
+                    ("http://example.com".to_string(), 0),
+                    ("http://example.com/a".to_string(), 1),
+                ],
+            )
+            .await?;
+
+        let page = manager
+            .get_frontier(crawl_id, Some("pending"), 1, 10)
+            .await?;
+        assert_eq!(page.len(), 1);
+        assert_eq!(
+            (page[0].url.as_str(), page[0].depth),
+            ("http://example.com/a", 1)
+        );
+        assert!(
+            manager
+                .get_frontier(crawl_id, Some("failed"), 0, 10)
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            manager.get_crawl_counts(crawl_id).await?.frontier_pending,
+            2
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_runs() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("Crawl: http://example.com").await?;
+
+        let run = manager
+            .record_scheduled_run("nightly", Some(crawl_id), RunStatus::Running)
+            .await?;
+        manager
+            .record_scheduled_run("nightly", None, RunStatus::Skipped)
+            .await?;
+        manager
+            .record_scheduled_run("hourly", None, RunStatus::Failed)
+            .await?;
+        manager
+            .finish_scheduled_run(run, RunStatus::Completed)
+            .await?;
+
+        let nightly = manager.get_scheduled_runs(Some("nightly"), 10).await?;
+        let summary: Vec<_> = nightly
+            .iter()
+            .map(|r| (r.crawl_id, r.status, r.finished_at.is_some()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, RunStatus::Skipped, true),
+                (Some(crawl_id), RunStatus::Completed, true),
+            ]
+        );
+        let latest = manager.get_scheduled_runs(None, 1).await?;
+        assert_eq!(latest[0].config, "hourly");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_queue() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        for url in ["http://a.test", "http://b.test", "http://c.test"] {
+            let crawl_id = manager.create_crawl(&format!("UI Crawl: {}", url)).await?;
+            manager.enqueue_job(crawl_id, url, None).await?;
+        }
+        // Queued crawls are the job queue's, not the CLI's to resume
+        assert_eq!(manager.get_active_crawl().await?, None);
+
+        let first = manager.claim_next_job().await?.unwrap();
+        assert_eq!(first.url, "http://a.test");
+        assert_eq!(first.status, JobStatus::Running);
+        let second = manager.claim_next_job().await?.unwrap();
+        manager.finish_job(second.id).await?;
+        let queued = manager.get_queued_jobs().await?;
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].url, "http://c.test");
+        // Only a job nobody has claimed yet can be taken out of the queue
+        assert!(!manager.cancel_queued_job(first.id).await?);
+
+        // A restart puts the interrupted job back at the head of the queue
+        assert_eq!(manager.requeue_running_jobs().await?, 1);
+        let urls: Vec<_> = manager
+            .get_queued_jobs()
+            .await?
+            .into_iter()
+            .map(|j| j.url)
+            .collect();
+        assert_eq!(urls, vec!["http://a.test", "http://c.test"]);
+        assert_eq!(manager.claim_next_job().await?.unwrap().id, first.id);
+
+        // Resuming a paused crawl queues a new job for the same crawl ID
+        manager
+            .set_crawl_status(first.crawl_id, CrawlStatus::Paused)
+            .await?;
+        manager.finish_job(first.id).await?;
+        manager
+            .enqueue_job(first.crawl_id, &first.url, None)
+            .await?;
+        let latest = manager.get_crawl_job(first.crawl_id).await?.unwrap();
+        assert_ne!(latest.id, first.id);
+        assert_eq!(latest.status, JobStatus::Queued);
+        let crawls = manager.get_all_crawls().await?;
+        let crawl = crawls.iter().find(|c| c.id == first.crawl_id).unwrap();
+        assert_eq!(crawl.status, "queued");
+        assert!(manager.cancel_queued_job(latest.id).await?);
+        assert_eq!(manager.get_queued_jobs().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_full_text_search() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+
+        manager
+            .save_result(
+                crawl_id,
+                "http://example.com/a",
+                &serde_json::json!({"title": "Rust crawlers"}),
+                None,
+                SearchIndex::ValuesAndText("Fearless concurrency for spiders"),
+            )
+            .await?;
+        manager
+            .save_result(
+                crawl_id,
+                "http://example.com/b",
+                &serde_json::json!({"title": "Python scrapers"}),
+                None,
+                SearchIndex::Off,
+            )
+            .await?;
+
+        let hits = manager.search_results(crawl_id, "concurrency", 10).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].url, "http://example.com/a");
+        assert!(hits[0].snippet.contains("«concurrency»"));
+
+        // Unindexed results and FTS syntax in the query are both harmless
+        assert!(
+            manager
+                .search_results(crawl_id, "python", 10)
+                .await?
+                .is_empty()
+        );
+        assert!(
+            manager
+                .search_results(crawl_id, "rust\" OR", 10)
+                .await?
+                .is_empty()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_results() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+        for (url, title) in [
+            ("http://example.com/a", "Rust crawlers"),
+            ("http://example.com/b", "Python scrapers"),
+        ] {
+            manager
+                .save_result(
+                    crawl_id,
+                    url,
+                    &serde_json::json!({ "title": title }),
+                    None,
+                    SearchIndex::Off,
+                )
+                .await?;
+        }
+
+        // Paged listing filters by URL text and time stored
+        let query = ResultQuery {
+            url_contains: Some("/b".to_string()),
+            limit: 10,
+            ..ResultQuery::default()
+        };
+        let (results, total) = manager.query_results(crawl_id, &query).await?;
+        assert_eq!(total, 1);
+        assert_eq!(results[0].data["title"], "Python scrapers");
+        let query = ResultQuery {
+            since: Some("2999-01-01".to_string()),
+            limit: 10,
+            ..ResultQuery::default()
+        };
+        assert_eq!(manager.query_results(crawl_id, &query).await?.1, 0);
+        let query = ResultQuery {
+            offset: 1,
+            limit: 10,
+            ..ResultQuery::default()
+        };
+        let (results, total) = manager.query_results(crawl_id, &query).await?;
+        assert_eq!((results.len(), total), (1, 2));
+        assert_eq!(results[0].url, "http://example.com/b");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recrawl_versions_changes() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+        let a = "http://example.com/a";
+        let b = "http://example.com/b";
+
+        let first = manager
+            .save_result(
+                crawl_id,
+                a,
+                &serde_json::json!({"price": "10"}),
+                Some("h1"),
+                SearchIndex::Values,
+            )
+            .await?;
+        assert_eq!(first, Some(ChangeKind::Added));
+        manager
+            .save_result(
+                crawl_id,
+                b,
+                &serde_json::json!({}),
+                Some("h2"),
+                SearchIndex::Off,
+            )
+            .await?;
+
+        assert!(manager.is_visited(crawl_id, b).await?);
+        for url in [a, b] {
+            let validators = Validators {
+                etag: Some(format!("\"{}\"", url.len())),
+                last_modified: None,
+                size: 100,
+            };
+            manager.save_validators(crawl_id, url, &validators).await?;
+        }
+        assert_eq!(manager.get_validators(crawl_id).await?.len(), 2);
+
+        // Second pass: `a` is unchanged then changes price, `b` disappears
+        manager.begin_recrawl(crawl_id).await?;
+        assert!(!manager.is_visited(crawl_id, a).await?);
+        // A 304 keeps the page without saving a result
+        manager.mark_unchanged(crawl_id, a).await?;
+        assert!(manager.is_visited(crawl_id, a).await?);
+        let among = manager
+            .get_visited_among(crawl_id, &[a.to_string(), b.to_string()])
+            .await?;
+        assert_eq!(among, HashSet::from([a.to_string()]));
+        let same = manager
+            .save_result(
+                crawl_id,
+                a,
+                &serde_json::json!({"price": "10"}),
+                Some("h1"),
+                SearchIndex::Values,
+            )
+            .await?;
+        assert_eq!(same, None);
+        assert!(manager.is_visited(crawl_id, a).await?);
+        let changed = manager
+            .save_result(
+                crawl_id,
+                a,
+                &serde_json::json!({"price": "12"}),
+                Some("h3"),
+                SearchIndex::Values,
+            )
+            .await?;
+        assert_eq!(changed, Some(ChangeKind::Modified));
+
+        let removed = manager.mark_unseen_removed(crawl_id).await?;
+        assert_eq!(removed, vec![b.to_string()]);
+        assert_eq!(
+            manager.get_visited_urls(crawl_id).await?,
+            vec![a.to_string()]
+        );
+        let validators = manager.get_validators(crawl_id).await?;
+        assert_eq!(validators.len(), 1);
+        assert_eq!(validators[a].size, 100);
+
+        let versions = manager.get_result_versions(crawl_id).await?;
+        let kinds: Vec<_> = versions
+            .iter()
+            .map(|v| (v.url.as_str(), v.version, v.change))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (a, 1, ChangeKind::Added),
+                (a, 2, ChangeKind::Modified),
+                (b, 1, ChangeKind::Added),
+                (b, 2, ChangeKind::Removed),
+            ]
+        );
+
+        // The search index follows the latest version
+        assert!(manager.search_results(crawl_id, "10", 10).await?.is_empty());
+        assert_eq!(manager.search_results(crawl_id, "12", 10).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_links_and_counts() -> Result<()> {
+        let tmp_file = NamedTempFile::new()?;
+        let manager = StateManager::new(tmp_file.path()).await?;
+        let crawl_id = manager.create_crawl("test").await?;
+
+        let link = |source: &str, target: &str, position: i64| Link {
+            source: source.to_string(),
+            target: target.to_string(),
+            anchor_text: format!("to {}", target),
+            rel: String::new(),
+            nofollow: false,
+            position,
+        };
+        let home = "http://example.com";
+        let about = "http://example.com/about";
+        manager
+            .save_links(
+                crawl_id,
+                home,
+                &[
+                    link(home, about, 0),
+                    link(home, about, 1),
+                    link(home, home, 2),
+                ],
+            )
+            .await?;
+        manager
+            .save_links(crawl_id, about, &[link(about, home, 0)])
+            .await?;
+
+        // Saving again replaces the page's previous links
+        let mut nofollow = link(about, "http://other.com", 0);
+        nofollow.rel = "nofollow ugc".to_string();
+        nofollow.nofollow = true;
+        manager
+            .save_links(crawl_id, about, &[link(about, home, 0), nofollow])
+            .await?;
+
+        let stored = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM links WHERE nofollow = 1")
+            .fetch_one(&manager.pool)
+            .await?;
+        assert_eq!(stored, 1);
+
+        let counts = manager.get_link_counts(crawl_id).await?;
+        assert_eq!(
+            counts[home],
+            LinkCounts {
+                inlinks: 1,
+                outlinks: 1
+            }
+        );
+        assert_eq!(
+            counts[about],
+            LinkCounts {
+                inlinks: 1,
+                outlinks: 2
+            }
+        );
+        assert_eq!(counts["http://other.com"].inlinks, 1);
+
+        let links = manager.get_links(crawl_id).await?;
+        assert_eq!(links.len(), 5);
+        assert_eq!(links[3], link(about, home, 0));
+        assert!(links[4].nofollow);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,495 @@
+//! JSON API under `/api/v1`, for tools that drive the dashboard without
+//! scraping its HTML. Errors come back as `{"error": "..."}` with a matching
+//! status code. `endpoints` is also the source of the OpenAPI document.
+
+use super::openapi::{self, Documented, Operation, ResponseDoc, Schema, schema_object};
+use super::{AppState, cancel_crawl, crawl_url, pause, queue_crawl, resume};
+use crate::state::{CrawlCounts, CrawlSummary, FrontierEntry, PageInfo, ResultQuery, StoredResult};
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    handler::Handler,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{MethodFilter, MethodRouter, on},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Page size when a request gives no `limit`.
+const DEFAULT_LIMIT: i64 = 100;
+/// Largest `limit` a request may ask for.
+const MAX_LIMIT: i64 = 1000;
+
+/// An `/api/v1` route and the OpenAPI operation describing it.
+pub(super) struct Endpoint {
+    /// Lowercase, as OpenAPI keys operations
+    pub method: &'static str,
+    /// Relative to `/api/v1`, in axum syntax
+    pub path: &'static str,
+    /// The handler's name
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    pub operation: Operation,
+    router: MethodRouter<Arc<AppState>>,
+}
+
+fn endpoint<H, T, A>(
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    handler: H,
+) -> Endpoint
+where
+    H: Handler<T, Arc<AppState>> + Documented<A>,
+    T: 'static,
+{
+    let mut operation = Operation::default();
+    H::describe(&mut operation, path);
+    let filter = match method {
+        "post" => MethodFilter::POST,
+        _ => MethodFilter::GET,
+    };
+    let name = std::any::type_name::<H>();
+    Endpoint {
+        method,
+        path,
+        operation_id: name.rsplit("::").next().unwrap_or(name),
+        summary,
+        operation,
+        router: on(filter, handler),
+    }
+}
+
+/// Every API route. Field descriptions live in the `Schema` impls below the
+/// types, where the OpenAPI document picks them up.
+pub(super) fn endpoints() -> Vec<Endpoint> {
+    vec![
+        endpoint("get", "/crawls", "List crawls", list_crawls),
+        endpoint("post", "/crawls", "Queue a new crawl", create_crawl),
+        endpoint("get", "/crawls/:id", "A crawl and its counts", get_crawl),
+        endpoint(
+            "post",
+            "/crawls/:id/stop",
+            "Stop or dequeue a crawl",
+            stop_crawl,
+        ),
+        endpoint(
+            "post",
+            "/crawls/:id/pause",
+            "Pause a running crawl",
+            pause_crawl,
+        ),
+        endpoint(
+            "post",
+            "/crawls/:id/resume",
+            "Resume a paused crawl",
+            resume_crawl,
+        ),
+        endpoint(
+            "get",
+            "/crawls/:id/results",
+            "Page through results",
+            list_results,
+        ),
+        endpoint(
+            "get",
+            "/crawls/:id/frontier",
+            "Page through the frontier",
+            list_frontier,
+        ),
+        endpoint(
+            "get",
+            "/crawls/:id/failures",
+            "Pages that failed",
+            list_failures,
+        ),
+    ]
+}
+
+pub(super) fn routes() -> Router<Arc<AppState>> {
+    endpoints()
+        .into_iter()
+        .fold(Router::new(), |router, e| router.route(e.path, e.router))
+}
+
+struct ApiError(StatusCode, String);
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+schema_object!(ErrorBody { error: String });
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(ErrorBody { error: self.1 })).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        ApiError(status, message)
+    }
+}
+
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+impl<T: Schema> ResponseDoc for ApiResult<T> {
+    fn describe(op: &mut Operation) {
+        op.response("200", "OK", T::schema());
+        op.response("default", "Error", ErrorBody::schema());
+    }
+}
+
+impl<T: Schema> ResponseDoc for std::result::Result<(StatusCode, Json<T>), ApiError> {
+    fn describe(op: &mut Operation) {
+        op.response("201", "Created", T::schema());
+        op.response("default", "Error", ErrorBody::schema());
+    }
+}
+
+#[derive(Serialize)]
+struct Crawl {
+    id: i64,
+    name: String,
+    url: String,
+    status: String,
+    updated_at: String,
+    queue_position: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct CrawlDetails {
+    #[serde(flatten)]
+    crawl: Crawl,
+    counts: CrawlCounts,
+}
+
+#[derive(Deserialize)]
+struct NewCrawl {
+    url: Option<String>,
+    config: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ControlResponse {
+    id: i64,
+    message: &'static str,
+}
+
+#[derive(Serialize)]
+struct Page<T> {
+    items: Vec<T>,
+    offset: i64,
+    limit: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ResultParams {
+    url: Option<String>,
+    since: Option<String>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct FrontierParams {
+    status: Option<String>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct PageParams {
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+schema_object!(Crawl {
+    id: i64,
+    name: String,
+    url: String => "Start URL",
+    status: String => "queued, active, paused, cancelled, failed or completed",
+    updated_at: String,
+    queue_position: Option<usize> => "Place in the job queue, starting at 1, while waiting to start",
+});
+
+impl Schema for CrawlDetails {
+    fn schema() -> Value {
+        // `crawl` is flattened into the details
+        let mut schema = Crawl::schema();
+        schema["title"] = "CrawlDetails".into();
+        schema["properties"]["counts"] = CrawlCounts::schema();
+        if let Some(required) = schema["required"].as_array_mut() {
+            required.push("counts".into());
+        }
+        schema
+    }
+}
+
+schema_object!(NewCrawl {
+    url: Option<String> => "Replaces the config's start URLs",
+    config: Option<String> => "Path of a config file on the server",
+});
+
+schema_object!(ControlResponse {
+    id: i64,
+    message: &'static str,
+});
+
+impl<T: Schema> Schema for Page<T> {
+    fn schema() -> Value {
+        let items = T::schema();
+        let title = format!("{}Page", items["title"].as_str().unwrap_or("Item"));
+        openapi::object(
+            &title,
+            vec![
+                openapi::Field {
+                    name: "items",
+                    schema: Vec::<T>::schema(),
+                    optional: false,
+                    description: None,
+                },
+                openapi::Field {
+                    name: "offset",
+                    schema: i64::schema(),
+                    optional: false,
+                    description: None,
+                },
+                openapi::Field {
+                    name: "limit",
+                    schema: i64::schema(),
+                    optional: false,
+                    description: None,
+                },
+                openapi::Field {
+                    name: "total",
+                    schema: i64::schema(),
+                    optional: true,
+                    description: Some("Matching items across all pages, where cheap to count"),
+                },
+            ],
+        )
+    }
+}
+
+schema_object!(ResultParams {
+    url: Option<String> => "Only URLs containing this text",
+    since: Option<String> => "Only results added or changed since this date or time",
+    offset: Option<i64>,
+    limit: Option<i64> => "Default 100, at most 1000",
+});
+
+schema_object!(FrontierParams {
+    status: Option<String> => "pending, processing, completed or failed",
+    offset: Option<i64>,
+    limit: Option<i64> => "Default 100, at most 1000",
+});
+
+schema_object!(PageParams {
+    offset: Option<i64>,
+    limit: Option<i64> => "Default 100, at most 1000",
+});
+
+/// Clamps requested paging to a non-negative offset and `1..=MAX_LIMIT`.
+fn paging(offset: Option<i64>, limit: Option<i64>) -> (i64, i64) {
+    (
+        offset.unwrap_or(0).max(0),
+        limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+    )
+}
+
+async fn to_crawls(state: &AppState, crawls: Vec<CrawlSummary>) -> anyhow::Result<Vec<Crawl>> {
+    let queue = state.state_manager.get_queued_jobs().await?;
+    Ok(crawls
+        .into_iter()
+        .map(|c| Crawl {
+            queue_position: queue.iter().position(|j| j.crawl_id == c.id).map(|i| i + 1),
+            url: crawl_url(&c.name),
+            id: c.id,
+            name: c.name,
+            status: c.status,
+            updated_at: c.updated_at,
+        })
+        .collect())
+}
+
+/// The crawl with `id`, or a 404.
+async fn find_crawl(state: &AppState, id: i64) -> Result<Crawl, ApiError> {
+    let crawl = state
+        .state_manager
+        .get_crawl(id)
+        .await?
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No crawl with ID {}", id)))?;
+    Ok(to_crawls(state, vec![crawl]).await?.remove(0))
+}
+
+async fn list_crawls(State(state): State<Arc<AppState>>) -> ApiResult<Vec<Crawl>> {
+    let crawls = state.state_manager.get_all_crawls().await?;
+    Ok(Json(to_crawls(&state, crawls).await?))
+}
+
+async fn create_crawl(
+    State(state): State<Arc<AppState>>,
+    Json(params): Json<NewCrawl>,
+) -> Result<(StatusCode, Json<Crawl>), ApiError> {
+    let id = queue_crawl(&state, params.url, params.config).await?;
+    Ok((StatusCode::CREATED, Json(find_crawl(&state, id).await?)))
+}
+
+async fn get_crawl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<CrawlDetails> {
+    let crawl = find_crawl(&state, id).await?;
+    let counts = state.state_manager.get_crawl_counts(id).await?;
+    Ok(Json(CrawlDetails { crawl, counts }))
+}
+
+async fn stop_crawl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<ControlResponse> {
+    let message = cancel_crawl(&state, id).await?;
+    Ok(Json(ControlResponse { id, message }))
+}
+
+async fn pause_crawl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<ControlResponse> {
+    let message = pause(&state, id)?;
+    Ok(Json(ControlResponse { id, message }))
+}
+
+async fn resume_crawl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> ApiResult<ControlResponse> {
+    let message = resume(&state, id).await?;
+    Ok(Json(ControlResponse { id, message }))
+}
+
+async fn list_results(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<ResultParams>,
+) -> ApiResult<Page<StoredResult>> {
+    find_crawl(&state, id).await?;
+    let (offset, limit) = paging(params.offset, params.limit);
+    let query = ResultQuery {
+        url_contains: params.url.filter(|s| !s.is_empty()),
+        since: params.since.filter(|s| !s.is_empty()),
+        offset,
+        limit,
+    };
+    let (items, total) = state.state_manager.query_results(id, &query).await?;
+    Ok(Json(Page {
+        items,
+        offset,
+        limit,
+        total: Some(total),
+    }))
+}
+
+async fn list_frontier(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<FrontierParams>,
+) -> ApiResult<Page<FrontierEntry>> {
+    find_crawl(&state, id).await?;
+    let (offset, limit) = paging(params.offset, params.limit);
+    let items = state
+        .state_manager
+        .get_frontier(id, params.status.as_deref(), offset, limit)
+        .await?;
+    Ok(Json(Page {
+        items,
+        offset,
+        limit,
+        total: None,
+    }))
+}
+
+async fn list_failures(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+) -> ApiResult<Page<PageInfo>> {
+    find_crawl(&state, id).await?;
+    let (offset, limit) = paging(params.offset, params.limit);
+    let items = state
+        .state_manager
+        .get_failed_pages(id, offset, limit)
+        .await?;
+    Ok(Json(Page {
+        items,
+        offset,
+        limit,
+        total: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{MemoryStateStore, SearchIndex, StateStore};
+
+    #[tokio::test]
+    async fn test_results_are_paged_and_filtered() -> anyhow::Result<()> {
+        let store = Arc::new(MemoryStateStore::new());
+        let crawl_id = store.create_crawl("Crawl: http://a.test/").await?;
+        for path in ["docs/1", "docs/2", "docs/3", "blog/1"] {
+            store
+                .save_result(
+                    crawl_id,
+                    &format!("http://a.test/{}", path),
+                    &serde_json::json!({ "title": path }),
+                    None,
+                    SearchIndex::Off,
+                )
+                .await?;
+        }
+        let state = Arc::new(AppState::new(store, None, 1, Arc::default(), vec![]));
+
+        let Json(page) = list_results(
+            State(state.clone()),
+            Path(crawl_id),
+            Query(ResultParams {
+                url: Some("/docs/".to_string()),
+                since: None,
+                offset: Some(1),
+                limit: Some(1),
+            }),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!(e.1))?;
+        assert_eq!(page.total, Some(3));
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].url, "http://a.test/docs/2");
+
+        let Json(details) = get_crawl(State(state.clone()), Path(crawl_id))
+            .await
+            .map_err(|e| anyhow::anyhow!(e.1))?;
+        assert_eq!(details.crawl.url, "http://a.test/");
+        assert_eq!(details.counts.results, 4);
+
+        let missing = get_crawl(State(state), Path(crawl_id + 1)).await;
+        assert!(matches!(missing, Err(ApiError(StatusCode::NOT_FOUND, _))));
+        Ok(())
+    }
+}
@@ -0,0 +1,476 @@
+//! Who may use the dashboard: credentials checked on every request, sessions
+//! for browsers, CSRF checks on anything that changes state, and the
+//! directories crawl configs may be loaded from.
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+
+/// HttpOnly cookie naming the client's session.
+const SESSION_COOKIE: &str = "spider_session";
+/// Cookie carrying the session's CSRF token to the page's scripts, which
+/// send it back in `CSRF_HEADER`.
+const CSRF_COOKIE: &str = "csrf_token";
+const CSRF_HEADER: &str = "x-csrf-token";
+/// Query parameter a browser signs in with when only a token is configured.
+const TOKEN_PARAM: &str = "token";
+
+/// How long a session lasts before the client is given a new one, with a new
+/// CSRF token; signed-in browsers then sign in again.
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+/// Sessions kept at once; the oldest go first beyond this.
+const MAX_SESSIONS: usize = 10_000;
+
+/// Credentials the dashboard accepts. With neither set, anyone who can reach
+/// it may use it.
+#[derive(Clone, Default)]
+pub struct Auth {
+    /// Accepted as `Authorization: Bearer <token>`, or once as `?token=` to
+    /// sign a browser in
+    pub token: Option<String>,
+    /// `user:password` for HTTP basic auth, which browsers prompt for
+    pub basic: Option<String>,
+}
+
+impl Auth {
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some() || self.basic.is_some()
+    }
+
+    fn allows(&self, headers: &HeaderMap) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let Some(value) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+        if let (Some(token), Some(given)) = (&self.token, value.strip_prefix("Bearer ")) {
+            return same(token, given.trim());
+        }
+        if let (Some(basic), Some(given)) = (&self.basic, value.strip_prefix("Basic ")) {
+            return base64::engine::general_purpose::STANDARD
+                .decode(given.trim())
+                .is_ok_and(|decoded| same(basic.as_bytes(), &decoded));
+        }
+        false
+    }
+}
+
+/// Compares secrets in constant time.
+fn same(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    a.as_ref().ct_eq(b.as_ref()).into()
+}
+
+struct Session {
+    csrf: String,
+    /// Signed in with the token, so the session alone lets requests in
+    signed_in: bool,
+    expires: Instant,
+}
+
+/// The configured credentials and the sessions handed out to browsers.
+pub(super) struct Guard {
+    auth: Auth,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Guard {
+    pub fn new(auth: Auth) -> Self {
+        Self {
+            auth,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts a session; returns the cookies that hand it to the client.
+    fn start_session(&self, signed_in: bool) -> [HeaderValue; 2] {
+        let id = random_token();
+        let csrf = random_token();
+        let cookies = [
+            cookie(&format!("{}={}; HttpOnly", SESSION_COOKIE, id)),
+            cookie(&format!("{}={}", CSRF_COOKIE, csrf)),
+        ];
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires > now);
+        if sessions.len() >= MAX_SESSIONS
+            && let Some(oldest) = sessions
+                .iter()
+                .min_by_key(|(_, s)| s.expires)
+                .map(|(id, _)| id.clone())
+        {
+            sessions.remove(&oldest);
+        }
+        sessions.insert(
+            id,
+            Session {
+                csrf,
+                signed_in,
+                expires: now + SESSION_TTL,
+            },
+        );
+        cookies
+    }
+
+    /// The live session the request's cookie names: whether it's signed in,
+    /// and its CSRF token.
+    fn session(&self, headers: &HeaderMap) -> Option<(bool, String)> {
+        let id = cookie_value(headers, SESSION_COOKIE)?;
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(id)
+            .filter(|s| s.expires > Instant::now())
+            .map(|s| (s.signed_in, s.csrf.clone()))
+    }
+}
+
+fn random_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn cookie(value: &str) -> HeaderValue {
+    HeaderValue::from_str(&format!("{}; Path=/; SameSite=Strict", value))
+        .expect("cookie values are hex tokens")
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|c| c.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Rejects requests without valid credentials or a signed-in session, asking
+/// browsers for basic auth when it's enabled. `?token=` with the right token
+/// signs a browser in and sends it back to the same page without it.
+pub(super) async fn require_auth(
+    State(guard): State<Arc<Guard>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(response) = sign_in(&guard, &request) {
+        return response;
+    }
+    let allowed = guard.auth.allows(request.headers())
+        || guard
+            .session(request.headers())
+            .is_some_and(|(signed_in, _)| signed_in);
+    if allowed {
+        next.run(request).await
+    } else {
+        unauthorized(&guard.auth)
+    }
+}
+
+/// Handles `?token=` on a page request when a token is configured.
+fn sign_in(guard: &Guard, request: &Request) -> Option<Response> {
+    let token = guard.auth.token.as_ref()?;
+    if request.method() != Method::GET {
+        return None;
+    }
+    let (given, rest) = take_token_param(request.uri())?;
+    if !same(token, given) {
+        return Some(unauthorized(&guard.auth));
+    }
+    let mut response = Redirect::to(&rest).into_response();
+    for cookie in guard.start_session(true) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    Some(response)
+}
+
+fn unauthorized(auth: &Auth) -> Response {
+    let (challenge, message) = if auth.basic.is_some() {
+        (
+            "Basic realm=\"spider-cli\", charset=\"UTF-8\"",
+            "Authentication required",
+        )
+    } else {
+        (
+            "Bearer",
+            "Authentication required: send the token as a Bearer header, or open /?token=<token> to sign a browser in",
+        )
+    };
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, challenge)],
+        message,
+    )
+        .into_response()
+}
+
+/// The `?token=` value of `uri`, and the path and query without it.
+fn take_token_param(uri: &axum::http::Uri) -> Option<(String, String)> {
+    let query = uri.query()?;
+    let mut token = None;
+    let mut rest = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        if key == TOKEN_PARAM {
+            token = Some(value.into_owned());
+        } else {
+            rest.append_pair(&key, &value);
+        }
+    }
+    let rest = rest.finish();
+    let location = if rest.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), rest)
+    };
+    Some((token?, location))
+}
+
+/// Refuses requests that change state from other sites' pages, which
+/// browsers mark with their `Origin` or `Referer`; clients that send
+/// neither, like curl, aren't browsers and pass. Pages served to a client
+/// without a session start one, handing out its CSRF token as a cookie for
+/// `require_csrf_token`.
+pub(super) async fn same_origin(
+    State(guard): State<Arc<Guard>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if !safe && own_origin(request.headers()) == Some(false) {
+        return (StatusCode::FORBIDDEN, "Cross-origin request refused").into_response();
+    }
+
+    let has_session = guard.session(request.headers()).is_some();
+    let mut response = next.run(request).await;
+    let is_page = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if !has_session && is_page {
+        for cookie in guard.start_session(false) {
+            response.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+/// Whether the request came from one of the dashboard's own pages, going by
+/// its `Origin`, or its `Referer` without one; `None` when it has neither.
+fn own_origin(headers: &HeaderMap) -> Option<bool> {
+    let source = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))?;
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+    let authority = source
+        .to_str()
+        .ok()
+        .and_then(|o| url::Url::parse(o).ok())
+        .and_then(|u| {
+            let host = u.host_str()?.to_string();
+            Some(match u.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        });
+    Some(authority.is_some() && authority.as_deref() == host)
+}
+
+/// Requires an `Origin` or `Referer` from the dashboard itself and the
+/// session's CSRF token in `X-CSRF-Token`, which only the dashboard's own
+/// pages can read from their cookie. Guards the HTMX form routes.
+pub(super) async fn require_csrf_token(
+    State(guard): State<Arc<Guard>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if own_origin(request.headers()) != Some(true) {
+        return (
+            StatusCode::FORBIDDEN,
+            "Missing or foreign Origin and Referer",
+        )
+            .into_response();
+    }
+    let valid = match (
+        guard.session(request.headers()),
+        request.headers().get(CSRF_HEADER),
+    ) {
+        (Some((_, csrf)), Some(given)) => same(given.as_bytes(), csrf),
+        _ => false,
+    };
+    if !valid {
+        return (StatusCode::FORBIDDEN, "Missing or invalid CSRF token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Canonical forms of the directories crawl configs may be loaded from;
+/// ones that don't exist are left out.
+pub(super) fn config_dirs(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    dirs.iter()
+        .filter_map(|dir| match std::fs::canonicalize(dir) {
+            Ok(dir) => Some(dir),
+            Err(_) => {
+                tracing::warn!("Config directory {} not found, ignoring it", dir.display());
+                None
+            }
+        })
+        .collect()
+}
+
+/// `path`, resolved, if it lies inside one of `dirs`.
+pub(super) fn allowed_config(dirs: &[PathBuf], path: &str) -> Result<PathBuf> {
+    let resolved = std::fs::canonicalize(Path::new(path))
+        .with_context(|| format!("Config not found: {}", path))?;
+    if dirs.iter().any(|dir| resolved.starts_with(dir)) {
+        Ok(resolved)
+    } else {
+        anyhow::bail!(
+            "{} is outside the allowed config directories (see --config-dir)",
+            path
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_and_config_checks() {
+        let auth = Auth {
+            token: Some("s3cret".to_string()),
+            basic: Some("admin:pw".to_string()),
+        };
+        let with = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+        assert!(auth.allows(&with("Bearer s3cret")));
+        assert!(!auth.allows(&with("Bearer wrong")));
+        // admin:pw
+        assert!(auth.allows(&with("Basic YWRtaW46cHc=")));
+        assert!(!auth.allows(&with("Basic YWRtaW46eA==")));
+        assert!(!auth.allows(&HeaderMap::new()));
+        assert!(Auth::default().allows(&HeaderMap::new()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "127.0.0.1:3030".parse().unwrap());
+        assert_eq!(own_origin(&headers), None);
+        headers.insert(header::REFERER, "http://127.0.0.1:3030/".parse().unwrap());
+        assert_eq!(own_origin(&headers), Some(true));
+        headers.insert(header::ORIGIN, "https://evil.test".parse().unwrap());
+        assert_eq!(own_origin(&headers), Some(false));
+
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("configs");
+        std::fs::create_dir(&allowed).unwrap();
+        std::fs::write(allowed.join("site.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("secret.json"), "{}").unwrap();
+        let dirs = config_dirs(std::slice::from_ref(&allowed));
+        let inside = allowed.join("site.json");
+        assert!(allowed_config(&dirs, inside.to_str().unwrap()).is_ok());
+        let escape = allowed.join("../secret.json");
+        assert!(allowed_config(&dirs, escape.to_str().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_sign_in_and_session_csrf() {
+        use axum::{Router, middleware, routing::get};
+
+        let guard = Arc::new(Guard::new(Auth {
+            token: Some("s3cret".to_string()),
+            basic: None,
+        }));
+        let app = Router::new()
+            .route("/", get(|| async { axum::response::Html("page") }))
+            .route(
+                "/control/stop",
+                axum::routing::post(|| async { "stopped" }).route_layer(
+                    middleware::from_fn_with_state(guard.clone(), require_csrf_token),
+                ),
+            )
+            .layer(middleware::from_fn_with_state(guard.clone(), same_origin))
+            .layer(middleware::from_fn_with_state(guard, require_auth));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = spider::reqwest::Client::builder()
+            .redirect(spider::reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let base = format!("http://{}", addr);
+
+        let res = client.get(&base).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = client
+            .get(format!("{}/?token=wrong", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        // Signing in drops the token from the URL and sets the session
+        let res = client
+            .get(format!("{}/?token=s3cret", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers()[header::LOCATION], "/");
+        let cookies: Vec<String> = res
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|c| c.to_str().unwrap().split(';').next().unwrap().to_string())
+            .collect();
+        let csrf = cookies[1].strip_prefix("csrf_token=").unwrap().to_string();
+        let cookie_header = cookies.join("; ");
+
+        let res = client
+            .get(&base)
+            .header(header::COOKIE, &cookie_header)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let stop = |origin: Option<&str>, token: &str| {
+            let mut req = client
+                .post(format!("{}/control/stop", base))
+                .header(header::COOKIE, &cookie_header)
+                .header(CSRF_HEADER, token);
+            if let Some(origin) = origin {
+                req = req.header(header::ORIGIN, origin);
+            }
+            req.send()
+        };
+        assert_eq!(
+            stop(None, &csrf).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            stop(Some(&base), "other").await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            stop(Some(&base), &csrf).await.unwrap().status(),
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Live progress of crawls, pushed to clients over WebSocket
+//! (`/ws/crawls`) or, where a socket can't be opened, server-sent events
+//! (`/sse/crawls`). Both take an optional `/:id` to follow a single crawl.
+
+use super::AppState;
+use crate::crawler::CrawlEvent;
+use crate::state::StateStore;
+use axum::{
+    extract::{
+        Path, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+
+/// Updates buffered per client before a slow one starts missing them.
+const BUFFER: usize = 1024;
+
+/// Something that happened in a crawl, tagged by `type` when serialized:
+/// what the crawler reports about its pages, and the job's progress through
+/// the queue.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum StreamEvent {
+    /// Waiting in the job queue
+    Queued,
+    /// Took a crawl slot and began fetching
+    Started,
+    /// Links found on the crawled site that haven't been fetched yet
+    Frontier { pending: i64 },
+    /// The crawl stopped; `status` is how it ended
+    Finished { status: String },
+    /// A page fetched, extracted, skipped or failed, or the whole crawl
+    /// failed
+    #[serde(untagged)]
+    Crawl(CrawlEvent),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct Update {
+    pub crawl_id: i64,
+    #[serde(flatten)]
+    pub event: StreamEvent,
+}
+
+/// Fans crawl events out to every connected client.
+#[derive(Clone)]
+pub struct EventHub {
+    tx: broadcast::Sender<Update>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(BUFFER).0,
+        }
+    }
+
+    pub(super) fn publish(&self, crawl_id: i64, event: StreamEvent) {
+        // No receivers just means nobody is watching
+        let _ = self.tx.send(Update { crawl_id, event });
+    }
+
+    /// Updates from now on, of one crawl or all of them. A client that falls
+    /// too far behind skips what it missed rather than disconnecting.
+    fn subscribe(&self, crawl_id: Option<i64>) -> impl Stream<Item = Update> + use<> {
+        futures::stream::unfold(self.tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(update) => return Some((update, rx)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .filter(move |u| std::future::ready(crawl_id.is_none_or(|id| u.crawl_id == id)))
+    }
+}
+
+/// Publishes the events of crawl `crawl_id` until its crawler is done, with
+/// the size of its frontier at most once a second. Pages being queued aren't
+/// streamed; `queued` is the job's.
+pub async fn relay(
+    hub: EventHub,
+    state_manager: Arc<dyn StateStore>,
+    crawl_id: i64,
+    start_url: String,
+    mut events: mpsc::Receiver<CrawlEvent>,
+) {
+    let authority = super::authority(&start_url);
+    let mut frontier_checked = Instant::now();
+    while let Some(event) = events.recv().await {
+        let update = match event {
+            CrawlEvent::Queued { .. } => continue,
+            CrawlEvent::Finished { summary } => StreamEvent::Finished {
+                status: summary.status.as_str().to_string(),
+            },
+            event => StreamEvent::Crawl(event),
+        };
+        hub.publish(crawl_id, update);
+
+        // Counting the frontier takes a query, so at most once a second
+        if let Some(authority) = &authority
+            && frontier_checked.elapsed() >= Duration::from_secs(1)
+        {
+            frontier_checked = Instant::now();
+            if let Ok(pending) = state_manager
+                .count_unvisited_links(crawl_id, authority)
+                .await
+            {
+                hub.publish(crawl_id, StreamEvent::Frontier { pending });
+            }
+        }
+    }
+}
+
+pub(super) async fn ws_all(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let updates = state.events.subscribe(None);
+    ws.on_upgrade(move |socket| forward(socket, updates))
+}
+
+pub(super) async fn ws_crawl(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let updates = state.events.subscribe(Some(id));
+    ws.on_upgrade(move |socket| forward(socket, updates))
+}
+
+/// Sends each update as a JSON text message until either side goes away.
+/// Anything the client sends is ignored.
+async fn forward(mut socket: WebSocket, updates: impl Stream<Item = Update>) {
+    let mut updates = std::pin::pin!(updates);
+    loop {
+        tokio::select! {
+            update = updates.next() => {
+                let Some(update) = update else { break };
+                let Ok(text) = serde_json::to_string(&update) else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+pub(super) async fn sse_all(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    sse(state.events.subscribe(None))
+}
+
+pub(super) async fn sse_crawl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    sse(state.events.subscribe(Some(id)))
+}
+
+fn sse(updates: impl Stream<Item = Update> + Send + 'static) -> impl IntoResponse {
+    Sse::new(updates.map(|update| Event::default().json_data(update)))
+        .keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_only_see_their_crawl() {
+        let hub = EventHub::new();
+        let mut one = std::pin::pin!(hub.subscribe(Some(2)));
+        let mut all = std::pin::pin!(hub.subscribe(None));
+
+        hub.publish(1, StreamEvent::Started);
+        hub.publish(
+            2,
+            StreamEvent::Crawl(CrawlEvent::Fetched {
+                url: "http://a.test/".to_string(),
+                status: 404,
+                bytes: 9,
+                latency_ms: None,
+            }),
+        );
+
+        assert_eq!(all.next().await.unwrap().crawl_id, 1);
+        let update = one.next().await.unwrap();
+        assert_eq!(update.crawl_id, 2);
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            serde_json::json!({
+                "crawl_id": 2,
+                "type": "fetched",
+                "url": "http://a.test/",
+                "status": 404,
+                "bytes": 9,
+                "latency_ms": null,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relay_streams_crawler_events() {
+        let hub = EventHub::new();
+        let updates = hub.subscribe(Some(1));
+        let store: Arc<dyn StateStore> = Arc::new(crate::state::MemoryStateStore::new());
+        let (events, progress) = crate::crawler::channel();
+        let relayed = tokio::spawn(relay(hub, store, 1, "http://a.test/".to_string(), progress));
+
+        let url = "http://a.test/".to_string();
+        events
+            .send(CrawlEvent::Queued { url: url.clone() })
+            .await
+            .unwrap();
+        events
+            .send(CrawlEvent::Failed {
+                url: Some(url),
+                error: "timed out".to_string(),
+            })
+            .await
+            .unwrap();
+        events
+            .send(CrawlEvent::Failed {
+                url: None,
+                error: "gone".to_string(),
+            })
+            .await
+            .unwrap();
+        drop(events);
+        relayed.await.unwrap();
+
+        // The hub is gone with the relay, which ends the stream
+        let received: Vec<_> = updates
+            .map(|u| serde_json::to_value(&u).unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            received,
+            vec![
+                serde_json::json!({"crawl_id": 1, "type": "failed", "url": "http://a.test/", "error": "timed out"}),
+                serde_json::json!({"crawl_id": 1, "type": "failed", "url": null, "error": "gone"}),
+            ]
+        );
+    }
+}
@@ -1,38 +1,122 @@
-use crate::state::StateManager;
+use crate::crawler::CrawlEvent;
+use crate::features::metrics::Metrics;
+use crate::features::schedule::Scheduler;
+use crate::state::{
+    CrawlCounts, CrawlJob, CrawlStatus, CrawlSummary, LinkCounts, PageInfo, ScheduledRun,
+    StateStore, is_failure,
+};
 use anyhow::Result;
 use askama::Template;
 use axum::{
     Router,
-    extract::{Form, Path, State},
+    extract::{Form, Path, Query, State},
+    middleware,
     response::IntoResponse,
     routing::{get, post},
 };
+use events::StreamEvent;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use tower_http::services::ServeDir;
 
+mod api;
+mod auth;
+mod events;
+mod openapi;
+
+/// Crawls started from the dashboard that may run at once by default.
+pub const DEFAULT_MAX_CRAWLS: usize = 2;
+
+pub use auth::Auth;
+pub use events::{EventHub, relay};
+
 pub struct DashboardServer {
-    state_manager: Arc<StateManager>,
+    state_manager: Arc<dyn StateStore>,
+    scheduler: Option<Arc<Scheduler>>,
+    max_crawls: usize,
+    metrics: Arc<Metrics>,
+    bind: IpAddr,
+    auth: Auth,
+    config_dirs: Vec<PathBuf>,
+    events: EventHub,
 }
 
 struct AppState {
-    state_manager: Arc<StateManager>,
-    sites: RwLock<Vec<SiteDisplay>>,
-    tokens: RwLock<HashMap<i64, tokio_util::sync::CancellationToken>>,
+    state_manager: Arc<dyn StateStore>,
+    /// Set when serving alongside `spider-cli daemon`
+    scheduler: Option<Arc<Scheduler>>,
+    handles: RwLock<HashMap<i64, CrawlHandle>>,
+    /// Crawls allowed to run at once; later ones wait in the job queue
+    max_crawls: usize,
+    running: AtomicUsize,
+    /// Wakes the dispatcher when a job is queued or a crawl finishes
+    jobs_changed: tokio::sync::Notify,
+    /// Progress pushed to the dashboard and other stream clients
+    events: EventHub,
+    metrics: Arc<Metrics>,
+    /// Where crawls started here may load their config from, canonicalized
+    config_dirs: Vec<PathBuf>,
+}
+
+impl AppState {
+    fn new(
+        state_manager: Arc<dyn StateStore>,
+        scheduler: Option<Arc<Scheduler>>,
+        max_crawls: usize,
+        metrics: Arc<Metrics>,
+        config_dirs: Vec<PathBuf>,
+    ) -> Self {
+        Self {
+            state_manager,
+            scheduler,
+            handles: RwLock::new(HashMap::new()),
+            max_crawls,
+            running: AtomicUsize::new(0),
+            jobs_changed: tokio::sync::Notify::new(),
+            events: EventHub::new(),
+            metrics,
+            config_dirs,
+        }
+    }
+}
+
+/// Stops a running crawl, either for good or paused.
+struct CrawlHandle {
+    cancel: tokio_util::sync::CancellationToken,
+    pause: Arc<AtomicBool>,
 }
 
-#[derive(Clone, Default)]
+/// A crawl as the stats view shows it, read from the database so crawls run
+/// by other processes sharing it show up too.
 struct SiteDisplay {
     id: i64,
     url: String,
+    /// As stored: active, paused, cancelled, failed or completed
+    status: String,
+    counts: CrawlCounts,
+    /// The pages saved most recently
     entries: Vec<LogEntry>,
     finished: bool,
+    /// Stopped with its frontier saved, and can be resumed
+    paused: bool,
+    /// Running in this process or waiting in the job queue, so it can be
+    /// stopped from here
+    controllable: bool,
+    /// Place in the job queue, starting at 1, while waiting to start
+    queue_position: Option<usize>,
+    /// Links found but not fetched yet, while running
+    frontier: Option<i64>,
 }
 
-#[derive(Clone)]
 struct LogEntry {
+    /// The response status, or what happened instead
     status: String,
+    /// Picks the entry's colour: DONE, ERR or SKIP
+    kind: &'static str,
     url: String,
 }
 
@@ -44,6 +128,28 @@ struct IndexTemplate {}
 #[template(path = "stats.html")]
 struct StatsTemplate {
     sites: Vec<SiteDisplay>,
+    /// Crawls past the newest `SHOWN_CRAWLS`, listed without their details
+    older: Vec<OlderCrawl>,
+}
+
+struct OlderCrawl {
+    id: i64,
+    url: String,
+    status: String,
+}
+
+struct UpcomingDisplay {
+    config: String,
+    schedule: String,
+    next_run: String,
+    running: bool,
+}
+
+#[derive(Template)]
+#[template(path = "schedule.html")]
+struct ScheduleTemplate {
+    upcoming: Vec<UpcomingDisplay>,
+    runs: Vec<ScheduledRun>,
 }
 
 #[derive(Template)]
@@ -57,69 +163,209 @@ struct HierarchyTemplate {
     stats: String,
 }
 
+#[derive(Template)]
+#[template(path = "hierarchy_diff.html")]
+struct HierarchyDiffTemplate {
+    crawl_a: i64,
+    crawl_b: i64,
+    summary: String,
+}
+
 #[derive(Deserialize)]
 struct StartParams {
     url: Option<String>,
     config: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct SearchParams {
+    q: String,
+}
+
 #[derive(Deserialize)]
 struct StopParams {
     id: i64,
 }
 
 impl DashboardServer {
-    pub fn new(state_manager: Arc<StateManager>) -> Self {
-        Self { state_manager }
+    pub fn new(state_manager: Arc<dyn StateStore>) -> Self {
+        Self {
+            state_manager,
+            scheduler: None,
+            max_crawls: DEFAULT_MAX_CRAWLS,
+            metrics: Arc::default(),
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            auth: Auth::default(),
+            config_dirs: vec![PathBuf::from("configs")],
+            events: EventHub::new(),
+        }
+    }
+
+    /// Crawls started from the dashboard that may run at once; the rest are
+    /// queued.
+    pub fn with_max_crawls(mut self, max_crawls: usize) -> Self {
+        self.max_crawls = max_crawls.max(1);
+        self
+    }
+
+    /// Show the daemon's upcoming runs on the dashboard.
+    pub fn with_scheduler(mut self, scheduler: Arc<Scheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Serve these metrics at `/metrics`, shared with whatever else in the
+    /// process reports to them.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Stream the events published to `events` on the live feeds, along with
+    /// the dashboard's own crawls. Crawls run elsewhere in the process
+    /// `relay` theirs to it.
+    pub fn with_events(mut self, events: EventHub) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Listen on `bind` instead of only the loopback interface.
+    pub fn with_bind(mut self, bind: IpAddr) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    /// Require these credentials on every route.
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Directories the `config` of crawls started from the dashboard must be
+    /// in.
+    pub fn with_config_dirs(mut self, config_dirs: Vec<PathBuf>) -> Self {
+        self.config_dirs = config_dirs;
+        self
     }
 
     pub async fn run(self, port: u16) -> Result<()> {
-        let existing_crawls = self
-            .state_manager
-            .get_all_crawls()
-            .await
-            .unwrap_or_default();
-        let mut initial_sites = vec![];
-        for c in existing_crawls {
-            let url = c
-                .name
-                .strip_prefix("Crawl: ")
-                .or_else(|| c.name.strip_prefix("UI Crawl: "))
-                .unwrap_or(&c.name)
-                .to_string();
-            initial_sites.push(SiteDisplay {
-                id: c.id,
-                url,
-                entries: vec![],
-                finished: true,
-            });
+        if !self.bind.is_loopback() && !self.auth.is_enabled() {
+            tracing::warn!(
+                "Dashboard listening on {} without authentication; anyone who can reach it can start crawls",
+                self.bind
+            );
+        }
+
+        // Crawls interrupted by the last shutdown start over from their
+        // frontier, before any that were still waiting
+        let requeued = self.state_manager.requeue_running_jobs().await?;
+        if requeued > 0 {
+            tracing::info!("Requeued {} interrupted crawls", requeued);
         }
 
         let state = Arc::new(AppState {
-            state_manager: self.state_manager,
-            sites: RwLock::new(initial_sites),
-            tokens: RwLock::new(HashMap::new()),
+            events: self.events,
+            ..AppState::new(
+                self.state_manager,
+                self.scheduler,
+                self.max_crawls,
+                self.metrics,
+                auth::config_dirs(&self.config_dirs),
+            )
         });
+        tokio::spawn(dispatch_jobs(state.clone()));
+
+        let guard = Arc::new(auth::Guard::new(self.auth));
+        let forms = Router::new()
+            .route("/control/start", post(start_crawl))
+            .route("/control/stop", post(stop_crawl))
+            .route("/control/pause", post(pause_crawl))
+            .route("/control/resume", post(resume_crawl))
+            .route_layer(middleware::from_fn_with_state(
+                guard.clone(),
+                auth::require_csrf_token,
+            ));
 
         let app = Router::new()
             .route("/", get(index))
             .route("/help", get(help))
             .route("/stats", get(stats))
+            .route("/schedule", get(schedule))
             .route("/hierarchy/:id", get(hierarchy))
             .route("/hierarchy/:id/json", get(hierarchy_json))
-            .route("/control/start", post(start_crawl))
-            .route("/control/stop", post(stop_crawl))
+            .route("/hierarchy/:id/search", get(hierarchy_search))
+            .route("/hierarchy/:id/analysis", get(hierarchy_analysis))
+            .route("/hierarchy/:id/analysis/csv", get(hierarchy_analysis_csv))
+            .route("/diff/:a/:b", get(hierarchy_diff))
+            .route("/diff/:a/:b/json", get(hierarchy_diff_json))
+            .merge(forms)
+            .nest("/api/v1", api::routes())
+            .route("/api/openapi.json", get(openapi::openapi_json))
+            .route("/api/docs", get(openapi::api_docs))
+            .route("/ws/crawls", get(events::ws_all))
+            .route("/ws/crawls/:id", get(events::ws_crawl))
+            .route("/sse/crawls", get(events::sse_all))
+            .route("/sse/crawls/:id", get(events::sse_crawl))
+            .route("/metrics", get(metrics))
             .nest_service("/assets", ServeDir::new("assets"))
-            .with_state(state);
+            .with_state(state)
+            .layer(middleware::from_fn_with_state(
+                guard.clone(),
+                auth::same_origin,
+            ))
+            .layer(middleware::from_fn_with_state(guard, auth::require_auth));
 
-        let addr = format!("0.0.0.0:{}", port);
-        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let addr = SocketAddr::new(self.bind, port);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
         tracing::info!("Dashboard running on http://{}", addr);
         axum::serve(listener, app).await?;
         Ok(())
     }
 }
 
+/// Serves only `/metrics`, for crawls that run without the dashboard.
+pub async fn serve_metrics(
+    metrics: Arc<Metrics>,
+    bind: IpAddr,
+    port: u16,
+    auth: Auth,
+) -> Result<()> {
+    let app = Router::new()
+        .route(
+            "/metrics",
+            get(move || async move { metrics_text(&metrics) }),
+        )
+        .layer(middleware::from_fn_with_state(
+            Arc::new(auth::Guard::new(auth)),
+            auth::require_auth,
+        ));
+    let addr = SocketAddr::new(bind, port);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Metrics available at http://{}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+fn metrics_text(metrics: &Metrics) -> impl IntoResponse + use<> {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        metrics.render(),
+    )
+}
+
+async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    state
+        .metrics
+        .set_dashboard_crawls("running", state.running.load(Ordering::SeqCst));
+    if let Ok(queued) = state.state_manager.get_queued_jobs().await {
+        state.metrics.set_dashboard_crawls("queued", queued.len());
+    }
+    metrics_text(&state.metrics)
+}
+
 async fn index() -> impl IntoResponse {
     match (IndexTemplate {}).render() {
         Ok(html) => axum::response::Html(html).into_response(),
@@ -140,8 +386,11 @@ async fn stats(
         return axum::response::Redirect::to("/").into_response();
     }
 
-    let sites = state.sites.read().unwrap().clone();
-    let template = StatsTemplate { sites };
+    let (sites, older) = match load_sites(&state).await {
+        Ok(loaded) => loaded,
+        Err(e) => return database_error(e).into_response(),
+    };
+    let template = StatsTemplate { sites, older };
     match template.render() {
         Ok(html) => axum::response::Html(html).into_response(),
         Err(e) => (
@@ -152,6 +401,158 @@ async fn stats(
     }
 }
 
+/// Crawls shown with their counts and log; older ones are only listed, their
+/// details a click away, so a long history doesn't slow every refresh.
+const SHOWN_CRAWLS: usize = 20;
+
+/// The newest crawls in the database with their details, and the rest.
+async fn load_sites(state: &AppState) -> Result<(Vec<SiteDisplay>, Vec<OlderCrawl>)> {
+    let queue = state.state_manager.get_queued_jobs().await?;
+    let mut crawls = state.state_manager.get_all_crawls().await?;
+    crawls.sort_by_key(|c| std::cmp::Reverse(c.id));
+    let older = crawls
+        .split_off(crawls.len().min(SHOWN_CRAWLS))
+        .into_iter()
+        .map(|c| OlderCrawl {
+            id: c.id,
+            url: crawl_url(&c.name),
+            status: c.status,
+        })
+        .collect();
+
+    let ids: Vec<i64> = crawls.iter().map(|c| c.id).collect();
+    let mut counts = state.state_manager.get_crawls_counts(&ids).await?;
+    let mut sites = Vec::with_capacity(crawls.len());
+    for crawl in crawls {
+        let crawl_counts = counts.remove(&crawl.id).unwrap_or_default();
+        sites.push(site_display(state, crawl, crawl_counts, &queue).await?);
+    }
+    Ok((sites, older))
+}
+
+async fn site_display(
+    state: &AppState,
+    crawl: CrawlSummary,
+    counts: CrawlCounts,
+    queue: &[CrawlJob],
+) -> Result<SiteDisplay> {
+    let url = crawl_url(&crawl.name);
+    let status = CrawlStatus::parse(&crawl.status);
+    let queue_position = queue
+        .iter()
+        .position(|j| j.crawl_id == crawl.id)
+        .map(|i| i + 1);
+    let running_here = state.handles.read().unwrap().contains_key(&crawl.id);
+
+    let entries = state
+        .state_manager
+        .get_recent_pages(crawl.id, RECENT_PAGES)
+        .await?
+        .into_iter()
+        .map(LogEntry::from)
+        .collect();
+    // Only live crawls have a frontier worth counting
+    let frontier = match authority(&url) {
+        Some(authority) if status == Some(CrawlStatus::Active) && queue_position.is_none() => Some(
+            state
+                .state_manager
+                .count_unvisited_links(crawl.id, &authority)
+                .await?,
+        ),
+        _ => None,
+    };
+
+    Ok(SiteDisplay {
+        id: crawl.id,
+        url,
+        counts,
+        entries,
+        finished: !matches!(
+            status,
+            Some(CrawlStatus::Queued | CrawlStatus::Active | CrawlStatus::Paused)
+        ),
+        paused: status == Some(CrawlStatus::Paused),
+        controllable: running_here || queue_position.is_some(),
+        queue_position,
+        frontier,
+        status: crawl.status,
+    })
+}
+
+/// Pages shown in each crawl's log when the stats view is drawn; the live
+/// feed appends to it from there.
+const RECENT_PAGES: i64 = 10;
+
+impl From<PageInfo> for LogEntry {
+    fn from(page: PageInfo) -> Self {
+        match page.duplicate_of {
+            Some(original) if !is_failure(page.status) => LogEntry {
+                status: "SKIP".to_string(),
+                kind: "SKIP",
+                url: format!("{} (duplicate of {})", page.url, original),
+            },
+            _ => LogEntry {
+                status: page.status.to_string(),
+                kind: if is_failure(page.status) {
+                    "ERR"
+                } else {
+                    "DONE"
+                },
+                url: page.url,
+            },
+        }
+    }
+}
+
+/// The host and port of `url`, to tell a site's links from external ones.
+fn authority(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Upcoming runs of the daemon's scheduled configs and the latest runs
+/// recorded in the database.
+async fn schedule(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let upcoming = state
+        .scheduler
+        .as_ref()
+        .map(|s| s.upcoming())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|u| UpcomingDisplay {
+            config: u.config,
+            schedule: u.schedule,
+            next_run: u.next_run.map_or("never".to_string(), |t| {
+                t.format("%Y-%m-%d %H:%M UTC").to_string()
+            }),
+            running: u.running,
+        })
+        .collect();
+    let runs = match state.state_manager.get_scheduled_runs(None, 20).await {
+        Ok(runs) => runs,
+        Err(e) => {
+            return (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Database error: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    match (ScheduleTemplate { upcoming, runs }).render() {
+        Ok(html) => axum::response::Html(html).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
 async fn help() -> impl IntoResponse {
     match (HelpTemplate {}).render() {
         Ok(html) => axum::response::Html(html).into_response(),
@@ -202,19 +603,37 @@ async fn hierarchy(State(state): State<Arc<AppState>>, Path(id): Path<i64>) -> i
     }
 }
 
+#[derive(serde::Serialize)]
+struct HierarchyJson<'a> {
+    #[serde(flatten)]
+    collection: crate::url_parser::UrlCollection<'a>,
+    /// Inlink/outlink counts keyed by URL
+    link_counts: HashMap<String, LinkCounts>,
+}
+
 async fn hierarchy_json(
     State(state): State<Arc<AppState>>,
     Path(id): Path<i64>,
 ) -> impl IntoResponse {
-    match state.state_manager.get_results_urls(id).await {
-        Ok(urls) => {
+    let urls_and_links = async {
+        Ok::<_, anyhow::Error>((
+            state.state_manager.get_results_urls(id).await?,
+            state.state_manager.get_link_counts(id).await?,
+        ))
+    };
+    match urls_and_links.await {
+        Ok((urls, link_counts)) => {
             let mut collection = crate::url_parser::UrlCollection::new();
             for url_str in &urls {
                 if let Ok(url_ref) = crate::url_parser::UrlRef::from_str(url_str) {
                     let _ = collection.add(url_ref);
                 }
             }
-            axum::Json(collection).into_response()
+            axum::Json(HierarchyJson {
+                collection,
+                link_counts,
+            })
+            .into_response()
         }
         Err(e) => (
             axum::http::StatusCode::INTERNAL_SERVER_ERROR,
@@ -223,159 +642,538 @@ async fn hierarchy_json(
             .into_response(),
     }
 }
-async fn start_crawl(
+
+async fn hierarchy_search(
     State(state): State<Arc<AppState>>,
-    Form(params): Form<StartParams>,
+    Path(id): Path<i64>,
+    Query(params): Query<SearchParams>,
 ) -> impl IntoResponse {
-    let mut final_config =
-        if let Some(config_path) = params.config.as_ref().filter(|s| !s.is_empty()) {
-            match crate::config::ConfigLoader::load(config_path) {
-                Ok(c) => c,
-                Err(e) => {
-                    return (
-                        axum::http::StatusCode::BAD_REQUEST,
-                        format!("Config Error: {}", e),
-                    )
-                        .into_response();
-                }
-            }
-        } else {
-            crate::config::SpiderConfig {
-                name: "adhoc".to_string(),
-                start_urls: params
-                    .url
-                    .as_ref()
-                    .map(|u| vec![u.clone()])
-                    .unwrap_or_default(),
-                ..crate::config::SpiderConfig::default()
+    match state.state_manager.search_results(id, &params.q, 200).await {
+        Ok(hits) => axum::Json(hits).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn link_analysis(
+    state: &AppState,
+    id: i64,
+) -> Result<crate::features::analysis::LinkAnalysis> {
+    Ok(crate::features::analysis::analyze(
+        id,
+        &state.state_manager.get_results_urls(id).await?,
+        &state.state_manager.get_links(id).await?,
+        &state.state_manager.get_seed_urls(id).await?,
+        &[],
+    ))
+}
+
+async fn hierarchy_analysis(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match link_analysis(&state, id).await {
+        Ok(analysis) => axum::Json(analysis).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn hierarchy_analysis_csv(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let csv = async {
+        let mut out = Vec::new();
+        link_analysis(&state, id).await?.write_csv(&mut out)?;
+        Ok::<_, anyhow::Error>(out)
+    };
+    match csv.await {
+        Ok(body) => (
+            [
+                (axum::http::header::CONTENT_TYPE, "text/csv".to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"crawl-{}-analysis.csv\"", id),
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn crawl_diff(state: &AppState, a: i64, b: i64) -> Result<crate::features::diff::CrawlDiff> {
+    Ok(crate::features::diff::diff_crawls(
+        a,
+        state.state_manager.get_results(a).await?,
+        b,
+        state.state_manager.get_results(b).await?,
+    ))
+}
+
+async fn hierarchy_diff(
+    State(state): State<Arc<AppState>>,
+    Path((a, b)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    match crawl_diff(&state, a, b).await {
+        Ok(diff) => {
+            let template = HierarchyDiffTemplate {
+                crawl_a: a,
+                crawl_b: b,
+                summary: diff.to_report().summary,
+            };
+            match template.render() {
+                Ok(html) => axum::response::Html(html).into_response(),
+                Err(e) => (
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Template error: {}", e),
+                )
+                    .into_response(),
             }
-        };
+        }
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn hierarchy_diff_json(
+    State(state): State<Arc<AppState>>,
+    Path((a, b)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    match crawl_diff(&state, a, b).await {
+        Ok(diff) => axum::Json(diff).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Database error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// The config a dashboard crawl runs with: the config file if one was given,
+/// which must be in one of `config_dirs`, with `url` replacing its start URLs.
+fn job_config(
+    url: Option<&str>,
+    config: Option<&str>,
+    config_dirs: &[PathBuf],
+) -> Result<crate::config::SpiderConfig> {
+    let mut final_config = if let Some(config_path) = config {
+        crate::config::ConfigLoader::load(auth::allowed_config(config_dirs, config_path)?)?
+    } else {
+        crate::config::SpiderConfig {
+            name: "adhoc".to_string(),
+            ..crate::config::SpiderConfig::default()
+        }
+    };
 
     // Override with URL if provided explicitly
-    if let Some(u) = params.url.as_ref().filter(|s| !s.is_empty()) {
-        final_config.start_urls = vec![u.clone()];
+    if let Some(u) = url {
+        final_config.start_urls = vec![u.to_string()];
     }
+    Ok(final_config)
+}
+
+/// Outcome of a dashboard control action: a message for the user, or the
+/// status and reason it failed.
+type ControlResult<T> = std::result::Result<T, (axum::http::StatusCode, String)>;
+
+fn database_error(e: anyhow::Error) -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Database error: {}", e),
+    )
+}
+
+/// The start URL of a crawl, from the name it was created with.
+pub fn crawl_url(name: &str) -> String {
+    name.strip_prefix("Crawl: ")
+        .or_else(|| name.strip_prefix("UI Crawl: "))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// Creates a crawl and adds it to the job queue. Returns its ID.
+async fn queue_crawl(
+    state: &AppState,
+    url: Option<String>,
+    config: Option<String>,
+) -> ControlResult<i64> {
+    let url = url.filter(|s| !s.is_empty());
+    let config = config.filter(|s| !s.is_empty());
+    let final_config =
+        job_config(url.as_deref(), config.as_deref(), &state.config_dirs).map_err(|e| {
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                format!("Config Error: {}", e),
+            )
+        })?;
 
     if final_config.start_urls.is_empty() {
-        return (axum::http::StatusCode::BAD_REQUEST, "No start URL provided").into_response();
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "No start URL provided".to_string(),
+        ));
     }
 
     let url = final_config.start_urls[0].clone();
 
-    // Create record in DB
-    let crawl_id = state
-        .state_manager
-        .create_crawl(&format!("UI Crawl: {}", url))
+    // Create record in DB and queue it
+    let queued = async {
+        let crawl_id = state
+            .state_manager
+            .create_crawl_with_status(&format!("UI Crawl: {}", url), CrawlStatus::Queued)
+            .await?;
+        state
+            .state_manager
+            .enqueue_job(crawl_id, &url, config.as_deref())
+            .await?;
+        Ok::<_, anyhow::Error>(crawl_id)
+    };
+    let crawl_id = queued.await.map_err(database_error)?;
+    state.events.publish(crawl_id, StreamEvent::Queued);
+    state.jobs_changed.notify_one();
+
+    Ok(crawl_id)
+}
+
+async fn start_crawl(
+    State(state): State<Arc<AppState>>,
+    Form(params): Form<StartParams>,
+) -> impl IntoResponse {
+    queue_crawl(&state, params.url, params.config)
         .await
-        .unwrap_or(0);
+        .map(|_| "Crawl queued")
+}
 
-    // Add to UI state
-    {
-        let mut sites = state.sites.write().unwrap();
-        sites.push(SiteDisplay {
-            id: crawl_id,
-            url: url.clone(),
-            entries: vec![],
-            finished: false,
-        });
+/// Starts queued jobs whenever a crawl slot is free, oldest first.
+async fn dispatch_jobs(state: Arc<AppState>) {
+    loop {
+        while state.running.load(Ordering::SeqCst) < state.max_crawls {
+            match state.state_manager.claim_next_job().await {
+                Ok(Some(job)) => {
+                    state.running.fetch_add(1, Ordering::SeqCst);
+                    tokio::spawn(run_job(state.clone(), job));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Failed to read the job queue: {}", e);
+                    break;
+                }
+            }
+        }
+        state.jobs_changed.notified().await;
     }
+}
+
+async fn run_job(state: Arc<AppState>, job: CrawlJob) {
+    let crawl_id = job.crawl_id;
+    state.events.publish(crawl_id, StreamEvent::Started);
+    let started = match job_config(Some(&job.url), job.config.as_deref(), &state.config_dirs) {
+        Ok(final_config) => crawl_job(&state, crawl_id, final_config).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &started {
+        tracing::error!("Crawl {} could not start: {}", crawl_id, e);
+        if let Err(e) = state
+            .state_manager
+            .set_crawl_status(crawl_id, CrawlStatus::Failed)
+            .await
+        {
+            tracing::error!("Failed to mark crawl {} failed: {}", crawl_id, e);
+        }
+    }
+
+    // The crawler has finished (rx closed) and recorded how it ended
+    state.handles.write().unwrap().remove(&crawl_id);
+    if let Err(e) = state.state_manager.finish_job(job.id).await {
+        tracing::error!("Failed to mark job {} done: {}", job.id, e);
+    }
+    // A crawl that ran has reported this itself
+    if let Err(e) = started {
+        state.events.publish(
+            crawl_id,
+            StreamEvent::Crawl(CrawlEvent::Failed {
+                url: None,
+                error: format!("Could not start: {}", e),
+            }),
+        );
+        state.events.publish(
+            crawl_id,
+            StreamEvent::Finished {
+                status: CrawlStatus::Failed.as_str().to_string(),
+            },
+        );
+    }
+    state.running.fetch_sub(1, Ordering::SeqCst);
+    state.jobs_changed.notify_one();
+}
+
+async fn crawl_job(
+    state: &Arc<AppState>,
+    crawl_id: i64,
+    final_config: crate::config::SpiderConfig,
+) -> anyhow::Result<()> {
+    let url = final_config.start_urls[0].clone();
+    let (tx, rx) = crate::crawler::channel();
+    let pause = Arc::new(AtomicBool::new(false));
+    let crawler =
+        crate::crawler::Crawler::from_config(state.state_manager.clone(), crawl_id, &final_config)?
+            .with_pause(pause.clone())
+            .with_events(Some(tx))
+            .with_metrics(Some(state.metrics.clone()));
 
     // Register cancellation token
     let cancel_token = tokio_util::sync::CancellationToken::new();
     {
-        let mut tokens = state.tokens.write().unwrap();
-        tokens.insert(crawl_id, cancel_token.clone());
+        let mut handles = state.handles.write().unwrap();
+        handles.insert(
+            crawl_id,
+            CrawlHandle {
+                cancel: cancel_token.clone(),
+                pause,
+            },
+        );
     }
 
-    // Spawn Crawler Task
-    let app_state = state.clone();
-    let state_manager = state.state_manager.clone();
-    tokio::spawn(async move {
-        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-        let crawler = crate::crawler::Crawler::new(state_manager.clone(), crawl_id, vec![]);
-
-        let selectors: HashMap<String, String> = if final_config.selectors.is_empty() {
-            let mut s = HashMap::new();
-            s.insert("title".to_string(), "title".to_string());
-            s
-        } else {
-            final_config
-                .selectors
-                .into_iter()
-                .map(|(k, v)| (k, v.to_query_string()))
-                .collect()
-        };
-
-        let respect_robots = final_config.respect_robots;
-        let delay = Some(final_config.delay_ms);
-        let concurrency = final_config.concurrency;
-
-        let crawler_cancel = cancel_token.clone();
-        tokio::spawn(async move {
-            if let Err(e) = crawler
-                .run(
-                    &url,
-                    selectors,
-                    true,
-                    respect_robots,
-                    delay,
-                    concurrency,
-                    final_config.blacklist,
-                    final_config.whitelist,
-                    final_config.max_depth,
-                    Some(tx),
-                    crawler_cancel,
-                )
-                .await
-            {
-                tracing::error!("Crawler background error: {}", e);
-            }
-        });
+    let selectors = final_config.selector_queries();
 
-        // Listen to Crawler's status updates for UI logs
-        while let Some(page_url) = rx.recv().await {
-            {
-                let mut sites = app_state.sites.write().unwrap();
-                if let Some(site) = sites.iter_mut().find(|s| s.id == crawl_id) {
-                    if site.entries.len() > 10 {
-                        site.entries.remove(0);
-                    }
-                    site.entries.push(LogEntry {
-                        status: "DONE".to_string(),
-                        url: page_url,
-                    });
-                }
-            }
-        }
+    let respect_robots = final_config.respect_robots;
+    let delay = Some(final_config.delay_ms);
+    let concurrency = final_config.concurrency;
 
-        // Mark as finished after the Crawler finishes (rx closes)
+    let crawler_cancel = cancel_token.clone();
+    let start_url = url.clone();
+    tokio::spawn(async move {
+        if let Err(e) = crawler
+            .run(
+                &start_url,
+                selectors,
+                true,
+                respect_robots,
+                delay,
+                concurrency,
+                final_config.blacklist,
+                final_config.whitelist,
+                final_config.max_depth,
+                crawler_cancel,
+            )
+            .await
         {
-            let mut sites = app_state.sites.write().unwrap();
-            let mut tokens = app_state.tokens.write().unwrap();
-
-            if let Some(site) = sites.iter_mut().find(|s| s.id == crawl_id) {
-                site.finished = true;
-            }
-            tokens.remove(&crawl_id);
+            tracing::error!("Crawler background error: {}", e);
         }
     });
 
-    "Crawl started".into_response()
+    relay(
+        state.events.clone(),
+        state.state_manager.clone(),
+        crawl_id,
+        url,
+        rx,
+    )
+    .await;
+    Ok(())
+}
+
+/// Cancels a crawl: stops it if running, takes it out of the queue if
+/// waiting, or drops its saved frontier if paused.
+async fn cancel_crawl(state: &AppState, id: i64) -> ControlResult<&'static str> {
+    if let Some(handle) = state.handles.read().unwrap().get(&id) {
+        handle.cancel.cancel();
+        return Ok("Crawl stopping...");
+    }
+
+    // Not running: a queued crawl leaves the queue, a paused one is dropped
+    let queued = state
+        .state_manager
+        .get_queued_jobs()
+        .await
+        .map_err(database_error)?
+        .into_iter()
+        .find(|j| j.crawl_id == id);
+    let paused = state
+        .state_manager
+        .get_crawl(id)
+        .await
+        .map_err(database_error)?
+        .is_some_and(|c| c.status == CrawlStatus::Paused.as_str());
+    if queued.is_none() && !paused {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Crawl not found or already stopped".to_string(),
+        ));
+    }
+
+    if let Some(job) = &queued
+        && !state
+            .state_manager
+            .cancel_queued_job(job.id)
+            .await
+            .map_err(database_error)?
+    {
+        // The dispatcher started it since the queue was read
+        if let Some(handle) = state.handles.read().unwrap().get(&id) {
+            handle.cancel.cancel();
+            return Ok("Crawl stopping...");
+        }
+        return Err((
+            axum::http::StatusCode::CONFLICT,
+            "Crawl is starting, try again".to_string(),
+        ));
+    }
+    state
+        .state_manager
+        .set_crawl_status(id, CrawlStatus::Cancelled)
+        .await
+        .map_err(database_error)?;
+    state.events.publish(
+        id,
+        StreamEvent::Finished {
+            status: CrawlStatus::Cancelled.as_str().to_string(),
+        },
+    );
+    Ok("Crawl cancelled")
+}
+
+/// Stops fetching and saves the crawl's frontier, so it can be resumed.
+fn pause(state: &AppState, id: i64) -> ControlResult<&'static str> {
+    if let Some(handle) = state.handles.read().unwrap().get(&id) {
+        handle.pause.store(true, Ordering::SeqCst);
+        handle.cancel.cancel();
+        Ok("Crawl pausing...")
+    } else {
+        Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Crawl is not running".to_string(),
+        ))
+    }
+}
+
+/// Queues a paused crawl again under the same ID; it continues from its
+/// saved frontier.
+async fn resume(state: &AppState, id: i64) -> ControlResult<&'static str> {
+    let paused = state
+        .state_manager
+        .get_crawl(id)
+        .await
+        .map_err(database_error)?
+        .is_some_and(|c| c.status == CrawlStatus::Paused.as_str());
+    if !paused {
+        return Err((
+            axum::http::StatusCode::NOT_FOUND,
+            "Crawl is not paused".to_string(),
+        ));
+    }
+    // Crawls started outside the dashboard have no job, and without its
+    // config the crawl would continue with no selectors
+    let Some(job) = state
+        .state_manager
+        .get_crawl_job(id)
+        .await
+        .map_err(database_error)?
+    else {
+        return Err((
+            axum::http::StatusCode::CONFLICT,
+            "Crawl wasn't started from the dashboard, so its config is unknown".to_string(),
+        ));
+    };
+    state
+        .state_manager
+        .enqueue_job(id, &job.url, job.config.as_deref())
+        .await
+        .map_err(database_error)?;
+    state.events.publish(id, StreamEvent::Queued);
+    state.jobs_changed.notify_one();
+    Ok("Crawl resumed")
 }
 
 async fn stop_crawl(
     State(state): State<Arc<AppState>>,
     Form(params): Form<StopParams>,
 ) -> impl IntoResponse {
-    let tokens = state.tokens.read().unwrap();
-    if let Some(token) = tokens.get(&params.id) {
-        token.cancel();
-        "Crawl stopping...".into_response()
-    } else {
-        (
-            axum::http::StatusCode::NOT_FOUND,
-            "Crawl not found or already stopped",
-        )
-            .into_response()
+    cancel_crawl(&state, params.id).await
+}
+
+async fn pause_crawl(
+    State(state): State<Arc<AppState>>,
+    Form(params): Form<StopParams>,
+) -> impl IntoResponse {
+    pause(&state, params.id)
+}
+
+async fn resume_crawl(
+    State(state): State<Arc<AppState>>,
+    Form(params): Form<StopParams>,
+) -> impl IntoResponse {
+    resume(&state, params.id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateManager;
+
+    #[tokio::test]
+    async fn test_stats_show_crawls_from_other_processes() -> Result<()> {
+        let db = tempfile::NamedTempFile::new()?;
+        // Another process crawling into the same database
+        let other = StateManager::new(db.path()).await?;
+        let active = other.create_crawl("Crawl: http://a.test/").await?;
+        for (path, status) in [("1", 200), ("2", 404), ("3", 200)] {
+            other
+                .save_page_info(
+                    active,
+                    &PageInfo {
+                        url: format!("http://a.test/{}", path),
+                        final_url: format!("http://a.test/{}", path),
+                        status,
+                        canonical: None,
+                        redirects: vec![],
+                        content_hash: None,
+                        simhash: None,
+                        duplicate_of: None,
+                    },
+                )
+                .await?;
+        }
+        let done = other.create_crawl("Crawl: http://b.test/").await?;
+        other.complete_crawl(done).await?;
+
+        let store = Arc::new(StateManager::new(db.path()).await?);
+        let state = AppState::new(store, None, 1, Arc::default(), vec![]);
+        let (sites, older) = load_sites(&state).await?;
+        assert!(older.is_empty());
+
+        assert_eq!(
+            sites.iter().map(|s| s.id).collect::<Vec<_>>(),
+            [done, active]
+        );
+        let running = &sites[1];
+        assert_eq!(running.url, "http://a.test/");
+        assert!(!running.finished && !running.controllable);
+        assert_eq!((running.counts.pages, running.counts.failed_pages), (3, 1));
+        let log: Vec<_> = running
+            .entries
+            .iter()
+            .map(|e| (e.status.as_str(), e.kind))
+            .collect();
+        assert_eq!(log, [("200", "DONE"), ("404", "ERR"), ("200", "DONE")]);
+        assert!(sites[0].finished);
+        assert_eq!(sites[0].status, "completed");
+        Ok(())
     }
 }
@@ -0,0 +1,422 @@
+//! OpenAPI 3 document for `/api/v1`, generated from the API's handler
+//! signatures and response types so it can't drift from the code.
+//!
+//! Every type that crosses the API implements `Schema`, usually through
+//! `schema_object!`. Handlers describe themselves through `Documented`: their
+//! `Query`/`Json` extractors become parameters and request bodies, their
+//! return type the responses.
+
+use super::api::{self, Endpoint};
+use crate::state::{CrawlCounts, FrontierEntry, PageInfo, RedirectHop, StoredResult};
+use askama::Template;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use serde_json::{Map, Value, json};
+
+/// JSON Schema (OpenAPI 3.0 dialect) of a type as serde serializes it.
+pub(super) trait Schema {
+    fn schema() -> Value;
+
+    /// Whether a field of this type may be null or left out.
+    const OPTIONAL: bool = false;
+}
+
+macro_rules! scalar_schema {
+    ($schema:tt: $($ty:ty),*) => {
+        $(impl Schema for $ty {
+            fn schema() -> Value {
+                json!($schema)
+            }
+        })*
+    };
+}
+
+scalar_schema!({ "type": "integer", "format": "int64" }: i64, u64);
+scalar_schema!({ "type": "integer" }: u16, usize);
+scalar_schema!({ "type": "boolean" }: bool);
+scalar_schema!({ "type": "string" }: String, &'static str);
+scalar_schema!({ "description": "Any JSON value" }: Value);
+
+impl<T: Schema> Schema for Option<T> {
+    fn schema() -> Value {
+        let mut schema = T::schema();
+        schema["nullable"] = true.into();
+        schema
+    }
+
+    const OPTIONAL: bool = true;
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": T::schema() })
+    }
+}
+
+/// A property of an object schema.
+pub(super) struct Field {
+    pub name: &'static str,
+    pub schema: Value,
+    pub optional: bool,
+    pub description: Option<&'static str>,
+}
+
+/// Schema of an object with `fields`; every field that isn't optional is
+/// required.
+pub(super) fn object(title: &str, fields: Vec<Field>) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for field in fields {
+        let mut schema = field.schema;
+        if let Some(description) = field.description {
+            schema["description"] = description.into();
+        }
+        if !field.optional {
+            required.push(field.name);
+        }
+        properties.insert(field.name.to_string(), schema);
+    }
+    json!({
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// Implements `Schema` for a struct from a list of its fields, each with an
+/// optional description. The list is checked against the struct: adding,
+/// removing or retyping a field without updating it fails to compile.
+macro_rules! schema_object {
+    ($name:ident { $($field:ident: $ty:ty $(=> $description:literal)?),* $(,)? }) => {
+        impl $crate::ui::openapi::Schema for $name {
+            fn schema() -> serde_json::Value {
+                #[allow(dead_code)]
+                fn same_fields(value: &$name) {
+                    let $name { $($field),* } = value;
+                    $(let _: &$ty = $field;)*
+                }
+
+                $crate::ui::openapi::object(
+                    stringify!($name),
+                    vec![$($crate::ui::openapi::Field {
+                        name: stringify!($field),
+                        schema: <$ty as $crate::ui::openapi::Schema>::schema(),
+                        optional: <$ty as $crate::ui::openapi::Schema>::OPTIONAL,
+                        description: None $(.or(Some($description)))?,
+                    }),*],
+                )
+            }
+        }
+    };
+}
+pub(super) use schema_object;
+
+schema_object!(StoredResult {
+    url: String,
+    data: Value => "Fields extracted by the crawl's selectors",
+    version: i64 => "Starts at 1 and goes up each time the page changes",
+    found_at: String => "When this version was stored",
+});
+
+schema_object!(FrontierEntry {
+    url: String,
+    depth: usize,
+    status: String => "pending, processing, completed or failed",
+    added_at: String,
+});
+
+schema_object!(CrawlCounts {
+    results: i64,
+    pages: i64 => "Pages fetched, whatever their status",
+    failed_pages: i64 => "Pages that returned 4xx or 5xx, or no response",
+    frontier_pending: i64,
+    links: i64,
+});
+
+schema_object!(RedirectHop {
+    url: String,
+    status: u16,
+    location: String,
+});
+
+schema_object!(PageInfo {
+    url: String,
+    final_url: String => "Where redirects ended up",
+    status: u16 => "Status of the final response, 0 when none arrived",
+    canonical: Option<String>,
+    redirects: Vec<RedirectHop>,
+    content_hash: Option<String> => "SHA-256 of the response body",
+    simhash: Option<u64>,
+    duplicate_of: Option<String>,
+});
+
+/// What a handler takes and returns, as an OpenAPI operation.
+#[derive(Default)]
+pub(super) struct Operation {
+    pub parameters: Vec<Value>,
+    pub request_body: Option<Value>,
+    pub responses: Map<String, Value>,
+}
+
+impl Operation {
+    /// Adds a JSON response with the given status.
+    pub fn response(&mut self, status: &str, description: &str, schema: Value) {
+        self.responses.insert(
+            status.to_string(),
+            json!({
+                "description": description,
+                "content": { "application/json": { "schema": schema } },
+            }),
+        );
+    }
+}
+
+/// A handler argument's part of the operation.
+pub(super) trait ArgDoc {
+    fn describe(_op: &mut Operation, _path: &str) {}
+}
+
+/// A handler return type's part of the operation.
+pub(super) trait ResponseDoc {
+    fn describe(op: &mut Operation);
+}
+
+impl<S> ArgDoc for State<S> {}
+
+impl<T: Schema> ArgDoc for Path<T> {
+    fn describe(op: &mut Operation, path: &str) {
+        for segment in path.split('/') {
+            if let Some(name) = segment.strip_prefix(':') {
+                op.parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": T::schema(),
+                }));
+            }
+        }
+    }
+}
+
+impl<T: Schema> ArgDoc for Query<T> {
+    fn describe(op: &mut Operation, _path: &str) {
+        let schema = T::schema();
+        let required = schema["required"].as_array().cloned().unwrap_or_default();
+        let Some(properties) = schema["properties"].as_object() else {
+            return;
+        };
+        for (name, property) in properties {
+            // Optional parameters are simply left out rather than null
+            let mut property = property.clone();
+            let description = property.as_object_mut().and_then(|p| {
+                p.remove("nullable");
+                p.remove("description")
+            });
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&name.as_str().into()),
+                "schema": property,
+            });
+            if let Some(description) = description {
+                parameter["description"] = description;
+            }
+            op.parameters.push(parameter);
+        }
+    }
+}
+
+impl<T: Schema> ArgDoc for Json<T> {
+    fn describe(op: &mut Operation, _path: &str) {
+        op.request_body = Some(json!({
+            "required": true,
+            "content": { "application/json": { "schema": T::schema() } },
+        }));
+    }
+}
+
+/// Implemented for every handler whose arguments and return type can be
+/// described; `Args` is the tuple of argument types.
+pub(super) trait Documented<Args> {
+    fn describe(op: &mut Operation, path: &str);
+}
+
+macro_rules! documented_handler {
+    ($($arg:ident),*) => {
+        impl<F, Fut, $($arg),*> Documented<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Fut,
+            Fut: std::future::Future,
+            Fut::Output: ResponseDoc,
+            $($arg: ArgDoc,)*
+        {
+            fn describe(op: &mut Operation, path: &str) {
+                $(<$arg as ArgDoc>::describe(op, path);)*
+                <Fut::Output as ResponseDoc>::describe(op);
+            }
+        }
+    };
+}
+
+documented_handler!(A);
+documented_handler!(A, B);
+documented_handler!(A, B, C);
+
+/// The OpenAPI document for `endpoints`, which are mounted under `/api/v1`.
+pub(super) fn document(endpoints: &[Endpoint]) -> Value {
+    let mut paths = Map::new();
+    for endpoint in endpoints {
+        // `:id` in axum is `{id}` in OpenAPI
+        let path = endpoint
+            .path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut operation = json!({
+            "operationId": endpoint.operation_id,
+            "summary": endpoint.summary,
+            "parameters": endpoint.operation.parameters,
+            "responses": endpoint.operation.responses,
+        });
+        if let Some(body) = &endpoint.operation.request_body {
+            operation["requestBody"] = body.clone();
+        }
+        paths
+            .entry(format!("/api/v1{}", path))
+            .or_insert_with(|| json!({}))[endpoint.method] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "spider-cli dashboard API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+pub(super) async fn openapi_json() -> impl IntoResponse {
+    Json(document(&api::endpoints()))
+}
+
+struct OperationDisplay {
+    method: String,
+    path: String,
+    summary: String,
+    parameters: Vec<ParameterDisplay>,
+    request: Option<String>,
+    responses: Vec<(String, String)>,
+}
+
+struct ParameterDisplay {
+    name: String,
+    location: String,
+    kind: String,
+    required: bool,
+    description: String,
+}
+
+#[derive(Template)]
+#[template(path = "api_docs.html")]
+struct ApiDocsTemplate {
+    operations: Vec<OperationDisplay>,
+}
+
+fn pretty(schema: &Value) -> String {
+    serde_json::to_string_pretty(schema).unwrap_or_default()
+}
+
+/// A readable page of the generated document, so the API can be browsed
+/// without any client tooling.
+pub(super) async fn api_docs() -> impl IntoResponse {
+    let document = document(&api::endpoints());
+    let mut operations = Vec::new();
+    for (path, methods) in document["paths"].as_object().into_iter().flatten() {
+        for (method, operation) in methods.as_object().into_iter().flatten() {
+            let text = |v: &Value| v.as_str().unwrap_or_default().to_string();
+            operations.push(OperationDisplay {
+                method: method.to_uppercase(),
+                path: path.clone(),
+                summary: text(&operation["summary"]),
+                parameters: operation["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|p| ParameterDisplay {
+                        name: text(&p["name"]),
+                        location: text(&p["in"]),
+                        kind: text(&p["schema"]["type"]),
+                        required: p["required"].as_bool().unwrap_or(false),
+                        description: text(&p["description"]),
+                    })
+                    .collect(),
+                request: operation
+                    .get("requestBody")
+                    .map(|b| pretty(&b["content"]["application/json"]["schema"])),
+                responses: operation["responses"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(status, r)| {
+                        (
+                            status.clone(),
+                            pretty(&r["content"]["application/json"]["schema"]),
+                        )
+                    })
+                    .collect(),
+            });
+        }
+    }
+
+    match (ApiDocsTemplate { operations }).render() {
+        Ok(html) => axum::response::Html(html).into_response(),
+        Err(e) => (
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Template error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_describes_handlers() {
+        let document = document(&api::endpoints());
+
+        let results = &document["paths"]["/api/v1/crawls/{id}/results"]["get"];
+        assert_eq!(results["operationId"], "list_results");
+        let parameters: Vec<&str> = results["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        for name in ["id", "url", "since", "offset", "limit"] {
+            assert!(parameters.contains(&name), "missing {}", name);
+        }
+        let page = &results["responses"]["200"]["content"]["application/json"]["schema"];
+        assert_eq!(page["title"], "StoredResultPage");
+        assert_eq!(
+            page["properties"]["items"]["items"]["title"],
+            "StoredResult"
+        );
+
+        let create = &document["paths"]["/api/v1/crawls"]["post"];
+        assert!(create["requestBody"].is_object());
+        assert!(create["responses"]["201"].is_object());
+        assert!(create["responses"]["default"].is_object());
+    }
+}
@@ -69,7 +69,14 @@ impl<'a> UrlRef<'a> {
         let path_start = if !hostname.is_empty() {
             full_url.find(hostname).unwrap() + hostname.len()
         } else {
-            full_url.find(':').unwrap_or(0) + 1
+            // No host (e.g. `file:///docs/index.html`): the path follows the
+            // scheme and its empty `//` authority, if present.
+            let after_scheme = full_url.find(':').unwrap_or(0) + 1;
+            if full_url[after_scheme..].starts_with("//") {
+                after_scheme + 2
+            } else {
+                after_scheme
+            }
         };
 
         let query_start = full_url.find('?').unwrap_or(full_url.len());
@@ -121,6 +128,7 @@ impl<'a> UrlRef<'a> {
             _ => {
                 // Heuristic: last two parts are the domain (e.g., example.com)
                 // In production, one might use a Public Suffix List
+                #[allow(clippy::collapsible_if)]
                 if let Some(pos) = hostname.rfind('.') {
                     if let Some(prev_pos) = hostname[..pos].rfind('.') {
                         let domain_start = prev_pos + 1;
@@ -304,11 +312,17 @@ impl<'a> UrlCollection<'a> {
 
     pub fn add(&mut self, url: UrlRef<'a>) -> Result<()> {
         let normalized = url.normalize();
+        #[allow(clippy::map_entry)]
         if !self.unique_urls.contains_key(&normalized) {
             self.unique_urls.insert(normalized, url);
 
-            // Add to hierarchy using hostname as root
-            let host = url.hostname;
+            // Add to hierarchy using hostname as root; local files group
+            // under their scheme
+            let host = if url.hostname.is_empty() {
+                url.protocol
+            } else {
+                url.hostname
+            };
             let root = self
                 .hierarchies
                 .entry(host)
@@ -403,6 +417,22 @@ mod tests {
         assert_eq!(u_root.domain, "example.com");
     }
 
+    #[test]
+    fn test_file_urls() {
+        let u = UrlRef::from_str("file:///srv/docs/guide/index.html#top").unwrap();
+        assert_eq!(u.hostname, "");
+        assert_eq!(u.path, "/srv/docs/guide/index.html");
+        assert_eq!(u.depth, 4);
+        assert_eq!(
+            normalize_url("file:///srv/docs/guide/"),
+            "file:///srv/docs/guide"
+        );
+
+        let mut collection = UrlCollection::new();
+        collection.add(u).unwrap();
+        assert!(collection.hierarchies.contains_key("file"));
+    }
+
     #[test]
     fn test_hierarchical_tree() {
         let mut collection = UrlCollection::new();